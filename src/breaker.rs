@@ -0,0 +1,258 @@
+use reqwest::Client;
+use rusqlite::{params, Connection};
+
+use crate::config::{self, RiskConfig};
+use crate::{cancel_open_orders, dry_run, events, get_price, ledger, portfolio};
+
+/// Bezpiecznik konta: migawki wyniku (PnL) i kapitału oraz stan wyłącznika
+pub fn setup(conn: &Connection) {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS equity_snapshots (
+            id INTEGER PRIMARY KEY,
+            timestamp TEXT NOT NULL DEFAULT (datetime('now')),
+            pnl REAL NOT NULL,
+            equity REAL NOT NULL
+        )",
+        [],
+    ).expect("Failed to create equity_snapshots table");
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS circuit_breaker (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            tripped INTEGER NOT NULL DEFAULT 0,
+            reason TEXT,
+            changed_at TEXT NOT NULL DEFAULT (datetime('now'))
+        )",
+        [],
+    ).expect("Failed to create circuit_breaker table");
+    conn.execute("INSERT OR IGNORE INTO circuit_breaker (id) VALUES (1)", [])
+        .expect("Failed to initialize circuit breaker");
+}
+
+/// Powód zadziałania bezpiecznika, jeśli gridy są wstrzymane
+pub fn tripped(db: &Connection) -> Option<String> {
+    db.query_row(
+        "SELECT reason FROM circuit_breaker WHERE id = 1 AND tripped = 1",
+        [],
+        |row| row.get::<_, Option<String>>(0),
+    ).ok().map(|reason| reason.unwrap_or_default())
+}
+
+/// Łączny wynik (zrealizowany + niezrealizowany) i kapitał (przydział + wynik) wszystkich par
+async fn current_equity(db: &mut Connection, client: &Client) -> Result<(f64, f64), String> {
+    let symbols: Vec<String> = {
+        let mut stmt = db.prepare("SELECT DISTINCT symbol FROM capital ORDER BY symbol ASC")
+            .expect("Failed to prepare statement");
+        stmt.query_map([], |row| row.get(0))
+            .expect("Failed to query capital pairs")
+            .filter_map(Result::ok)
+            .collect()
+    };
+
+    let (mut pnl, mut allocation) = (0.0, 0.0);
+    for symbol in symbols {
+        let position = portfolio::load_position(db, &symbol);
+        let price = if position.held_qty > 0.0 {
+            get_price(&symbol, client).await.map_err(|_| format!("price unavailable for {}", symbol))?
+        } else {
+            0.0
+        };
+        pnl += position.realized_pnl + position.unrealized_pnl(price);
+        allocation += ledger::balances(db, &symbol).allocation;
+    }
+    Ok((pnl, allocation + pnl))
+}
+
+/// Migawka z początku okna 24h (nie starsza niż ostatnie uzbrojenie) i szczyt kapitału od uzbrojenia
+fn reference_points(db: &Connection) -> (Option<f64>, Option<f64>) {
+    let day_start_pnl = db.query_row(
+        "SELECT pnl FROM equity_snapshots
+         WHERE timestamp >= MAX(datetime('now', '-1 day'), (SELECT changed_at FROM circuit_breaker WHERE id = 1))
+         ORDER BY id ASC LIMIT 1",
+        [],
+        |row| row.get(0),
+    ).ok();
+    let peak_equity = db.query_row(
+        "SELECT MAX(equity) FROM equity_snapshots
+         WHERE timestamp >= (SELECT changed_at FROM circuit_breaker WHERE id = 1)",
+        [],
+        |row| row.get::<_, Option<f64>>(0),
+    ).ok().flatten();
+    (day_start_pnl, peak_equity)
+}
+
+/// Powód zadziałania, jeśli wynik przekroczył limit dziennej straty lub obsunięcia od szczytu
+fn trip_reason(risk: &RiskConfig, pnl: f64, equity: f64, (day_start_pnl, peak_equity): (Option<f64>, Option<f64>)) -> Option<String> {
+    let daily_loss = day_start_pnl.map(|start| start - pnl).unwrap_or(0.0);
+    let drawdown = match peak_equity {
+        Some(peak) if peak > 0.0 => (peak - equity) / peak,
+        _ => 0.0,
+    };
+
+    match (risk.max_daily_loss, risk.max_drawdown_pct) {
+        (Some(limit), _) if daily_loss >= limit => {
+            Some(format!("24h loss {:.2} reached max_daily_loss {:.2}", daily_loss, limit))
+        }
+        (_, Some(limit)) if drawdown >= limit => Some(format!(
+            "drawdown {:.2}% from equity peak {:.2} reached max_drawdown_pct {:.2}%",
+            drawdown * 100.0, peak_equity.unwrap_or(0.0), limit * 100.0
+        )),
+        _ => None,
+    }
+}
+
+pub async fn trip(db: &mut Connection, client: &Client, reason: &str) {
+    println!("🚨 Circuit breaker tripped: {} – all grids paused (run `rearm` to resume)", reason);
+    db.execute(
+        "UPDATE circuit_breaker SET tripped = 1, reason = ?1, changed_at = datetime('now') WHERE id = 1",
+        params![reason],
+    ).expect("Failed to update circuit breaker");
+    events::record(db, None, "CIRCUIT_BREAKER", reason);
+
+    if !config::config().risk.cancel_buys_on_breaker {
+        return;
+    }
+    if dry_run() {
+        println!("🧪 [DRY-RUN] Open buy orders would be canceled (nothing was sent)");
+        return;
+    }
+
+    let symbols: Vec<String> = {
+        let mut stmt = db.prepare("SELECT DISTINCT symbol FROM capital ORDER BY symbol ASC")
+            .expect("Failed to prepare statement");
+        stmt.query_map([], |row| row.get(0))
+            .expect("Failed to query capital pairs")
+            .filter_map(Result::ok)
+            .collect()
+    };
+    for symbol in symbols {
+        if let Err(e) = cancel_open_orders(db, client, &symbol, Some("BUY")).await {
+            println!("❌ Failed to cancel buy orders for {}: {}", symbol, e);
+        }
+    }
+}
+
+/// Zapisuje migawkę wyniku i sprawdza limity dziennej straty i obsunięcia (wywoływane z pętli monitora)
+pub async fn check(db: &mut Connection, client: &Client) {
+    let risk = &config::config().risk;
+    if tripped(db).is_some() || (risk.max_daily_loss.is_none() && risk.max_drawdown_pct.is_none()) {
+        return;
+    }
+
+    let (pnl, equity) = match current_equity(db, client).await {
+        Ok(values) => values,
+        Err(e) => {
+            println!("⚠️ Circuit breaker check skipped: {}", e);
+            return;
+        }
+    };
+    db.execute("INSERT INTO equity_snapshots (pnl, equity) VALUES (?1, ?2)", params![pnl, equity])
+        .expect("Failed to record equity snapshot");
+
+    if let Some(reason) = trip_reason(risk, pnl, equity, reference_points(db)) {
+        trip(db, client, &reason).await;
+    }
+}
+
+/// Ręczne ponowne uzbrojenie – wznawia gridy; okno 24h i szczyt kapitału liczone są od nowa
+pub fn rearm(db: &Connection) {
+    match tripped(db) {
+        None => println!("ℹ️ Circuit breaker is armed, nothing to do."),
+        Some(reason) => {
+            db.execute(
+                "UPDATE circuit_breaker SET tripped = 0, reason = NULL, changed_at = datetime('now') WHERE id = 1",
+                [],
+            ).expect("Failed to update circuit breaker");
+            events::record(db, None, "CIRCUIT_BREAKER_REARM", &format!("previous trip: {}", reason));
+            println!("✅ Circuit breaker re-armed – grids may place orders again.");
+        }
+    }
+}
+
+pub fn show_status(db: &Connection) {
+    match tripped(db) {
+        Some(reason) => println!("Circuit breaker: 🔴 TRIPPED ({}) – run `rearm` to resume", reason),
+        None => println!("Circuit breaker: 🟢 armed"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn risk(max_daily_loss: Option<f64>, max_drawdown_pct: Option<f64>) -> RiskConfig {
+        RiskConfig { max_daily_loss, max_drawdown_pct, ..RiskConfig::default() }
+    }
+
+    fn snapshot(db: &Connection, age: &str, pnl: f64, equity: f64) {
+        db.execute(
+            "INSERT INTO equity_snapshots (timestamp, pnl, equity) VALUES (datetime('now', ?1), ?2, ?3)",
+            params![age, pnl, equity],
+        ).unwrap();
+    }
+
+    #[test]
+    fn daily_loss_limit_trips() {
+        let risk = risk(Some(50.0), None);
+        assert!(trip_reason(&risk, -40.0, 960.0, (Some(0.0), Some(1000.0))).is_none());
+
+        let reason = trip_reason(&risk, -50.0, 950.0, (Some(0.0), Some(1000.0))).unwrap();
+        assert!(reason.contains("max_daily_loss"), "{}", reason);
+    }
+
+    #[test]
+    fn drawdown_from_peak_trips() {
+        let risk = risk(None, Some(0.1));
+        assert!(trip_reason(&risk, 0.0, 1090.0, (None, Some(1200.0))).is_none());
+
+        let reason = trip_reason(&risk, 0.0, 1080.0, (None, Some(1200.0))).unwrap();
+        assert!(reason.contains("max_drawdown_pct"), "{}", reason);
+    }
+
+    #[test]
+    fn no_reference_snapshots_never_trip() {
+        assert!(trip_reason(&risk(Some(1.0), Some(0.01)), -500.0, 0.0, (None, None)).is_none());
+    }
+
+    #[test]
+    fn reference_points_ignore_snapshots_before_the_last_rearm() {
+        let db = Connection::open_in_memory().unwrap();
+        crate::create_schema(&db);
+        snapshot(&db, "-3 hours", 100.0, 2000.0);
+        db.execute("UPDATE circuit_breaker SET changed_at = datetime('now', '-2 hours') WHERE id = 1", []).unwrap();
+        snapshot(&db, "-1 hours", 20.0, 1200.0);
+        snapshot(&db, "-30 minutes", 10.0, 1100.0);
+
+        assert_eq!(reference_points(&db), (Some(20.0), Some(1200.0)));
+    }
+
+    #[test]
+    fn daily_window_starts_at_most_24h_ago() {
+        let db = Connection::open_in_memory().unwrap();
+        crate::create_schema(&db);
+        db.execute("UPDATE circuit_breaker SET changed_at = datetime('now', '-3 days') WHERE id = 1", []).unwrap();
+        snapshot(&db, "-2 days", 100.0, 1500.0);
+        snapshot(&db, "-12 hours", 40.0, 1000.0);
+
+        assert_eq!(reference_points(&db), (Some(40.0), Some(1500.0)));
+    }
+
+    #[tokio::test]
+    async fn trip_pauses_until_rearmed() {
+        config::init_for_tests();
+        let mut db = Connection::open_in_memory().unwrap();
+        crate::create_schema(&db);
+        assert_eq!(tripped(&db), None);
+
+        trip(&mut db, &Client::new(), "test limit").await;
+        assert_eq!(tripped(&db).as_deref(), Some("test limit"));
+
+        rearm(&db);
+        assert_eq!(tripped(&db), None);
+        let events: i64 = db.query_row(
+            "SELECT COUNT(*) FROM events WHERE event IN ('CIRCUIT_BREAKER', 'CIRCUIT_BREAKER_REARM')",
+            [],
+            |row| row.get(0),
+        ).unwrap();
+        assert_eq!(events, 2);
+    }
+}
@@ -0,0 +1,616 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::sync::OnceLock;
+use serde::Deserialize;
+
+use crate::credentials;
+
+/// Konfiguracja bota wczytywana raz przy starcie z pliku TOML
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BotConfig {
+    #[serde(default)]
+    pub api: ApiConfig,
+    #[serde(default)]
+    pub environment: EnvironmentConfig,
+    #[serde(default)]
+    pub risk: RiskConfig,
+    #[serde(default)]
+    pub volatility: VolatilityConfig,
+    #[serde(default)]
+    pub fees: FeeConfig,
+    #[serde(default)]
+    pub pairs: Vec<PairConfig>,
+}
+
+/// Klucze w pliku są opcjonalne – mogą pochodzić ze zmiennych środowiskowych lub keystore
+#[derive(Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiConfig {
+    pub api_key: Option<String>,
+    pub secret_key: Option<String>,
+    /// Ścieżka do zaszyfrowanego keystore (hasło z BINANCE_KEYSTORE_PASSPHRASE lub z klawiatury)
+    pub keystore: Option<String>,
+}
+
+impl fmt::Debug for ApiConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiConfig")
+            .field("api_key", &self.api_key.as_ref().map(|_| "***"))
+            .field("secret_key", &self.secret_key.as_ref().map(|_| "***"))
+            .field("keystore", &self.keystore)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnvironmentConfig {
+    /// `mainnet` lub `testnet`
+    pub profile: Option<String>,
+    /// Dowolny adres API (np. lokalny mock) – ma pierwszeństwo przed profilem
+    pub base_url: Option<String>,
+}
+
+/// Limity ryzyka sprawdzane przed każdym zleceniem (kwoty w walucie quote)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct RiskConfig {
+    /// Otwarte zlecenia łącznie na wszystkich parach
+    pub max_open_orders: usize,
+    pub max_open_orders_per_pair: usize,
+    pub max_order_notional: f64,
+    /// Wartość zapasu pary po cenie rynkowej + otwarte kupna (brak = bez limitu)
+    pub max_inventory_value_per_pair: Option<f64>,
+    /// Zapas + otwarte kupna na wszystkich parach (brak = bez limitu)
+    pub max_total_exposure: Option<f64>,
+    /// Minimalna odległość zlecenia oczekującego od ceny rynkowej (0.002 = 0.2%)
+    pub min_price_distance: f64,
+    /// Strata (zrealizowana + niezrealizowana) w ciągu ostatnich 24h, po której gridy są wstrzymywane
+    pub max_daily_loss: Option<f64>,
+    /// Spadek kapitału od szczytu (0.1 = 10%), po którym gridy są wstrzymywane
+    pub max_drawdown_pct: Option<f64>,
+    /// Po zadziałaniu bezpiecznika anuluj otwarte zlecenia kupna
+    pub cancel_buys_on_breaker: bool,
+}
+
+impl Default for RiskConfig {
+    fn default() -> Self {
+        RiskConfig {
+            max_open_orders: 5,
+            max_open_orders_per_pair: 5,
+            max_order_notional: 1000.0,
+            max_inventory_value_per_pair: None,
+            max_total_exposure: None,
+            min_price_distance: 0.0,
+            max_daily_loss: None,
+            max_drawdown_pct: None,
+            cancel_buys_on_breaker: false,
+        }
+    }
+}
+
+/// Prowizje konta (ułamek wartości wykonania) używane w kalkulatorze opłacalności gridu
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct FeeConfig {
+    /// Zlecenia oczekujące (poziomy gridu)
+    pub maker: f64,
+    /// Zlecenia wykonane od razu (kupna po cenie rynkowej przy starcie)
+    pub taker: f64,
+}
+
+impl Default for FeeConfig {
+    fn default() -> Self {
+        FeeConfig { maker: 0.001, taker: 0.001 }
+    }
+}
+
+/// Wstrzymanie nowych zleceń kupna po gwałtownym ruchu ceny (świece 1m)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct VolatilityConfig {
+    /// Spadek od najwyższej ceny w oknie `window_minutes` (0.05 = 5%); brak = wyłączone
+    pub max_drop_pct: Option<f64>,
+    pub window_minutes: usize,
+    /// Ostatni zakres świecy większy niż ATR * mnożnik; brak = wyłączone
+    pub atr_spike_multiplier: Option<f64>,
+    pub atr_period: usize,
+    /// Jak długo po wykryciu ruchu nie składamy nowych kupn
+    pub cooldown_minutes: u32,
+}
+
+impl Default for VolatilityConfig {
+    fn default() -> Self {
+        VolatilityConfig {
+            max_drop_pct: None,
+            window_minutes: 15,
+            atr_spike_multiplier: None,
+            atr_period: 14,
+            cooldown_minutes: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpacingType {
+    /// Poziomy co stały procent ceny startowej: p * (1 + k * step)
+    Arithmetic,
+    /// Poziomy co stały procent poprzedniego poziomu: p * (1 + step)^k
+    Geometric,
+}
+
+impl SpacingType {
+    pub const ALL: [SpacingType; 2] = [SpacingType::Arithmetic, SpacingType::Geometric];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SpacingType::Arithmetic => "arithmetic",
+            SpacingType::Geometric => "geometric",
+        }
+    }
+}
+
+/// Co dzieje się ze zrealizowanym zyskiem pary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProfitMode {
+    /// Zysk zostaje w kapitale pary – kolejne zlecenia są odpowiednio większe
+    Compound,
+    /// Zysk z każdej sprzedaży trafia do puli `harvested`, której grid nie wydaje;
+    /// strata sprzedaży jest pokrywana z tej puli (do jej wysokości)
+    Sweep,
+}
+
+impl ProfitMode {
+    pub const ALL: [ProfitMode; 2] = [ProfitMode::Compound, ProfitMode::Sweep];
+
+    /// Nazwa używana w pliku konfiguracyjnym i kolumnie `capital.profit_mode`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProfitMode::Compound => "compound",
+            ProfitMode::Sweep => "sweep",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<ProfitMode> {
+        ProfitMode::ALL.into_iter().find(|mode| mode.as_str() == name.trim().to_lowercase())
+    }
+}
+
+/// Podział kapitału między poziomy kupna gridu
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SizingScheme {
+    /// Ta sama wartość w quote na poziom: `order_size` przydziału
+    EqualQuote,
+    /// Ta sama ilość base na poziom (łącznie `order_size` × liczba poziomów przydziału)
+    EqualBase,
+    /// Więcej na niższych poziomach – waga rośnie o 1 z każdym poziomem w dół
+    Pyramid,
+    /// Cały przydział równo na wszystkie poziomy (bez `order_size`)
+    Full,
+}
+
+impl SizingScheme {
+    pub const ALL: [SizingScheme; 4] = [SizingScheme::EqualQuote, SizingScheme::EqualBase, SizingScheme::Pyramid, SizingScheme::Full];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SizingScheme::EqualQuote => "equal_quote",
+            SizingScheme::EqualBase => "equal_base",
+            SizingScheme::Pyramid => "pyramid",
+            SizingScheme::Full => "full",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StrategyKind {
+    /// Stały zakres: kupna od razu z poziomami sprzedaży powyżej i kupna poniżej ceny
+    Grid,
+    /// Tylko odkup po wykonanej sprzedaży (`reinvest_offset` poniżej ceny sprzedaży)
+    Ladder,
+    /// Bez górnej granicy: stała wartość (w quote) zapasu base, korekta o jeden poziom w każdą stronę
+    Infinity,
+    /// Najpierw sprzedaż posiadanego base powyżej ceny, odkup niżej – zysk liczony w base
+    Reverse,
+    /// Dollar-cost averaging: stała kwota w quote co `dca_interval_minutes`, dokupienie na spadkach
+    Dca,
+}
+
+impl StrategyKind {
+    pub const ALL: [StrategyKind; 5] = [
+        StrategyKind::Grid, StrategyKind::Ladder, StrategyKind::Infinity, StrategyKind::Reverse, StrategyKind::Dca,
+    ];
+
+    /// Nazwa używana w pliku konfiguracyjnym, CLI i kolumnie `capital.strategy`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StrategyKind::Grid => "grid",
+            StrategyKind::Ladder => "ladder",
+            StrategyKind::Infinity => "infinity",
+            StrategyKind::Reverse => "reverse",
+            StrategyKind::Dca => "dca",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<StrategyKind> {
+        StrategyKind::ALL.into_iter().find(|kind| kind.as_str() == name.trim().to_lowercase())
+    }
+}
+
+/// Parametry strategii grid dla jednej pary
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PairConfig {
+    pub symbol: String,
+    #[serde(default = "default_strategy")]
+    pub strategy: StrategyKind,
+    /// Liczba pozycji kupowanych od razu, każda z własnym poziomem sprzedaży powyżej ceny
+    #[serde(default = "default_levels_above")]
+    pub levels_above: u32,
+    /// Liczba poziomów kupna poniżej ceny
+    #[serde(default = "default_levels_below")]
+    pub levels_below: u32,
+    #[serde(default = "default_spacing")]
+    pub spacing: SpacingType,
+    /// Odstęp między poziomami jako ułamek ceny (0.05 = 5%)
+    #[serde(default = "default_step")]
+    pub step: f64,
+    /// Wartość jednego zlecenia jako ułamek przydzielonego kapitału
+    #[serde(default = "default_order_size")]
+    pub order_size: f64,
+    #[serde(default = "default_sizing")]
+    pub sizing: SizingScheme,
+    #[serde(default = "default_profit_mode")]
+    pub profit_mode: ProfitMode,
+    /// O ile poniżej ceny sprzedaży odkupujemy (0.05 = -5%)
+    #[serde(default = "default_reinvest_offset")]
+    pub reinvest_offset: f64,
+    /// Infinity grid: ułamek kapitału trzymany w base – jego wartość w quote jest utrzymywana stała
+    #[serde(default = "default_infinity_hold")]
+    pub infinity_hold: f64,
+    /// DCA: kwota w quote jednego kupna
+    #[serde(default = "default_dca_amount")]
+    pub dca_amount: f64,
+    /// DCA: odstęp między planowymi kupnami
+    #[serde(default = "default_dca_interval_minutes")]
+    pub dca_interval_minutes: u64,
+    /// DCA: dodatkowe kupno, gdy cena spadnie o ten ułamek poniżej ostatniego kupna (0.05 = 5%)
+    #[serde(default)]
+    pub dca_dip_pct: Option<f64>,
+    /// DCA: sprzedaż całego zapasu o ten ułamek powyżej średniej ceny wejścia
+    #[serde(default)]
+    pub dca_take_profit_pct: Option<f64>,
+    /// Zamknięcie gridu, gdy cena spadnie do tego poziomu
+    #[serde(default)]
+    pub stop_loss_price: Option<f64>,
+    /// Zamknięcie gridu, gdy strata (zrealizowana + niezrealizowana) osiągnie ułamek przydziału (0.2 = 20%)
+    #[serde(default)]
+    pub stop_loss_pct: Option<f64>,
+    #[serde(default)]
+    pub take_profit_price: Option<f64>,
+    /// Zamknięcie gridu, gdy zysk (zrealizowany + niezrealizowany) osiągnie ułamek przydziału
+    #[serde(default)]
+    pub take_profit_pct: Option<f64>,
+    /// Po stop-lossie / take-profit sprzedaj cały zapas zleceniem MARKET
+    #[serde(default)]
+    pub liquidate_on_exit: bool,
+    /// Gdy cena wyjdzie ponad `max_price`, przesuwaj zakres w górę o jeden poziom
+    #[serde(default)]
+    pub trail_up: bool,
+    /// Gdy cena spadnie poniżej `min_price`, przesuwaj zakres w dół o jeden poziom
+    #[serde(default)]
+    pub trail_down: bool,
+    /// Najwyższy `max_price`, do jakiego zakres może się przesunąć (brak = bez limitu)
+    #[serde(default)]
+    pub trail_ceiling: Option<f64>,
+    /// Najniższy `min_price`, do jakiego zakres może się przesunąć (brak = bez limitu)
+    #[serde(default)]
+    pub trail_floor: Option<f64>,
+}
+
+fn default_strategy() -> StrategyKind { StrategyKind::Grid }
+fn default_levels_above() -> u32 { 3 }
+fn default_levels_below() -> u32 { 2 }
+fn default_spacing() -> SpacingType { SpacingType::Arithmetic }
+fn default_step() -> f64 { 0.05 }
+fn default_order_size() -> f64 { 0.1 }
+fn default_sizing() -> SizingScheme { SizingScheme::EqualQuote }
+fn default_profit_mode() -> ProfitMode { ProfitMode::Compound }
+fn default_reinvest_offset() -> f64 { 0.05 }
+fn default_infinity_hold() -> f64 { 0.5 }
+fn default_dca_amount() -> f64 { 10.0 }
+fn default_dca_interval_minutes() -> u64 { 1440 }
+
+impl PairConfig {
+    /// Parametry domyślne dla par bez własnej sekcji `[[pairs]]`
+    pub fn default_for(symbol: &str) -> PairConfig {
+        PairConfig {
+            symbol: symbol.to_string(),
+            strategy: default_strategy(),
+            levels_above: default_levels_above(),
+            levels_below: default_levels_below(),
+            spacing: default_spacing(),
+            step: default_step(),
+            order_size: default_order_size(),
+            sizing: default_sizing(),
+            profit_mode: default_profit_mode(),
+            reinvest_offset: default_reinvest_offset(),
+            infinity_hold: default_infinity_hold(),
+            dca_amount: default_dca_amount(),
+            dca_interval_minutes: default_dca_interval_minutes(),
+            dca_dip_pct: None,
+            dca_take_profit_pct: None,
+            stop_loss_price: None,
+            stop_loss_pct: None,
+            take_profit_price: None,
+            take_profit_pct: None,
+            liquidate_on_exit: false,
+            trail_up: false,
+            trail_down: false,
+            trail_ceiling: None,
+            trail_floor: None,
+        }
+    }
+
+    /// Cena poziomu `k` względem ceny bazowej (k > 0 powyżej, k < 0 poniżej)
+    pub fn level_price(&self, base_price: f64, k: i32) -> f64 {
+        match self.spacing {
+            SpacingType::Arithmetic => base_price * (1.0 + self.step * k as f64),
+            SpacingType::Geometric => base_price * (1.0 + self.step).powi(k),
+        }
+    }
+
+    pub fn validate(&self, index: usize) -> Result<(), String> {
+        let name = format!("pairs[{}] ({})", index, self.symbol);
+        if self.symbol.is_empty() || !self.symbol.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()) {
+            return Err(format!("{}: `symbol` must be an uppercase Binance symbol like BTCUSDT", name));
+        }
+        if self.levels_above + self.levels_below == 0 {
+            return Err(format!("{}: at least one of `levels_above` / `levels_below` must be > 0", name));
+        }
+        if !(self.step > 0.0 && self.step < 1.0) {
+            return Err(format!("{}: `step` must be between 0 and 1, got {}", name, self.step));
+        }
+        if self.spacing == SpacingType::Arithmetic && self.step * self.levels_below as f64 >= 1.0 {
+            return Err(format!("{}: `step` * `levels_below` must be below 1 (lowest level would be <= 0)", name));
+        }
+        if !(self.order_size > 0.0 && self.order_size <= 1.0) {
+            return Err(format!("{}: `order_size` must be in (0, 1], got {}", name, self.order_size));
+        }
+        let total = self.order_size * (self.levels_above + self.levels_below) as f64;
+        if self.sizing != SizingScheme::Full && total > 1.0 + 1e-9 {
+            return Err(format!(
+                "{}: `order_size` * levels = {:.2} exceeds the whole allocation (max 1.0)",
+                name, total
+            ));
+        }
+        if !(0.0..1.0).contains(&self.reinvest_offset) {
+            return Err(format!("{}: `reinvest_offset` must be in [0, 1), got {}", name, self.reinvest_offset));
+        }
+        if !(self.infinity_hold > 0.0 && self.infinity_hold < 1.0) {
+            return Err(format!("{}: `infinity_hold` must be in (0, 1), got {}", name, self.infinity_hold));
+        }
+        if self.dca_amount <= 0.0 {
+            return Err(format!("{}: `dca_amount` must be > 0, got {}", name, self.dca_amount));
+        }
+        if self.dca_interval_minutes == 0 {
+            return Err(format!("{}: `dca_interval_minutes` must be > 0", name));
+        }
+        if matches!(self.dca_dip_pct, Some(pct) if !(pct > 0.0 && pct < 1.0)) {
+            return Err(format!("{}: `dca_dip_pct` must be in (0, 1)", name));
+        }
+        if matches!(self.dca_take_profit_pct, Some(pct) if pct <= 0.0) {
+            return Err(format!("{}: `dca_take_profit_pct` must be > 0", name));
+        }
+        for (field, value) in [
+            ("stop_loss_price", self.stop_loss_price),
+            ("take_profit_price", self.take_profit_price),
+            ("trail_ceiling", self.trail_ceiling),
+            ("trail_floor", self.trail_floor),
+        ] {
+            if matches!(value, Some(price) if price <= 0.0) {
+                return Err(format!("{}: `{}` must be > 0", name, field));
+            }
+        }
+        if let (Some(stop), Some(take)) = (self.stop_loss_price, self.take_profit_price) {
+            if stop >= take {
+                return Err(format!("{}: `stop_loss_price` must be below `take_profit_price`", name));
+            }
+        }
+        if let (Some(floor), Some(ceiling)) = (self.trail_floor, self.trail_ceiling) {
+            if floor >= ceiling {
+                return Err(format!("{}: `trail_floor` must be below `trail_ceiling`", name));
+            }
+        }
+        if matches!(self.stop_loss_pct, Some(pct) if !(pct > 0.0 && pct <= 1.0)) {
+            return Err(format!("{}: `stop_loss_pct` must be in (0, 1]", name));
+        }
+        if matches!(self.take_profit_pct, Some(pct) if pct <= 0.0) {
+            return Err(format!("{}: `take_profit_pct` must be > 0", name));
+        }
+        Ok(())
+    }
+}
+
+impl BotConfig {
+    /// Parametry strategii dla pary (sekcja z pliku lub wartości domyślne)
+    pub fn pair(&self, symbol: &str) -> PairConfig {
+        self.pairs.iter()
+            .find(|p| p.symbol == symbol)
+            .cloned()
+            .unwrap_or_else(|| PairConfig::default_for(symbol))
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.api.api_key.is_some() != self.api.secret_key.is_some() {
+            return Err("[api]: `api_key` and `secret_key` must be set together".to_string());
+        }
+        if let Some(profile) = &self.environment.profile {
+            if profile != "mainnet" && profile != "testnet" {
+                return Err(format!("[environment]: unknown profile `{}` (expected mainnet or testnet)", profile));
+            }
+        }
+        if self.risk.max_open_orders == 0 {
+            return Err("[risk]: `max_open_orders` must be > 0".to_string());
+        }
+        if self.risk.max_open_orders_per_pair == 0 {
+            return Err("[risk]: `max_open_orders_per_pair` must be > 0".to_string());
+        }
+        if self.risk.max_order_notional <= 0.0 {
+            return Err("[risk]: `max_order_notional` must be > 0".to_string());
+        }
+        for (name, limit) in [
+            ("max_inventory_value_per_pair", self.risk.max_inventory_value_per_pair),
+            ("max_total_exposure", self.risk.max_total_exposure),
+            ("max_daily_loss", self.risk.max_daily_loss),
+        ] {
+            if matches!(limit, Some(value) if value <= 0.0) {
+                return Err(format!("[risk]: `{}` must be > 0", name));
+            }
+        }
+        if matches!(self.risk.max_drawdown_pct, Some(pct) if !(pct > 0.0 && pct < 1.0)) {
+            return Err("[risk]: `max_drawdown_pct` must be in (0, 1)".to_string());
+        }
+        if !(0.0..1.0).contains(&self.risk.min_price_distance) {
+            return Err(format!("[risk]: `min_price_distance` must be in [0, 1), got {}", self.risk.min_price_distance));
+        }
+
+        if matches!(self.volatility.max_drop_pct, Some(pct) if !(pct > 0.0 && pct < 1.0)) {
+            return Err("[volatility]: `max_drop_pct` must be in (0, 1)".to_string());
+        }
+        if matches!(self.volatility.atr_spike_multiplier, Some(multiplier) if multiplier <= 1.0) {
+            return Err("[volatility]: `atr_spike_multiplier` must be > 1".to_string());
+        }
+        if !(1..=1000).contains(&self.volatility.window_minutes) || !(2..=999).contains(&self.volatility.atr_period) {
+            return Err("[volatility]: `window_minutes` must be in 1..=1000 and `atr_period` in 2..=999".to_string());
+        }
+
+        if !(0.0..0.1).contains(&self.fees.maker) || !(0.0..0.1).contains(&self.fees.taker) {
+            return Err("[fees]: `maker` and `taker` must be in [0, 0.1)".to_string());
+        }
+
+        let mut seen = HashSet::new();
+        for (index, pair) in self.pairs.iter().enumerate() {
+            pair.validate(index)?;
+            if !seen.insert(pair.symbol.clone()) {
+                return Err(format!("pairs[{}]: duplicate section for {}", index, pair.symbol));
+            }
+        }
+        Ok(())
+    }
+}
+
+static CONFIG: OnceLock<BotConfig> = OnceLock::new();
+
+/// Wczytuje i waliduje plik konfiguracyjny
+pub fn load(path: &str) -> Result<BotConfig, String> {
+    // Uprawnienia sprawdzane przed wczytaniem – plik z kluczami czytelny dla wszystkich jest odrzucany, zanim klucze trafią dalej
+    let permissions = credentials::check_not_world_readable(path);
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read config file `{}`: {} (see config.example.toml)", path, e))?;
+    let config: BotConfig = toml::from_str(&contents)
+        .map_err(|e| format!("Invalid config file `{}`: {}", path, e))?;
+    if config.api.api_key.is_some() || config.api.secret_key.is_some() {
+        permissions?;
+    }
+    config.validate().map_err(|e| format!("Invalid config file `{}`: {}", path, e))?;
+    Ok(config)
+}
+
+/// Wspólna konfiguracja testów jednostkowych (limity ryzyka ustawione tak, by dało się sprawdzić każdy warunek)
+#[cfg(test)]
+pub fn init_for_tests() -> &'static BotConfig {
+    CONFIG.get_or_init(|| {
+        toml::from_str(
+            "[risk]
+             max_open_orders = 5
+             max_open_orders_per_pair = 3
+             max_order_notional = 1000.0
+             max_inventory_value_per_pair = 500.0
+             max_total_exposure = 800.0
+             min_price_distance = 0.01",
+        ).expect("Invalid test config")
+    })
+}
+
+pub fn init(config: BotConfig) {
+    CONFIG.set(config).expect("Config already initialized");
+}
+
+/// Konfiguracja wczytana przy starcie
+pub fn config() -> &'static BotConfig {
+    CONFIG.get().expect("Config not initialized")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(toml: &str) -> Result<(), String> {
+        toml::from_str::<BotConfig>(toml).map_err(|e| e.to_string())?.validate()
+    }
+
+    #[test]
+    fn example_config_is_valid() {
+        let config: BotConfig = toml::from_str(include_str!("../config.example.toml")).unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.pair("BTCUSDT").spacing, SpacingType::Arithmetic);
+        assert_eq!(config.pair("BTCUSDT").strategy, StrategyKind::Grid);
+        assert_eq!(config.pair("ETHUSDT").levels_above, default_levels_above());
+    }
+
+    #[test]
+    fn api_keys_must_be_set_together() {
+        let error = parse("[api]\napi_key = \"key\"").unwrap_err();
+        assert!(error.contains("set together"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_invalid_risk_limits() {
+        assert!(parse("[risk]\nmax_open_orders = 0").unwrap_err().contains("max_open_orders"));
+        assert!(parse("[risk]\nmax_order_notional = -1.0").unwrap_err().contains("max_order_notional"));
+        assert!(parse("[risk]\nmax_drawdown_pct = 1.5").unwrap_err().contains("max_drawdown_pct"));
+        assert!(parse("[environment]\nprofile = \"devnet\"").unwrap_err().contains("unknown profile"));
+    }
+
+    #[test]
+    fn rejects_invalid_pair_parameters() {
+        let pair = |fields: &str| parse(&format!("[[pairs]]\nsymbol = \"BTCUSDT\"\n{}", fields));
+        assert!(pair("step = 0.0").unwrap_err().contains("`step`"));
+        assert!(pair("step = 0.5\nlevels_below = 2").unwrap_err().contains("levels_below"));
+        assert!(pair("order_size = 0.5").unwrap_err().contains("exceeds the whole allocation"));
+        assert!(pair("reinvest_offset = 1.0").unwrap_err().contains("reinvest_offset"));
+        assert!(pair("stop_loss_price = 100.0\ntake_profit_price = 90.0").unwrap_err().contains("stop_loss_price"));
+        assert!(pair("trail_floor = 0.0").unwrap_err().contains("trail_floor"));
+        assert!(parse("[[pairs]]\nsymbol = \"btcusdt\"").unwrap_err().contains("uppercase"));
+        assert!(pair("order_size = 0.2").is_ok());
+    }
+
+    #[test]
+    fn rejects_duplicate_pair_sections() {
+        let error = parse("[[pairs]]\nsymbol = \"BTCUSDT\"\n[[pairs]]\nsymbol = \"BTCUSDT\"").unwrap_err();
+        assert!(error.contains("duplicate"), "{}", error);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn world_readable_config_with_keys_is_rejected() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("grid_bot_config_test_{}.toml", std::process::id()));
+        let path_str = path.to_str().unwrap();
+        fs::write(&path, "[api]\napi_key = \"key\"\nsecret_key = \"secret\"").unwrap();
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(load(path_str).unwrap_err().contains("world-readable"));
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        assert!(load(path_str).is_ok());
+        fs::remove_file(&path).unwrap();
+    }
+}
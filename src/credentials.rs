@@ -0,0 +1,254 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::Argon2;
+use serde::{Deserialize, Serialize};
+
+use crate::{config, get_user_input};
+
+/// Zmienne środowiskowe z kluczami (sprawdzane jako pierwsze)
+const ENV_API_KEY: &str = "BINANCE_API_KEY";
+const ENV_SECRET_KEY: &str = "BINANCE_SECRET_KEY";
+/// Hasło do keystore – jeśli brak, bot pyta o nie przy starcie
+const ENV_KEYSTORE_PASSPHRASE: &str = "BINANCE_KEYSTORE_PASSPHRASE";
+
+/// Klucze API Binance; `Debug` nigdy nie wypisuje ich wartości
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Credentials {
+    pub api_key: String,
+    pub secret_key: String,
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("api_key", &"***")
+            .field("secret_key", &"***")
+            .finish()
+    }
+}
+
+/// Zaszyfrowany plik z kluczami (Argon2id + AES-256-GCM)
+#[derive(Serialize, Deserialize)]
+struct Keystore {
+    version: u32,
+    kdf: String,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+static CREDENTIALS: OnceLock<Credentials> = OnceLock::new();
+
+/// Klucze API ustalone przy starcie
+pub fn credentials() -> &'static Credentials {
+    CREDENTIALS.get().expect("Credentials not initialized")
+}
+
+/// Usuwa z tekstu klucze API oraz podpisy zapytań (do logów i komunikatów błędów)
+pub fn redact(text: &str) -> String {
+    let mut redacted = text.to_string();
+    if let Some(creds) = CREDENTIALS.get() {
+        for secret in [&creds.api_key, &creds.secret_key] {
+            if !secret.is_empty() {
+                redacted = redacted.replace(secret.as_str(), "***");
+            }
+        }
+    }
+    let mut search_from = 0;
+    while let Some(offset) = redacted[search_from..].find("signature=") {
+        let value_start = search_from + offset + "signature=".len();
+        let value_end = redacted[value_start..]
+            .find(|c: char| !c.is_ascii_hexdigit())
+            .map(|i| value_start + i)
+            .unwrap_or(redacted.len());
+        if value_end > value_start {
+            redacted.replace_range(value_start..value_end, "***");
+            search_from = value_start + "***".len();
+        } else {
+            search_from = value_start;
+        }
+    }
+    redacted
+}
+
+/// Odmawia użycia pliku z kluczami czytelnego dla wszystkich użytkowników
+#[cfg(unix)]
+pub fn check_not_world_readable(path: &str) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = fs::metadata(path)
+        .map_err(|e| format!("Cannot read permissions of `{}`: {}", path, e))?
+        .permissions()
+        .mode();
+    if mode & 0o004 != 0 {
+        return Err(format!(
+            "Key file `{}` is world-readable (mode {:o}); run `chmod 600 {}` first",
+            path, mode & 0o777, path
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn check_not_world_readable(_path: &str) -> Result<(), String> {
+    Ok(())
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32], String> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+fn read_passphrase(prompt: &str) -> Result<String, String> {
+    if let Ok(passphrase) = env::var(ENV_KEYSTORE_PASSPHRASE) {
+        return Ok(passphrase);
+    }
+    rpassword::prompt_password(prompt).map_err(|e| format!("Cannot read passphrase: {}", e))
+}
+
+fn decrypt_keystore(path: &str, passphrase: &str) -> Result<Credentials, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Cannot read keystore `{}`: {}", path, e))?;
+    let keystore: Keystore = serde_json::from_str(&contents)
+        .map_err(|e| format!("Invalid keystore `{}`: {}", path, e))?;
+    if keystore.version != 1 || keystore.kdf != "argon2id" {
+        return Err(format!("Unsupported keystore format in `{}`", path));
+    }
+
+    let decode = |field: &str, value: &str| hex::decode(value).map_err(|_| format!("Invalid keystore `{}`: bad {}", path, field));
+    let salt = decode("salt", &keystore.salt)?;
+    let nonce = decode("nonce", &keystore.nonce)?;
+    let ciphertext = decode("ciphertext", &keystore.ciphertext)?;
+    if nonce.len() != 12 {
+        return Err(format!("Invalid keystore `{}`: bad nonce", path));
+    }
+
+    let key = derive_key(passphrase, &salt)?;
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+        .map_err(|_| format!("Cannot unlock keystore `{}`: wrong passphrase or corrupted file", path))?;
+
+    serde_json::from_slice(&plaintext).map_err(|_| format!("Invalid keystore `{}`: bad payload", path))
+}
+
+/// Ustala klucze: zmienne środowiskowe → plik konfiguracyjny → zaszyfrowany keystore
+pub fn init(config_path: &str) -> Result<(), String> {
+    let api = &config::config().api;
+
+    let creds = if let (Ok(api_key), Ok(secret_key)) = (env::var(ENV_API_KEY), env::var(ENV_SECRET_KEY)) {
+        println!("🔑 Using API keys from environment variables");
+        Credentials { api_key, secret_key }
+    } else if let (Some(api_key), Some(secret_key)) = (&api.api_key, &api.secret_key) {
+        // Uprawnienia pliku z kluczami sprawdził już `config::load`
+        println!("🔑 Using API keys from {}", config_path);
+        Credentials { api_key: api_key.clone(), secret_key: secret_key.clone() }
+    } else if let Some(keystore) = &api.keystore {
+        check_not_world_readable(keystore)?;
+        let passphrase = read_passphrase(&format!("Enter passphrase for keystore {}: ", keystore))?;
+        let creds = decrypt_keystore(keystore, &passphrase)?;
+        println!("🔑 Using API keys from encrypted keystore {}", keystore);
+        creds
+    } else {
+        return Err(format!(
+            "No API keys found: set {} / {}, add them to [api] in {} or configure [api].keystore",
+            ENV_API_KEY, ENV_SECRET_KEY, config_path
+        ));
+    };
+
+    if creds.api_key.trim().is_empty() || creds.secret_key.trim().is_empty() {
+        return Err("API key and secret key must not be empty".to_string());
+    }
+
+    CREDENTIALS.set(creds).map_err(|_| "Credentials already initialized".to_string())
+}
+
+/// Tworzy zaszyfrowany keystore z kluczami podanymi interaktywnie
+pub fn create_keystore(path: &str) -> Result<(), String> {
+    if Path::new(path).exists() {
+        return Err(format!("Keystore `{}` already exists", path));
+    }
+
+    let api_key = get_user_input("Enter Binance API key:");
+    let secret_key = rpassword::prompt_password("Enter Binance secret key: ").map_err(|e| e.to_string())?;
+    let passphrase = rpassword::prompt_password("Choose keystore passphrase: ").map_err(|e| e.to_string())?;
+    let confirm = rpassword::prompt_password("Repeat passphrase: ").map_err(|e| e.to_string())?;
+    if passphrase != confirm {
+        return Err("Passphrases do not match".to_string());
+    }
+    if passphrase.len() < 8 {
+        return Err("Passphrase must have at least 8 characters".to_string());
+    }
+
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive_key(&passphrase, &salt)?;
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let payload = serde_json::to_vec(&Credentials { api_key, secret_key }).map_err(|e| e.to_string())?;
+    let ciphertext = cipher.encrypt(&nonce, payload.as_ref()).map_err(|_| "Encryption failed".to_string())?;
+
+    let keystore = Keystore {
+        version: 1,
+        kdf: "argon2id".to_string(),
+        salt: hex::encode(salt),
+        nonce: hex::encode(nonce),
+        ciphertext: hex::encode(ciphertext),
+    };
+    let contents = serde_json::to_string_pretty(&keystore).map_err(|e| e.to_string())?;
+    write_private_file(path, &contents)?;
+
+    println!("✅ Keystore written to {} (set [api].keystore = \"{}\" in config.toml)", path, path);
+    Ok(())
+}
+
+#[cfg(unix)]
+fn write_private_file(path: &str, contents: &str) -> Result<(), String> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+        .map_err(|e| format!("Cannot create `{}`: {}", path, e))?;
+    file.write_all(contents.as_bytes()).map_err(|e| format!("Cannot write `{}`: {}", path, e))
+}
+
+#[cfg(not(unix))]
+fn write_private_file(path: &str, contents: &str) -> Result<(), String> {
+    fs::write(path, contents).map_err(|e| format!("Cannot write `{}`: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_every_signature() {
+        let text = "GET /api/v3/order?symbol=BTCUSDT&signature=abc123 failed, retry /api/v3/account?signature=DEF456&recvWindow=5000";
+        assert_eq!(
+            redact(text),
+            "GET /api/v3/order?symbol=BTCUSDT&signature=*** failed, retry /api/v3/account?signature=***&recvWindow=5000"
+        );
+    }
+
+    #[test]
+    fn keeps_text_after_an_empty_signature() {
+        assert_eq!(redact("signature=&x=1 signature=ff00"), "signature=&x=1 signature=***");
+    }
+
+    #[test]
+    fn leaves_text_without_signatures_unchanged() {
+        assert_eq!(redact("Invalid quantity"), "Invalid quantity");
+    }
+}
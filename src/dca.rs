@@ -0,0 +1,165 @@
+use crate::strategy::{FillEvent, OrderIntent, OrderSpec, Strategy, StrategyContext};
+
+/// DCA: kupno `dca_amount` quote co `dca_interval_minutes` (i dodatkowo na spadkach),
+/// aż do wyczerpania przydziału; opcjonalnie sprzedaż całości nad średnią ceną wejścia
+pub struct DcaAccumulation;
+
+impl DcaAccumulation {
+    /// Kupno po aktualnej cenie, jeśli minął interwał albo cena spadła o `dca_dip_pct`
+    fn due_buy(ctx: &StrategyContext, price: f64) -> Option<OrderIntent> {
+        let pair = ctx.pair;
+        // Niewykonane kupno czeka – nie dokładamy kolejnego
+        if ctx.open_orders.iter().any(|order| order.side == "BUY") {
+            return None;
+        }
+        let scheduled = ctx.last_buy.is_none_or(|(at, _)| ctx.now - at >= pair.dca_interval_minutes as i64 * 60);
+        let dip = match (pair.dca_dip_pct, ctx.last_buy) {
+            (Some(pct), Some((_, last_price))) => price <= last_price * (1.0 - pct),
+            _ => false,
+        };
+        let invested = ctx.avg_entry * ctx.held;
+        if !(scheduled || dip) || invested + pair.dca_amount > ctx.capital {
+            return None;
+        }
+        Some(OrderIntent::Place(OrderSpec::new("BUY", price, pair.dca_amount / price)))
+    }
+}
+
+impl Strategy for DcaAccumulation {
+    fn on_start(&mut self, ctx: &StrategyContext, price: f64) -> Vec<OrderIntent> {
+        Self::due_buy(ctx, price).into_iter().collect()
+    }
+
+    fn on_price(&mut self, ctx: &StrategyContext, price: f64) -> Vec<OrderIntent> {
+        Self::due_buy(ctx, price).into_iter().collect()
+    }
+
+    /// Po kupnie przestawia take-profit na nową średnią cenę wejścia
+    fn on_fill(&mut self, ctx: &StrategyContext, fill: &FillEvent) -> Vec<OrderIntent> {
+        let Some(pct) = ctx.pair.dca_take_profit_pct.filter(|_| fill.side == "BUY") else {
+            return Vec::new();
+        };
+        let mut intents: Vec<OrderIntent> = ctx.open_orders.iter()
+            .filter(|order| order.side == "SELL")
+            .map(|order| OrderIntent::Cancel(order.order_id))
+            .collect();
+        let avg_entry = if ctx.avg_entry > 0.0 { ctx.avg_entry } else { fill.price };
+        if ctx.held > 0.0 {
+            intents.push(OrderIntent::Place(OrderSpec::new("SELL", avg_entry * (1.0 + pct), ctx.held)));
+        }
+        intents
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PairConfig;
+    use crate::strategy::OpenOrder;
+
+    const HOUR: i64 = 3600;
+
+    fn pair() -> PairConfig {
+        PairConfig { dca_amount: 100.0, dca_interval_minutes: 60, ..PairConfig::default_for("BTCUSDT") }
+    }
+
+    fn context(pair: &PairConfig, last_buy: Option<(i64, f64)>, held: f64, avg_entry: f64) -> StrategyContext<'_> {
+        StrategyContext {
+            pair,
+            capital: 1000.0,
+            base_amount: 0.0,
+            min_price: 0.0,
+            held,
+            avg_entry,
+            last_buy,
+            now: 10 * HOUR,
+            open_orders: Vec::new(),
+        }
+    }
+
+    fn open(order_id: u64, side: &str) -> OpenOrder {
+        OpenOrder { order_id, side: side.to_string(), price: 100.0, quantity: 1.0 }
+    }
+
+    fn buy_quantity(intent: Option<OrderIntent>) -> Option<f64> {
+        match intent {
+            Some(OrderIntent::Place(spec)) if spec.side == "BUY" => Some(spec.quantity),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn first_buy_is_placed_immediately() {
+        let pair = pair();
+        let quantity = buy_quantity(DcaAccumulation::due_buy(&context(&pair, None, 0.0, 0.0), 50.0));
+        assert_eq!(quantity, Some(2.0));
+    }
+
+    #[test]
+    fn next_buy_waits_for_the_interval() {
+        let pair = pair();
+        let recent = context(&pair, Some((10 * HOUR - 59 * 60, 100.0)), 1.0, 100.0);
+        assert!(DcaAccumulation::due_buy(&recent, 100.0).is_none());
+
+        let due = context(&pair, Some((9 * HOUR, 100.0)), 1.0, 100.0);
+        assert_eq!(buy_quantity(DcaAccumulation::due_buy(&due, 100.0)), Some(1.0));
+    }
+
+    #[test]
+    fn dip_buys_before_the_interval() {
+        let pair = PairConfig { dca_dip_pct: Some(0.05), ..pair() };
+        let ctx = context(&pair, Some((10 * HOUR - 60, 100.0)), 1.0, 100.0);
+
+        assert!(DcaAccumulation::due_buy(&ctx, 96.0).is_none());
+        assert!(buy_quantity(DcaAccumulation::due_buy(&ctx, 95.0)).is_some());
+    }
+
+    #[test]
+    fn pending_buy_blocks_the_next_one() {
+        let pair = pair();
+        let mut ctx = context(&pair, None, 0.0, 0.0);
+        ctx.open_orders = vec![open(1, "BUY")];
+        assert!(DcaAccumulation::due_buy(&ctx, 100.0).is_none());
+    }
+
+    #[test]
+    fn buys_stop_at_the_allocation() {
+        let pair = pair();
+        // 9 base po 100 = 900 zainwestowane; kolejne 100 mieści się, 9.5 base już nie
+        assert!(DcaAccumulation::due_buy(&context(&pair, None, 9.0, 100.0), 100.0).is_some());
+        assert!(DcaAccumulation::due_buy(&context(&pair, None, 9.5, 100.0), 100.0).is_none());
+    }
+
+    #[test]
+    fn buy_fill_moves_take_profit_to_the_new_average_entry() {
+        let pair = PairConfig { dca_take_profit_pct: Some(0.1), ..pair() };
+        let mut ctx = context(&pair, None, 3.0, 90.0);
+        ctx.open_orders = vec![open(1, "BUY"), open(2, "SELL")];
+        let fill = FillEvent {
+            order_id: 3, side: "BUY".to_string(), price: 80.0, quantity: 1.0,
+            net_base: 1.0, net_quote: 0.0, level: None, covers_qty: None,
+        };
+        let intents = DcaAccumulation.on_fill(&ctx, &fill);
+
+        assert_eq!(intents.len(), 2);
+        assert!(matches!(intents[0], OrderIntent::Cancel(2)));
+        match &intents[1] {
+            OrderIntent::Place(sell) => {
+                assert_eq!(sell.side, "SELL");
+                assert!((sell.price - 99.0).abs() < 1e-9);
+                assert_eq!(sell.quantity, 3.0);
+            }
+            other => panic!("expected a take-profit sell, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn no_take_profit_without_the_setting() {
+        let pair = pair();
+        let fill = FillEvent {
+            order_id: 3, side: "BUY".to_string(), price: 80.0, quantity: 1.0,
+            net_base: 1.0, net_quote: 0.0, level: None, covers_qty: None,
+        };
+        assert!(DcaAccumulation.on_fill(&context(&pair, None, 1.0, 80.0), &fill).is_empty());
+    }
+}
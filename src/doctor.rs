@@ -0,0 +1,196 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use reqwest::Client;
+use serde_json::Value;
+
+use crate::{base_url, credentials, get_binance_server_time, send_signed_request, MAINNET_BASE_URL};
+
+/// Handel na żywo jest dozwolony dopiero po pozytywnym sprawdzeniu uprawnień klucza
+static LIVE_TRADING_ALLOWED: AtomicBool = AtomicBool::new(false);
+
+pub fn live_trading_allowed() -> bool {
+    LIVE_TRADING_ALLOWED.load(Ordering::SeqCst)
+}
+
+/// Wynik sprawdzenia uprawnień klucza API
+#[derive(Debug, Default)]
+pub struct KeyCheck {
+    pub can_trade: Option<bool>,
+    pub permissions: Vec<String>,
+    pub withdrawals_enabled: Option<bool>,
+    pub spot_trading_enabled: Option<bool>,
+    pub ip_restricted: Option<bool>,
+    /// Powody blokady handlu na żywo
+    pub problems: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl KeyCheck {
+    pub fn passed(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Odpowiedź `/api/v3/account`: konto musi móc handlować na rynku spot
+    fn apply_account(&mut self, account: &Value) {
+        self.can_trade = account["canTrade"].as_bool();
+        self.permissions = account["permissions"].as_array()
+            .map(|list| list.iter().filter_map(Value::as_str).map(str::to_string).collect())
+            .unwrap_or_default();
+        if self.can_trade != Some(true) {
+            self.problems.push("Account reports canTrade = false".to_string());
+        }
+        if !self.permissions.is_empty() && !self.permissions.iter().any(|p| p == "SPOT") {
+            self.problems.push("Account has no SPOT permission".to_string());
+        }
+    }
+
+    /// Odpowiedź `/sapi/v1/account/apiRestrictions`: bez wypłat, z handlem spot; brak listy IP to tylko ostrzeżenie
+    fn apply_restrictions(&mut self, restrictions: &Value) {
+        self.withdrawals_enabled = restrictions["enableWithdrawals"].as_bool();
+        self.spot_trading_enabled = restrictions["enableSpotAndMarginTrading"].as_bool();
+        self.ip_restricted = restrictions["ipRestrict"].as_bool();
+
+        if self.withdrawals_enabled != Some(false) {
+            self.problems.push("Withdrawals are enabled for this API key – disable them on Binance".to_string());
+        }
+        if self.spot_trading_enabled != Some(true) {
+            self.problems.push("Spot & Margin trading is not enabled for this API key".to_string());
+        }
+        if self.ip_restricted != Some(true) {
+            self.warnings.push("API key is not restricted to trusted IPs".to_string());
+        }
+    }
+
+    pub fn print(&self) {
+        let show = |value: Option<bool>| match value {
+            Some(true) => "yes",
+            Some(false) => "no",
+            None => "unknown",
+        };
+        println!("\n🩺 **API key self-check** ({})", base_url());
+        println!("   canTrade:               {}", show(self.can_trade));
+        println!("   permissions:            {}", if self.permissions.is_empty() { "-".to_string() } else { self.permissions.join(", ") });
+        println!("   spot trading enabled:   {}", show(self.spot_trading_enabled));
+        println!("   withdrawals enabled:    {}", show(self.withdrawals_enabled));
+        println!("   IP restricted:          {}", show(self.ip_restricted));
+        for warning in &self.warnings {
+            println!("   ⚠️ {}", warning);
+        }
+        for problem in &self.problems {
+            println!("   ❌ {}", problem);
+        }
+        if self.passed() {
+            println!("   ✅ Live trading allowed");
+        } else {
+            println!("   ⛔ Live trading disabled until the problems above are fixed");
+        }
+    }
+}
+
+/// Sprawdza `/api/v3/account` i `/sapi/v1/account/apiRestrictions`
+pub async fn check_api_key(client: &Client) -> KeyCheck {
+    let creds = credentials::credentials();
+    let mut check = KeyCheck::default();
+
+    match send_signed_request(client, reqwest::Method::GET, "/api/v3/account", "", &creds.api_key, &creds.secret_key).await {
+        Ok(account) => check.apply_account(&account),
+        Err(e) => check.problems.push(format!("Cannot read /api/v3/account: {}", e)),
+    }
+
+    // Testnet i mocki nie udostępniają /sapi – wypłaty i tak nie są tam możliwe
+    if base_url() != MAINNET_BASE_URL {
+        check.warnings.push("API restrictions not checked outside mainnet (/sapi unavailable)".to_string());
+        return check;
+    }
+
+    match send_signed_request(client, reqwest::Method::GET, "/sapi/v1/account/apiRestrictions", "", &creds.api_key, &creds.secret_key).await {
+        Ok(restrictions) => check.apply_restrictions(&restrictions),
+        Err(e) => check.problems.push(format!("Cannot read /sapi/v1/account/apiRestrictions: {}", e)),
+    }
+
+    check
+}
+
+/// Sprawdzenie przy starcie – ustawia zgodę na handel na żywo
+pub async fn startup_check(client: &Client) {
+    let check = check_api_key(client).await;
+    LIVE_TRADING_ALLOWED.store(check.passed(), Ordering::SeqCst);
+    if !check.passed() || !check.warnings.is_empty() {
+        check.print();
+    }
+}
+
+/// Komenda `doctor`: łączność, czas serwera i uprawnienia klucza. Zwraca `true`, gdy wszystko OK.
+pub async fn run_doctor() -> bool {
+    let client = Client::new();
+
+    match get_binance_server_time().await {
+        Ok(server_time) if server_time > 0 => {
+            let local_time = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_millis())
+                .unwrap_or(0);
+            let skew = local_time as i128 - server_time as i128;
+            println!("✅ Connected to {} | clock skew: {} ms", base_url(), skew);
+        }
+        _ => println!("❌ Cannot reach {}", base_url()),
+    }
+
+    let check = check_api_key(&client).await;
+    LIVE_TRADING_ALLOWED.store(check.passed(), Ordering::SeqCst);
+    check.print();
+    check.passed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn check(account: Value, restrictions: Value) -> KeyCheck {
+        let mut check = KeyCheck::default();
+        check.apply_account(&account);
+        check.apply_restrictions(&restrictions);
+        check
+    }
+
+    fn safe_restrictions() -> Value {
+        json!({ "enableWithdrawals": false, "enableSpotAndMarginTrading": true, "ipRestrict": true })
+    }
+
+    #[test]
+    fn trade_only_key_passes() {
+        let check = check(json!({ "canTrade": true, "permissions": ["SPOT"] }), safe_restrictions());
+        assert!(check.passed());
+        assert!(check.warnings.is_empty());
+    }
+
+    #[test]
+    fn withdrawals_or_missing_spot_trading_block_live_trading() {
+        let withdrawals = json!({ "enableWithdrawals": true, "enableSpotAndMarginTrading": true, "ipRestrict": true });
+        assert!(!check(json!({ "canTrade": true }), withdrawals).passed());
+        let no_spot = json!({ "enableWithdrawals": false, "enableSpotAndMarginTrading": false, "ipRestrict": true });
+        assert!(!check(json!({ "canTrade": true }), no_spot).passed());
+        // Brak odpowiedzi o wypłatach to też blokada – nie wiadomo, czy są wyłączone
+        assert!(!check(json!({ "canTrade": true }), json!({})).passed());
+    }
+
+    #[test]
+    fn account_without_trading_or_spot_permission_is_blocked() {
+        assert!(!check(json!({ "canTrade": false, "permissions": ["SPOT"] }), safe_restrictions()).passed());
+        let margin_only = check(json!({ "canTrade": true, "permissions": ["MARGIN"] }), safe_restrictions());
+        assert_eq!(margin_only.problems, vec!["Account has no SPOT permission".to_string()]);
+    }
+
+    #[test]
+    fn missing_ip_restriction_is_only_a_warning() {
+        let restrictions = json!({ "enableWithdrawals": false, "enableSpotAndMarginTrading": true, "ipRestrict": false });
+        let check = check(json!({ "canTrade": true, "permissions": ["SPOT"] }), restrictions);
+        assert!(check.passed());
+        assert_eq!(check.warnings.len(), 1);
+    }
+
+    #[test]
+    fn live_trading_is_disabled_until_the_check_passes() {
+        assert!(!live_trading_allowed());
+    }
+}
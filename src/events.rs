@@ -0,0 +1,42 @@
+use rusqlite::{params, Connection};
+
+/// Dziennik zdarzeń bota (stop-loss, take-profit, wyłączenia gridów)
+pub fn setup(conn: &Connection) {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS events (
+            id INTEGER PRIMARY KEY,
+            symbol TEXT,
+            timestamp TEXT NOT NULL DEFAULT (datetime('now')),
+            event TEXT NOT NULL,
+            details TEXT NOT NULL
+        )",
+        [],
+    ).expect("Failed to create events table");
+}
+
+/// Zapisuje zdarzenie; `symbol` = None dla zdarzeń dotyczących całego konta
+pub fn record(db: &Connection, symbol: Option<&str>, event: &str, details: &str) {
+    db.execute(
+        "INSERT INTO events (symbol, event, details) VALUES (?1, ?2, ?3)",
+        params![symbol, event, details],
+    ).expect("Failed to record event");
+}
+
+pub fn show_events(db: &Connection) {
+    let mut stmt = db.prepare("SELECT timestamp, COALESCE(symbol, '-'), event, details FROM events ORDER BY id DESC LIMIT 50")
+        .expect("Failed to prepare statement");
+    let events = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+        ))
+    }).expect("Failed to query events");
+
+    println!("\n📜 **Event log (last 50):**");
+    for event in events {
+        let (timestamp, symbol, event, details) = event.expect("Failed to fetch event");
+        println!("{} | {} | {} | {}", timestamp, symbol, event, details);
+    }
+}
@@ -0,0 +1,164 @@
+use rusqlite::Connection;
+
+use crate::config::{FeeConfig, PairConfig};
+use crate::{portfolio, sizing, split_symbol};
+
+/// Zysk jednego cyklu kupno → sprzedaż wartości `value` (prowizja kupna w base, sprzedaży w quote)
+pub fn cycle_profit(buy_price: f64, sell_price: f64, value: f64, buy_fee: f64, sell_fee: f64) -> f64 {
+    let quantity = value / buy_price * (1.0 - buy_fee);
+    quantity * sell_price * (1.0 - sell_fee) - value
+}
+
+/// Najmniejszy odstęp poziomów, przy którym cykl wychodzi na zero
+pub fn min_viable_step(buy_fee: f64, sell_fee: f64) -> f64 {
+    1.0 / ((1.0 - buy_fee) * (1.0 - sell_fee)) - 1.0
+}
+
+/// Cykl jednego poziomu kupna gridu
+pub struct LevelCycle {
+    pub buy_price: f64,
+    pub sell_price: f64,
+    pub value: f64,
+    /// Pierwszy cykl – kupna po cenie rynkowej płacą taker
+    pub first_profit: f64,
+    /// Kolejne cykle – oba zlecenia oczekujące (maker)
+    pub repeat_profit: f64,
+}
+
+pub struct FeeReport {
+    pub cycles: Vec<LevelCycle>,
+    pub min_step: f64,
+    pub min_step_taker: f64,
+    pub warnings: Vec<String>,
+    /// Cykl maker/maker co najmniej jednego poziomu nie pokrywa prowizji
+    pub unprofitable: bool,
+}
+
+/// Zysk z cyklu każdego poziomu gridu po prowizjach przy cenie odniesienia `price`
+pub fn analyze(pair: &PairConfig, capital: f64, price: f64, fees: &FeeConfig) -> FeeReport {
+    let prices = sizing::grid_buy_prices(pair, price);
+    let quantities = sizing::level_quantities(pair, capital, &prices);
+    let cycles: Vec<LevelCycle> = prices.iter().zip(&quantities).enumerate().map(|(index, (&buy_price, &quantity))| {
+        let initial = index < pair.levels_above as usize;
+        let level = if initial { index as i32 + 1 } else { 1 };
+        let sell_price = pair.level_price(buy_price, level);
+        let value = buy_price * quantity;
+        let entry_fee = if initial { fees.taker } else { fees.maker };
+        LevelCycle {
+            buy_price,
+            sell_price,
+            value,
+            first_profit: cycle_profit(buy_price, sell_price, value, entry_fee, fees.maker),
+            repeat_profit: cycle_profit(buy_price, pair.level_price(buy_price, 1), value, fees.maker, fees.maker),
+        }
+    }).collect();
+
+    let min_step = min_viable_step(fees.maker, fees.maker);
+    let min_step_taker = min_viable_step(fees.taker, fees.maker);
+    let unprofitable = cycles.iter().any(|cycle| cycle.repeat_profit <= 0.0);
+
+    let mut warnings = Vec::new();
+    if unprofitable {
+        warnings.push(format!(
+            "step {:.4}% is below the minimum viable step {:.4}% – every cycle loses money",
+            pair.step * 100.0, min_step * 100.0
+        ));
+    } else if pair.step < 2.0 * min_step {
+        warnings.push(format!("more than half of each cycle's gross profit goes to fees (step {:.4}%)", pair.step * 100.0));
+    }
+    if cycles.iter().any(|cycle| cycle.first_profit <= 0.0) {
+        warnings.push(format!(
+            "initial market buys pay taker fees – their first cycle needs a step of at least {:.4}%",
+            min_step_taker * 100.0
+        ));
+    }
+    if pair.reinvest_offset > 0.0 && pair.reinvest_offset < min_step {
+        warnings.push(format!(
+            "reinvest_offset {:.4}% is below the minimum viable step – rebuys will not cover fees",
+            pair.reinvest_offset * 100.0
+        ));
+    }
+
+    FeeReport { cycles, min_step, min_step_taker, warnings, unprofitable }
+}
+
+/// Cena, przy której sprzedaż całego zapasu (po prowizji) zwraca jego koszt: (ilość, cena)
+pub fn break_even_price(db: &Connection, symbol: &str, sell_fee: f64) -> Option<(f64, f64)> {
+    let position = portfolio::load_position(db, symbol);
+    if position.held_qty <= 0.0 {
+        return None;
+    }
+    Some((position.held_qty, position.cost_basis / (position.held_qty * (1.0 - sell_fee))))
+}
+
+pub fn print_report(db: &Connection, pair: &PairConfig, report: &FeeReport, fees: &FeeConfig) {
+    let (base_asset, quote_asset) = split_symbol(&pair.symbol);
+    println!(
+        "\n🧮 **Fee check for {}** (maker {:.3}%, taker {:.3}%, step {:.3}%)",
+        pair.symbol, fees.maker * 100.0, fees.taker * 100.0, pair.step * 100.0
+    );
+    for (index, cycle) in report.cycles.iter().enumerate() {
+        println!(
+            "   {:>2}. Buy {:>14.4} -> Sell {:>14.4} | Value: {:>10.2} | First cycle: {:>+10.4} | Next cycles: {:>+10.4} {} ({:+.3}%)",
+            index + 1, cycle.buy_price, cycle.sell_price, cycle.value, cycle.first_profit, cycle.repeat_profit,
+            quote_asset, if cycle.value > 0.0 { cycle.repeat_profit / cycle.value * 100.0 } else { 0.0 }
+        );
+    }
+    println!(
+        "   Minimum viable step: {:.4}% (maker/maker), {:.4}% (taker entry)",
+        report.min_step * 100.0, report.min_step_taker * 100.0
+    );
+    match break_even_price(db, &pair.symbol, fees.maker) {
+        Some((held, price)) => println!("   Break-even of current inventory: {:.8} {} at {:.4} {}", held, base_asset, price, quote_asset),
+        None => println!("   Break-even of current inventory: no {} held", base_asset),
+    }
+    for warning in &report.warnings {
+        println!("   ⚠️ {}", warning);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycle_profit_without_fees_is_the_price_gain() {
+        assert!((cycle_profit(100.0, 105.0, 1000.0, 0.0, 0.0) - 50.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn cycle_profit_pays_fees_on_both_legs() {
+        // 10 base - 0.1% = 9.99, sprzedaż 9.99 * 105 * 0.999 = 1047.901...
+        let profit = cycle_profit(100.0, 105.0, 1000.0, 0.001, 0.001);
+        assert!((profit - (9.99 * 105.0 * 0.999 - 1000.0)).abs() < 1e-9);
+        assert!(profit < 50.0);
+    }
+
+    #[test]
+    fn min_viable_step_breaks_even() {
+        for (buy_fee, sell_fee) in [(0.001, 0.001), (0.00075, 0.001), (0.0, 0.0)] {
+            let step = min_viable_step(buy_fee, sell_fee);
+            let profit = cycle_profit(100.0, 100.0 * (1.0 + step), 1000.0, buy_fee, sell_fee);
+            assert!(profit.abs() < 1e-9, "fees {} / {}: {}", buy_fee, sell_fee, profit);
+        }
+        assert!((min_viable_step(0.001, 0.001) - 0.002003).abs() < 1e-6);
+    }
+
+    #[test]
+    fn step_below_minimum_is_unprofitable() {
+        let fees = FeeConfig::default();
+        let pair = PairConfig { step: 0.001, reinvest_offset: 0.0, ..PairConfig::default_for("BTCUSDT") };
+        let report = analyze(&pair, 1000.0, 100.0, &fees);
+        assert!(report.unprofitable);
+        assert!(report.warnings[0].contains("minimum viable step"));
+    }
+
+    #[test]
+    fn wide_step_has_no_warnings() {
+        let pair = PairConfig { step: 0.05, reinvest_offset: 0.05, ..PairConfig::default_for("BTCUSDT") };
+        let report = analyze(&pair, 1000.0, 100.0, &FeeConfig::default());
+        assert!(!report.unprofitable);
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        assert_eq!(report.cycles.len(), (pair.levels_above + pair.levels_below) as usize);
+    }
+}
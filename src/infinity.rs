@@ -0,0 +1,154 @@
+use crate::strategy::{FillEvent, OrderIntent, OrderSpec, Strategy, StrategyContext};
+
+/// Infinity grid: bez górnej granicy, wartość zapasu base w quote utrzymywana na poziomie
+/// `capital * infinity_hold` – na każdym poziomie wyżej sprzedaje nadwyżkę, niżej dokupuje
+pub struct InfinityGrid;
+
+impl InfinityGrid {
+    /// Po jednym zleceniu w każdą stronę od ceny `anchor`, tak by po wykonaniu
+    /// wartość zapasu base wróciła do stałej wartości
+    fn ladder(ctx: &StrategyContext, anchor: f64) -> Vec<OrderIntent> {
+        let pair = ctx.pair;
+        let hold_value = ctx.capital * pair.infinity_hold;
+        let mut intents = Vec::new();
+
+        // 📈 Poziom wyżej: sprzedaż nadwyżki ponad stałą wartość
+        let up_price = pair.level_price(anchor, 1);
+        let sell_quantity = ctx.held - hold_value / up_price;
+        if sell_quantity > 0.0 {
+            intents.push(OrderIntent::Place(OrderSpec::new("SELL", up_price, sell_quantity)));
+        }
+
+        // 📉 Poziom niżej: dokupienie brakującej wartości (tylko powyżej dolnej granicy)
+        let down_price = pair.level_price(anchor, -1);
+        let buy_quantity = hold_value / down_price - ctx.held;
+        if down_price >= ctx.min_price && buy_quantity > 0.0 {
+            intents.push(OrderIntent::Place(OrderSpec::new("BUY", down_price, buy_quantity)));
+        }
+        intents
+    }
+}
+
+impl Strategy for InfinityGrid {
+    /// Dokupuje base do stałej wartości; poziomy powstają po wykonaniu tego kupna
+    fn on_start(&mut self, ctx: &StrategyContext, price: f64) -> Vec<OrderIntent> {
+        if price < ctx.min_price {
+            return Vec::new();
+        }
+        let initial_quantity = ctx.capital * ctx.pair.infinity_hold / price - ctx.held;
+        if initial_quantity > 0.0 {
+            vec![OrderIntent::Place(OrderSpec::new("BUY", price, initial_quantity))]
+        } else {
+            Self::ladder(ctx, price)
+        }
+    }
+
+    /// Po wykonaniu poziomu anuluje pozostałe i składa nowe wokół ceny wykonania
+    fn on_fill(&mut self, ctx: &StrategyContext, fill: &FillEvent) -> Vec<OrderIntent> {
+        let mut intents: Vec<OrderIntent> = ctx.open_orders.iter()
+            .filter(|order| order.order_id != fill.order_id)
+            .map(|order| OrderIntent::Cancel(order.order_id))
+            .collect();
+        intents.extend(Self::ladder(ctx, fill.price));
+        intents
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{PairConfig, SpacingType};
+    use crate::strategy::OpenOrder;
+
+    fn pair() -> PairConfig {
+        PairConfig { spacing: SpacingType::Arithmetic, step: 0.1, infinity_hold: 0.5, ..PairConfig::default_for("BTCUSDT") }
+    }
+
+    fn context(pair: &PairConfig, held: f64, open_orders: Vec<OpenOrder>) -> StrategyContext<'_> {
+        StrategyContext {
+            pair,
+            capital: 1000.0,
+            base_amount: 0.0,
+            min_price: 50.0,
+            held,
+            avg_entry: 100.0,
+            last_buy: None,
+            now: 0,
+            open_orders,
+        }
+    }
+
+    fn placed(intents: &[OrderIntent]) -> Vec<(&'static str, f64, f64)> {
+        intents.iter().filter_map(|intent| match intent {
+            OrderIntent::Place(spec) => Some((spec.side, spec.price, spec.quantity)),
+            OrderIntent::Cancel(_) => None,
+        }).collect()
+    }
+
+    fn assert_order(actual: (&'static str, f64, f64), side: &str, price: f64, quantity: f64) {
+        assert_eq!(actual.0, side);
+        assert!((actual.1 - price).abs() < 1e-9, "price {} != {}", actual.1, price);
+        assert!((actual.2 - quantity).abs() < 1e-9, "quantity {} != {}", actual.2, quantity);
+    }
+
+    #[test]
+    fn start_buys_up_to_the_hold_value() {
+        let pair = pair();
+        let intents = InfinityGrid.on_start(&context(&pair, 1.0, Vec::new()), 100.0);
+
+        let placed = placed(&intents);
+        assert_eq!(placed.len(), 1);
+        assert_order(placed[0], "BUY", 100.0, 4.0);
+    }
+
+    #[test]
+    fn start_below_the_lower_bound_places_nothing() {
+        let pair = pair();
+        assert!(InfinityGrid.on_start(&context(&pair, 0.0, Vec::new()), 40.0).is_empty());
+    }
+
+    #[test]
+    fn ladder_keeps_the_hold_value_on_both_sides() {
+        let pair = pair();
+        // 5 base po 100 to dokładnie 500 quote – od razu poziomy
+        let intents = InfinityGrid.on_start(&context(&pair, 5.0, Vec::new()), 100.0);
+
+        let placed = placed(&intents);
+        assert_eq!(placed.len(), 2);
+        assert_order(placed[0], "SELL", 110.0, 5.0 - 500.0 / 110.0);
+        assert_order(placed[1], "BUY", 90.0, 500.0 / 90.0 - 5.0);
+    }
+
+    #[test]
+    fn no_buy_level_below_the_lower_bound() {
+        let pair = pair();
+        let fill = FillEvent {
+            order_id: 7, side: "BUY".to_string(), price: 52.0, quantity: 1.0,
+            net_base: 1.0, net_quote: 0.0, level: None, covers_qty: None,
+        };
+        let intents = InfinityGrid.on_fill(&context(&pair, 9.0, Vec::new()), &fill);
+
+        let placed = placed(&intents);
+        assert_eq!(placed.len(), 1);
+        assert_eq!(placed[0].0, "SELL");
+    }
+
+    #[test]
+    fn fill_cancels_the_other_levels_and_recenters() {
+        let pair = pair();
+        let open = |order_id, side: &str, price| OpenOrder { order_id, side: side.to_string(), price, quantity: 0.5 };
+        let ctx = context(&pair, 500.0 / 110.0, vec![open(1, "SELL", 110.0), open(2, "BUY", 90.0)]);
+        let fill = FillEvent {
+            order_id: 1, side: "SELL".to_string(), price: 110.0, quantity: 0.5,
+            net_base: 0.0, net_quote: 55.0, level: None, covers_qty: None,
+        };
+        let intents = InfinityGrid.on_fill(&ctx, &fill);
+
+        assert!(matches!(intents[0], OrderIntent::Cancel(2)));
+        assert_eq!(intents.iter().filter(|intent| matches!(intent, OrderIntent::Cancel(_))).count(), 1);
+        let placed = placed(&intents);
+        assert_eq!(placed.len(), 2);
+        assert_order(placed[0], "SELL", 121.0, 500.0 / 110.0 - 500.0 / 121.0);
+        assert_order(placed[1], "BUY", 99.0, 500.0 / 99.0 - 500.0 / 110.0);
+    }
+}
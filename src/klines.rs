@@ -0,0 +1,255 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use reqwest::Client;
+use rusqlite::{params, Connection};
+use serde_json::Value;
+
+use crate::{base_url, Candle};
+
+/// Maksymalna liczba świec w jednej odpowiedzi `/api/v3/klines`
+const PAGE_LIMIT: usize = 1000;
+
+/// Lokalny magazyn świec (backtesty, propozycje zakresu i wykresy bez sieci)
+pub fn setup(conn: &Connection) {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS klines (
+            symbol TEXT NOT NULL,
+            interval TEXT NOT NULL,
+            open_time INTEGER NOT NULL,
+            open REAL NOT NULL,
+            high REAL NOT NULL,
+            low REAL NOT NULL,
+            close REAL NOT NULL,
+            volume REAL NOT NULL,
+            close_time INTEGER NOT NULL,
+            PRIMARY KEY (symbol, interval, open_time)
+        )",
+        [],
+    ).expect("Failed to create klines table");
+    // Luki, których giełda nie uzupełnia (np. przerwy techniczne) – nie są pobierane ani zgłaszane ponownie
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS kline_gaps (
+            symbol TEXT NOT NULL,
+            interval TEXT NOT NULL,
+            before_open_time INTEGER NOT NULL,
+            after_open_time INTEGER NOT NULL,
+            PRIMARY KEY (symbol, interval, before_open_time)
+        )",
+        [],
+    ).expect("Failed to create kline_gaps table");
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as i64)
+}
+
+/// Jedna strona świec od `start_ms` (włącznie) do `end_ms`
+async fn fetch_page(client: &Client, symbol: &str, interval: &str, start_ms: i64, end_ms: i64) -> Result<Vec<Value>, String> {
+    let url = format!(
+        "{}/api/v3/klines?symbol={}&interval={}&startTime={}&endTime={}&limit={}",
+        base_url(), symbol, interval, start_ms, end_ms, PAGE_LIMIT
+    );
+    let response = client.get(&url).send().await.map_err(|e| e.to_string())?
+        .json::<Value>().await.map_err(|e| e.to_string())?;
+    response.as_array().cloned().ok_or_else(|| format!("Unexpected klines response: {}", response))
+}
+
+/// Zapisuje zamknięte świece (z `close_time` przed `now_ms`); zwraca liczbę zapisanych.
+/// Niepoprawny wiersz przerywa zapis – zera w cenach zafałszowałyby backtesty.
+fn store(db: &Connection, symbol: &str, interval: &str, rows: &[Value], now_ms: i64) -> Result<usize, String> {
+    let mut stored = 0;
+    for row in rows {
+        let integer = |i: usize| row[i].as_i64().ok_or_else(|| format!("Invalid kline field {} in {}", i, row));
+        let number = |i: usize| {
+            row[i].as_str()
+                .and_then(|value| value.parse::<f64>().ok())
+                .ok_or_else(|| format!("Invalid kline field {} in {}", i, row))
+        };
+        let close_time = integer(6)?;
+        // Jeszcze otwarta świeca – zostanie zapisana po zamknięciu
+        if close_time >= now_ms {
+            continue;
+        }
+        db.execute(
+            "INSERT OR REPLACE INTO klines (symbol, interval, open_time, open, high, low, close, volume, close_time)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![symbol, interval, integer(0)?, number(1)?, number(2)?, number(3)?, number(4)?, number(5)?, close_time],
+        ).expect("Failed to store kline");
+        stored += 1;
+    }
+    Ok(stored)
+}
+
+/// Pobiera strona po stronie świece z zakresu [`from_ms`, `to_ms`]; zwraca liczbę zapisanych
+async fn download_range(db: &Connection, client: &Client, symbol: &str, interval: &str, from_ms: i64, to_ms: i64) -> Result<usize, String> {
+    let mut start = from_ms;
+    let mut stored = 0;
+    while start <= to_ms {
+        let rows = fetch_page(client, symbol, interval, start, to_ms).await?;
+        let Some(last_open) = rows.last().and_then(|row| row[0].as_i64()) else {
+            break;
+        };
+        stored += store(db, symbol, interval, &rows, now_ms())?;
+        // Niepełna strona lub brak postępu (np. serwer ignorujący startTime) – koniec
+        if rows.len() < PAGE_LIMIT || last_open < start {
+            break;
+        }
+        start = last_open + 1;
+    }
+    Ok(stored)
+}
+
+fn last_open_time(db: &Connection, symbol: &str, interval: &str) -> Option<i64> {
+    db.query_row(
+        "SELECT MAX(open_time) FROM klines WHERE symbol = ?1 AND interval = ?2",
+        params![symbol, interval],
+        |row| row.get(0),
+    ).ok().flatten()
+}
+
+/// Luki w zapisanych świecach: (open_time ostatniej przed luką, open_time pierwszej po niej)
+pub fn gaps(db: &Connection, symbol: &str, interval: &str, step_ms: i64) -> Vec<(i64, i64)> {
+    let mut stmt = db.prepare(
+        "SELECT open_time FROM klines WHERE symbol = ?1 AND interval = ?2 ORDER BY open_time ASC"
+    ).expect("Failed to prepare statement");
+    let times: Vec<i64> = stmt.query_map(params![symbol, interval], |row| row.get(0))
+        .expect("Failed to query klines")
+        .filter_map(Result::ok)
+        .collect();
+    times.windows(2)
+        .filter(|w| w[1] - w[0] > step_ms)
+        .map(|w| (w[0], w[1]))
+        .collect()
+}
+
+/// Przyrostowe pobranie świec pary: od ostatniej zapisanej (lub `days` wstecz) do teraz, potem łatanie luk
+pub async fn sync(db: &Connection, client: &Client, symbol: &str, interval: &str, step_ms: i64, days: u32) -> Result<(), String> {
+    let now = now_ms();
+    let from = last_open_time(db, symbol, interval).unwrap_or(now - days as i64 * 86_400_000);
+    let stored = download_range(db, client, symbol, interval, from, now).await?;
+    println!("📥 {} {}: {} klines downloaded", symbol, interval, stored);
+
+    let found = unfilled_gaps(db, symbol, interval, step_ms);
+    if found.is_empty() {
+        return Ok(());
+    }
+    println!("🕳️ {} {}: {} gaps found, refilling", symbol, interval, found.len());
+    for &(before, after) in &found {
+        download_range(db, client, symbol, interval, before + step_ms, after - 1).await?;
+    }
+    // Pozostałe luki to zwykle przerwy techniczne giełdy – bez świec do pobrania; zapamiętujemy je
+    for (before, after) in unfilled_gaps(db, symbol, interval, step_ms) {
+        println!(
+            "   ⚠️ {} {}: {} missing klines after open_time {} (not available on the exchange)",
+            symbol, interval, (after - before) / step_ms - 1, before
+        );
+        db.execute(
+            "INSERT OR REPLACE INTO kline_gaps (symbol, interval, before_open_time, after_open_time) VALUES (?1, ?2, ?3, ?4)",
+            params![symbol, interval, before, after],
+        ).expect("Failed to record kline gap");
+    }
+    Ok(())
+}
+
+/// Luki, których nie ma jeszcze w `kline_gaps` (nieuzupełnialnych)
+fn unfilled_gaps(db: &Connection, symbol: &str, interval: &str, step_ms: i64) -> Vec<(i64, i64)> {
+    gaps(db, symbol, interval, step_ms).into_iter()
+        .filter(|(before, after)| !db.query_row(
+            "SELECT EXISTS(SELECT 1 FROM kline_gaps WHERE symbol = ?1 AND interval = ?2 AND before_open_time = ?3 AND after_open_time = ?4)",
+            params![symbol, interval, before, after],
+            |row| row.get::<_, bool>(0),
+        ).unwrap_or(false))
+        .collect()
+}
+
+/// Ostatnie `limit` zapisanych świec pary (od najstarszej)
+pub fn load(db: &Connection, symbol: &str, interval: &str, limit: usize) -> Vec<Candle> {
+    let mut stmt = db.prepare(
+        "SELECT open_time, open, high, low, close FROM (
+             SELECT open_time, open, high, low, close FROM klines
+             WHERE symbol = ?1 AND interval = ?2 ORDER BY open_time DESC LIMIT ?3
+         ) ORDER BY open_time ASC"
+    ).expect("Failed to prepare statement");
+    stmt.query_map(params![symbol, interval, limit as i64], |row| {
+        Ok(Candle { open_time: row.get(0)?, open: row.get(1)?, high: row.get(2)?, low: row.get(3)?, close: row.get(4)? })
+    })
+        .expect("Failed to query klines")
+        .filter_map(Result::ok)
+        .collect()
+}
+
+/// Podsumowanie zapisanych świec: para, interwał, liczba, zakres czasu
+pub fn show_coverage(db: &Connection) {
+    let mut stmt = db.prepare(
+        "SELECT symbol, interval, COUNT(*), datetime(MIN(open_time) / 1000, 'unixepoch'), datetime(MAX(open_time) / 1000, 'unixepoch')
+         FROM klines GROUP BY symbol, interval ORDER BY symbol, interval"
+    ).expect("Failed to prepare statement");
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?, row.get::<_, String>(3)?, row.get::<_, String>(4)?))
+    }).expect("Failed to query klines");
+
+    println!("\n🗄️ **Local market data**");
+    for (symbol, interval, count, first, last) in rows.filter_map(Result::ok) {
+        println!("   {} {:>4} | {:>7} klines | {} -> {}", symbol, interval, count, first, last);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const MINUTE: i64 = 60_000;
+
+    fn test_db() -> Connection {
+        let db = Connection::open_in_memory().unwrap();
+        setup(&db);
+        db
+    }
+
+    fn row(open_time: i64, close: &str) -> Value {
+        json!([open_time, "100.0", "101.0", "99.0", close, "12.5", open_time + MINUTE - 1])
+    }
+
+    fn store_minutes(db: &Connection, minutes: &[i64]) {
+        let rows: Vec<Value> = minutes.iter().map(|m| row(m * MINUTE, "100.5")).collect();
+        store(db, "BTCUSDT", "1m", &rows, i64::MAX).unwrap();
+    }
+
+    #[test]
+    fn stores_closed_candles_and_skips_the_open_one() {
+        let db = test_db();
+        let rows = vec![row(0, "100.5"), row(MINUTE, "100.7")];
+        assert_eq!(store(&db, "BTCUSDT", "1m", &rows, MINUTE + 10).unwrap(), 1);
+
+        let candles = load(&db, "BTCUSDT", "1m", 10);
+        assert_eq!(candles.len(), 1);
+        assert_eq!(candles[0].close, 100.5);
+    }
+
+    #[test]
+    fn unparseable_field_is_an_error() {
+        let db = test_db();
+        assert!(store(&db, "BTCUSDT", "1m", &[row(0, "not a number")], i64::MAX).is_err());
+        assert!(store(&db, "BTCUSDT", "1m", &[json!(["x"])], i64::MAX).is_err());
+        assert!(load(&db, "BTCUSDT", "1m", 10).is_empty());
+    }
+
+    #[test]
+    fn finds_gaps_between_stored_candles() {
+        let db = test_db();
+        store_minutes(&db, &[0, 1, 2, 5, 6, 8]);
+        assert_eq!(gaps(&db, "BTCUSDT", "1m", MINUTE), vec![(2 * MINUTE, 5 * MINUTE), (6 * MINUTE, 8 * MINUTE)]);
+        assert!(gaps(&db, "BTCUSDT", "5m", MINUTE).is_empty());
+    }
+
+    #[test]
+    fn remembered_gaps_are_not_reported_again() {
+        let db = test_db();
+        store_minutes(&db, &[0, 3, 4, 6]);
+        db.execute(
+            "INSERT INTO kline_gaps (symbol, interval, before_open_time, after_open_time) VALUES ('BTCUSDT', '1m', ?1, ?2)",
+            params![0, 3 * MINUTE],
+        ).unwrap();
+        assert_eq!(unfilled_gaps(&db, "BTCUSDT", "1m", MINUTE), vec![(4 * MINUTE, 6 * MINUTE)]);
+    }
+}
@@ -0,0 +1,368 @@
+use rusqlite::{params, Connection};
+
+use crate::config::ProfitMode;
+use crate::{config, portfolio};
+
+/// Konta księgi kapitału pary – każdy wpis przenosi kwotę (w quote) z jednego konta na drugie
+pub const EXTERNAL: &str = "external";
+pub const FREE: &str = "free";
+pub const RESERVED: &str = "reserved";
+pub const INVENTORY: &str = "inventory";
+/// Zysk odłożony przy `profit_mode = "sweep"` – grid go nie wydaje
+pub const HARVESTED: &str = "harvested";
+
+/// Stan księgi kapitału dla jednej pary
+#[derive(Debug, Clone, Default)]
+pub struct LedgerBalances {
+    pub allocation: f64,
+    pub reserved: f64,
+    pub inventory: f64,
+    pub realized_profit: f64,
+    pub free: f64,
+    pub harvested: f64,
+}
+
+impl LedgerBalances {
+    /// Różnica między źródłami (przydział + zysk) a wykorzystaniem kapitału – powinna wynosić 0
+    pub fn imbalance(&self) -> f64 {
+        self.allocation + self.realized_profit - (self.working_capital() + self.harvested)
+    }
+
+    /// Kapitał, którym handluje grid (przydział + zysk pozostawiony w parze)
+    pub fn working_capital(&self) -> f64 {
+        self.free + self.reserved + self.inventory
+    }
+}
+
+pub fn setup(conn: &Connection) {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS capital_ledger (
+            id INTEGER PRIMARY KEY,
+            symbol TEXT NOT NULL,
+            timestamp TEXT NOT NULL,
+            event TEXT NOT NULL,
+            from_account TEXT NOT NULL,
+            to_account TEXT NOT NULL,
+            amount REAL NOT NULL,
+            ref TEXT NOT NULL,
+            UNIQUE(symbol, event, ref)
+        )",
+        [],
+    ).expect("Failed to create capital_ledger table");
+
+    bootstrap_existing_pairs(conn);
+}
+
+/// Księguje przeniesienie kwoty między kontami (ujemna kwota odwraca kierunek).
+/// Wpis z tym samym (symbol, event, ref) jest księgowany tylko raz.
+pub fn post(db: &Connection, symbol: &str, event: &str, from: &str, to: &str, amount: f64, reference: &str) {
+    if amount.abs() < 1e-12 {
+        return;
+    }
+    let (from, to, amount) = if amount < 0.0 { (to, from, -amount) } else { (from, to, amount) };
+    db.execute(
+        "INSERT OR IGNORE INTO capital_ledger (symbol, timestamp, event, from_account, to_account, amount, ref)
+         VALUES (?1, datetime('now'), ?2, ?3, ?4, ?5, ?6)",
+        params![symbol, event, from, to, amount, reference],
+    ).expect("Failed to post capital ledger entry");
+}
+
+pub fn balance(db: &Connection, symbol: &str, account: &str) -> f64 {
+    db.query_row(
+        "SELECT COALESCE(SUM(CASE WHEN to_account = ?2 THEN amount ELSE 0 END), 0.0)
+              - COALESCE(SUM(CASE WHEN from_account = ?2 THEN amount ELSE 0 END), 0.0)
+         FROM capital_ledger WHERE symbol = ?1",
+        params![symbol, account],
+        |row| row.get(0),
+    ).unwrap_or(0.0)
+}
+
+/// Suma wpisów danego typu ze znakiem (przychodzące z `external` dodatnie)
+fn external_inflow(db: &Connection, symbol: &str, events: &[&str]) -> f64 {
+    let list = events.iter().map(|e| format!("'{}'", e)).collect::<Vec<_>>().join(",");
+    db.query_row(
+        &format!(
+            "SELECT COALESCE(SUM(CASE WHEN from_account = '{ext}' THEN amount ELSE -amount END), 0.0)
+             FROM capital_ledger WHERE symbol = ?1 AND event IN ({list})
+               AND (from_account = '{ext}' OR to_account = '{ext}')",
+            ext = EXTERNAL,
+            list = list
+        ),
+        params![symbol],
+        |row| row.get(0),
+    ).unwrap_or(0.0)
+}
+
+pub fn balances(db: &Connection, symbol: &str) -> LedgerBalances {
+    LedgerBalances {
+        allocation: external_inflow(db, symbol, &["ALLOCATE", "OPENING", "ALLOCATE_BASE"]),
+        reserved: balance(db, symbol, RESERVED),
+        inventory: balance(db, symbol, INVENTORY),
+        realized_profit: external_inflow(db, symbol, &["SELL_PROFIT", "SELL_PROFIT_ADJ", "OPENING_PROFIT"]),
+        free: balance(db, symbol, FREE),
+        harvested: balance(db, symbol, HARVESTED),
+    }
+}
+
+/// Zmiana przydziału kapitału pary – księgowana jest różnica względem dotychczasowego przydziału
+pub fn allocate(db: &Connection, symbol: &str, new_allocation: f64) {
+    let current = external_inflow(db, symbol, &["ALLOCATE", "OPENING"]);
+    let reference = format!("alloc:{}", now_ref(db));
+    post(db, symbol, "ALLOCATE", EXTERNAL, FREE, new_allocation - current, &reference);
+}
+
+/// Przydział base reverse gridu wchodzi do zapasu po wartości rynkowej (`cost` w quote),
+/// więc sprzedaż tego base realizuje tylko różnicę ceny, a nie cały przychód
+pub fn allocate_base(db: &Connection, symbol: &str, cost: f64) {
+    let current = external_inflow(db, symbol, &["ALLOCATE_BASE"]);
+    let reference = format!("alloc_base:{}", now_ref(db));
+    post(db, symbol, "ALLOCATE_BASE", EXTERNAL, INVENTORY, cost - current, &reference);
+}
+
+/// Blokuje kapitał pod otwarte zlecenie kupna
+pub fn reserve_for_buy(db: &Connection, symbol: &str, order_id: u64, amount: f64) {
+    post(db, symbol, "RESERVE", FREE, RESERVED, amount, &format!("order:{}", order_id));
+}
+
+/// Kwota wciąż zarezerwowana pod konkretne zlecenie
+pub fn reserved_for_order(db: &Connection, symbol: &str, order_id: u64) -> f64 {
+    db.query_row(
+        "SELECT COALESCE(SUM(CASE WHEN to_account = ?3 THEN amount ELSE -amount END), 0.0)
+         FROM capital_ledger
+         WHERE symbol = ?1 AND (ref = ?2 OR ref LIKE ?2 || ':%')
+           AND (from_account = ?3 OR to_account = ?3)",
+        params![symbol, format!("order:{}", order_id), RESERVED],
+        |row| row.get(0),
+    ).unwrap_or(0.0)
+}
+
+/// Księguje wykonanie kupna: rezerwacja (a jej brak – wolny kapitał) przechodzi w zapas po koszcie
+pub fn on_buy_fill(db: &Connection, symbol: &str, order_id: u64, trade_id: u64, cost: f64, quote_fee: f64) {
+    let from_reserved = reserved_for_order(db, symbol, order_id).clamp(0.0, cost);
+    let reference = format!("order:{}:trade:{}", order_id, trade_id);
+    post(db, symbol, "BUY_FILL", RESERVED, INVENTORY, from_reserved, &reference);
+    post(db, symbol, "BUY_FILL_FREE", FREE, INVENTORY, cost - from_reserved, &reference);
+    post(db, symbol, "BUY_FEE", FREE, INVENTORY, quote_fee, &reference);
+}
+
+/// Księguje wykonanie sprzedaży: koszt sprzedanego zapasu wraca do wolnego kapitału, wynik z zewnątrz
+pub fn on_sell_fill(db: &Connection, symbol: &str, trade_id: u64, proceeds: f64, profit: f64) {
+    let reference = format!("trade:{}", trade_id);
+    post(db, symbol, "SELL_COST", INVENTORY, FREE, proceeds - profit, &reference);
+    post(db, symbol, "SELL_PROFIT", EXTERNAL, FREE, profit, &reference);
+}
+
+/// Koryguje zaksięgowany zysk sprzedaży do `profit` (np. po wykonaniu starszym niż już zapisane).
+/// Różnica przechodzi między zapasem a wynikiem – wolny kapitał się nie zmienia.
+pub fn adjust_sell_profit(db: &Connection, symbol: &str, trade_id: u64, profit: f64) {
+    let reference = format!("trade:{}", trade_id);
+    let (booked, posted): (bool, f64) = db.query_row(
+        "SELECT EXISTS(SELECT 1 FROM capital_ledger WHERE symbol = ?1 AND ref = ?2 AND event IN ('SELL_COST', 'SELL_PROFIT')),
+                (SELECT COALESCE(SUM(CASE WHEN from_account = ?3 THEN amount ELSE -amount END), 0.0) FROM capital_ledger
+                 WHERE symbol = ?1 AND event IN ('SELL_PROFIT', 'SELL_PROFIT_ADJ') AND (ref = ?2 OR ref LIKE ?2 || ':%'))",
+        params![symbol, reference, EXTERNAL],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).unwrap_or((false, 0.0));
+    let difference = profit - posted;
+    // Sprzedaże sprzed księgi są w wpisie otwarcia
+    if !booked || difference.abs() < 1e-9 {
+        return;
+    }
+    let adjustment = format!("{}:adj:{}", reference, now_ref(db));
+    post(db, symbol, "SELL_PROFIT_ADJ", EXTERNAL, FREE, difference, &adjustment);
+    post(db, symbol, "SELL_COST_ADJ", FREE, INVENTORY, difference, &adjustment);
+    if profit_mode(db, symbol) == ProfitMode::Sweep {
+        sweep_profit(db, symbol, &adjustment, difference);
+    }
+}
+
+/// Tryb zysku pary zapisany w `capital` (pary bez wpisu – domyślny z konfiguracji)
+pub fn profit_mode(db: &Connection, symbol: &str) -> ProfitMode {
+    db.query_row(
+        "SELECT profit_mode FROM capital WHERE symbol = ?1",
+        params![symbol],
+        |row| row.get::<_, Option<String>>(0),
+    ).ok().flatten()
+        .and_then(|name| ProfitMode::parse(&name))
+        .unwrap_or_else(|| config::config().pair(symbol).profit_mode)
+}
+
+/// Tryb sweep: zysk ze sprzedaży trafia do puli `harvested`, a strata jest z niej pokrywana
+/// (najwyżej do jej wysokości – resztę ponosi kapitał pary)
+pub fn sweep_profit(db: &Connection, symbol: &str, reference: &str, profit: f64) {
+    if profit > 0.0 {
+        post(db, symbol, "SWEEP", FREE, HARVESTED, profit, reference);
+    } else {
+        let covered = (-profit).min(balance(db, symbol, HARVESTED));
+        if covered > 0.0 {
+            post(db, symbol, "SWEEP_LOSS", HARVESTED, FREE, covered, reference);
+        }
+    }
+}
+
+/// Zwalnia resztę rezerwacji zlecenia zamkniętego (wykonanego lub anulowanego)
+pub fn release_order(db: &Connection, symbol: &str, order_id: u64) {
+    let remaining = reserved_for_order(db, symbol, order_id);
+    if remaining > 0.0 {
+        post(db, symbol, "RELEASE", RESERVED, FREE, remaining, &format!("order:{}", order_id));
+    }
+}
+
+/// Otwiera księgę dla par, które istniały przed jej wprowadzeniem (przydział, zapas i wynik z `trades`)
+fn bootstrap_existing_pairs(conn: &Connection) {
+    let pairs: Vec<(String, f64)> = {
+        let mut stmt = conn.prepare(
+            "SELECT symbol, amount FROM capital
+             WHERE symbol NOT IN (SELECT DISTINCT symbol FROM capital_ledger)",
+        ).expect("Failed to prepare statement");
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .expect("Failed to query capital pairs")
+            .filter_map(Result::ok)
+            .collect()
+    };
+
+    for (symbol, amount) in pairs {
+        let position = portfolio::load_position(conn, &symbol);
+        post(conn, &symbol, "OPENING", EXTERNAL, FREE, amount, "opening");
+        post(conn, &symbol, "OPENING_PROFIT", EXTERNAL, FREE, position.realized_pnl, "opening");
+        post(conn, &symbol, "OPENING_INVENTORY", FREE, INVENTORY, position.cost_basis, "opening");
+    }
+}
+
+fn now_ref(db: &Connection) -> String {
+    // Numer kolejnego wpisu rozróżnia referencje z tej samej milisekundy
+    db.query_row(
+        "SELECT strftime('%Y%m%d%H%M%f', 'now') || '-' || (SELECT COALESCE(MAX(id), 0) + 1 FROM capital_ledger)",
+        [],
+        |row| row.get(0),
+    )
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYMBOL: &str = "BTCUSDT";
+
+    fn test_db() -> Connection {
+        config::init_for_tests();
+        let db = Connection::open_in_memory().unwrap();
+        crate::create_schema(&db);
+        db
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn allocation_change_posts_only_the_difference() {
+        let db = test_db();
+        allocate(&db, SYMBOL, 1000.0);
+        allocate(&db, SYMBOL, 1500.0);
+        let balances = balances(&db, SYMBOL);
+        assert_close(balances.allocation, 1500.0);
+        assert_close(balances.free, 1500.0);
+        assert_close(balances.imbalance(), 0.0);
+    }
+
+    #[test]
+    fn buy_and_sell_cycle_stays_balanced() {
+        let db = test_db();
+        allocate(&db, SYMBOL, 1000.0);
+        reserve_for_buy(&db, SYMBOL, 1, 100.0);
+        assert_close(balances(&db, SYMBOL).reserved, 100.0);
+
+        on_buy_fill(&db, SYMBOL, 1, 11, 100.0, 0.1);
+        release_order(&db, SYMBOL, 1);
+        let after_buy = balances(&db, SYMBOL);
+        assert_close(after_buy.reserved, 0.0);
+        assert_close(after_buy.inventory, 100.1);
+        assert_close(after_buy.free, 899.9);
+        assert_close(after_buy.imbalance(), 0.0);
+
+        on_sell_fill(&db, SYMBOL, 12, 109.9, 9.8);
+        let after_sell = balances(&db, SYMBOL);
+        assert_close(after_sell.inventory, 0.0);
+        assert_close(after_sell.realized_profit, 9.8);
+        assert_close(after_sell.working_capital(), 1009.8);
+        assert_close(after_sell.imbalance(), 0.0);
+    }
+
+    #[test]
+    fn partial_fill_releases_the_rest_of_the_reservation() {
+        let db = test_db();
+        allocate(&db, SYMBOL, 500.0);
+        reserve_for_buy(&db, SYMBOL, 7, 200.0);
+        on_buy_fill(&db, SYMBOL, 7, 70, 120.0, 0.0);
+        assert_close(reserved_for_order(&db, SYMBOL, 7), 80.0);
+
+        release_order(&db, SYMBOL, 7);
+        let balances = balances(&db, SYMBOL);
+        assert_close(balances.reserved, 0.0);
+        assert_close(balances.free, 380.0);
+        assert_close(balances.imbalance(), 0.0);
+    }
+
+    #[test]
+    fn postings_with_the_same_reference_are_booked_once() {
+        let db = test_db();
+        allocate(&db, SYMBOL, 1000.0);
+        on_buy_fill(&db, SYMBOL, 1, 11, 100.0, 0.0);
+        on_buy_fill(&db, SYMBOL, 1, 11, 100.0, 0.0);
+        assert_close(balances(&db, SYMBOL).inventory, 100.0);
+    }
+
+    #[test]
+    fn changed_sell_profit_is_adjusted_without_drift() {
+        let db = test_db();
+        allocate(&db, SYMBOL, 1000.0);
+        on_buy_fill(&db, SYMBOL, 1, 11, 100.0, 0.0);
+        on_sell_fill(&db, SYMBOL, 12, 110.0, 10.0);
+
+        adjust_sell_profit(&db, SYMBOL, 12, 4.0);
+        let adjusted = balances(&db, SYMBOL);
+        assert_close(adjusted.realized_profit, 4.0);
+        assert_close(adjusted.working_capital(), 1004.0);
+        assert_close(adjusted.imbalance(), 0.0);
+
+        // Ten sam zysk ponownie – bez nowych wpisów
+        adjust_sell_profit(&db, SYMBOL, 12, 4.0);
+        assert_close(balances(&db, SYMBOL).realized_profit, 4.0);
+    }
+
+    #[test]
+    fn sweep_harvests_profit_and_covers_losses_up_to_the_pool() {
+        let db = test_db();
+        allocate(&db, SYMBOL, 1000.0);
+        on_buy_fill(&db, SYMBOL, 1, 11, 100.0, 0.0);
+        on_sell_fill(&db, SYMBOL, 12, 110.0, 10.0);
+        sweep_profit(&db, SYMBOL, "trade:12", 10.0);
+        assert_close(balances(&db, SYMBOL).harvested, 10.0);
+
+        on_buy_fill(&db, SYMBOL, 2, 13, 100.0, 0.0);
+        on_sell_fill(&db, SYMBOL, 14, 96.0, -4.0);
+        sweep_profit(&db, SYMBOL, "trade:14", -4.0);
+        let after_loss = balances(&db, SYMBOL);
+        assert_close(after_loss.harvested, 6.0);
+        assert_close(after_loss.working_capital(), 1000.0);
+        assert_close(after_loss.imbalance(), 0.0);
+
+        on_buy_fill(&db, SYMBOL, 3, 15, 100.0, 0.0);
+        on_sell_fill(&db, SYMBOL, 16, 90.0, -10.0);
+        sweep_profit(&db, SYMBOL, "trade:16", -10.0);
+        let drained = balances(&db, SYMBOL);
+        assert_close(drained.harvested, 0.0);
+        assert_close(drained.working_capital(), 996.0);
+        assert_close(drained.imbalance(), 0.0);
+    }
+
+    #[test]
+    fn unbooked_sell_is_not_adjusted() {
+        let db = test_db();
+        allocate(&db, SYMBOL, 1000.0);
+        adjust_sell_profit(&db, SYMBOL, 99, 5.0);
+        assert_close(balances(&db, SYMBOL).realized_profit, 0.0);
+    }
+}
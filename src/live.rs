@@ -0,0 +1,277 @@
+use std::collections::{HashSet, VecDeque};
+use std::time::{SystemTime, UNIX_EPOCH};
+use reqwest::Client;
+use rusqlite::{params, Connection};
+
+use crate::config::PairConfig;
+use crate::strategy::{self, FillEvent, OpenOrder, OrderIntent, Strategy, StrategyContext};
+use crate::{
+    cancel_order, credentials, dry_run, events, get_price, ledger, place_binance_order, portfolio,
+    print_order_plan, record_placed_order, send_signed_request, split_symbol, sync,
+};
+
+/// Otwarte zlecenia złożone przez bota dla pary
+fn open_orders(db: &Connection, symbol: &str) -> Vec<OpenOrder> {
+    let mut stmt = db.prepare(
+        "SELECT order_id, COALESCE(side, ''), price, quantity FROM orders
+         WHERE symbol = ?1 AND source = 'bot' AND status IN ('NEW', 'PARTIALLY_FILLED')
+         ORDER BY order_id ASC"
+    ).expect("Failed to prepare statement");
+    stmt.query_map(params![symbol], |row| {
+        Ok(OpenOrder { order_id: row.get(0)?, side: row.get(1)?, price: row.get(2)?, quantity: row.get(3)? })
+    })
+        .expect("Failed to query orders")
+        .filter_map(Result::ok)
+        .collect()
+}
+
+fn context<'a>(db: &Connection, pair: &'a PairConfig) -> StrategyContext<'a> {
+    let (base_amount, min_price) = db.query_row(
+        "SELECT base_amount, min_price FROM capital WHERE symbol = ?1",
+        params![pair.symbol],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).unwrap_or((0.0, 0.0));
+    // Compound: zysk zostaje w kapitale pary; sweep: odłożony zysk jest poza nim
+    let capital = ledger::balances(db, &pair.symbol).working_capital();
+    let position = portfolio::load_position(db, &pair.symbol);
+    // Ostatnie wykonane kupno złożone przez bota
+    let last_buy = db.query_row(
+        "SELECT CAST(strftime('%s', t.timestamp) AS INTEGER), t.price FROM trades t
+         JOIN orders o ON o.symbol = t.symbol AND o.order_id = t.order_id
+         WHERE t.symbol = ?1 AND t.type = 'Buy' AND o.source = 'bot'
+         ORDER BY t.timestamp DESC, t.id DESC LIMIT 1",
+        params![pair.symbol],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).ok();
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64);
+
+    StrategyContext {
+        pair,
+        capital,
+        base_amount,
+        min_price,
+        held: position.held_qty,
+        avg_entry: position.avg_entry_price(),
+        last_buy,
+        now,
+        open_orders: open_orders(db, &pair.symbol),
+    }
+}
+
+/// Zdarzenie wykonania z lokalnej kopii `trades` (ilości po prowizjach) i metadanych zlecenia
+fn fill_event(db: &Connection, symbol: &str, order_id: u64, side: &str, price: f64, quantity: f64) -> FillEvent {
+    let (base_asset, quote_asset) = split_symbol(symbol);
+    let (net_base, net_quote) = db.query_row(
+        "SELECT COALESCE(SUM(quantity), 0) - COALESCE(SUM(CASE WHEN commission_asset = ?2 THEN commission ELSE 0 END), 0),
+                COALESCE(SUM(price * quantity), 0) - COALESCE(SUM(CASE WHEN commission_asset = ?3 THEN commission ELSE 0 END), 0)
+         FROM trades WHERE symbol = ?4 AND order_id = ?1",
+        params![order_id, base_asset, quote_asset, symbol],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).unwrap_or((0.0, 0.0));
+    let (level, covers_qty) = db.query_row(
+        "SELECT level, covers_qty FROM orders WHERE order_id = ?1",
+        params![order_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).unwrap_or((None, None));
+
+    FillEvent { order_id, side: side.to_string(), price, quantity, net_base, net_quote, level, covers_qty }
+}
+
+/// Zamknięty cykl reverse grid (odkup sprzedanej ilości) – zysk w base trafia do `capital`
+fn record_reverse_cycle(db: &Connection, symbol: &str, fill: &FillEvent) {
+    let Some(covers) = fill.covers_qty.filter(|_| fill.side == "BUY") else {
+        return;
+    };
+    let (base_asset, _) = split_symbol(symbol);
+    let profit = fill.net_base - covers;
+    db.execute("UPDATE capital SET base_profit = base_profit + ?2 WHERE symbol = ?1", params![symbol, profit])
+        .expect("Failed to update base profit");
+    events::record(
+        db, Some(symbol), "REVERSE_CYCLE",
+        &format!("bought back {:.8} {} for {:.8} sold, profit {:.8} {}", fill.net_base, base_asset, covers, profit, base_asset),
+    );
+    println!("🔁 {} reverse cycle closed: profit {:.8} {}", symbol, profit, base_asset);
+}
+
+/// Wykonuje polecenia strategii; natychmiastowe wykonania od razu wracają do `on_fill`.
+/// W trybie próbnym zlecenia po cenie rynkowej traktowane są jako wykonane. Zwraca plan zleceń.
+async fn execute(
+    db: &mut Connection,
+    client: &Client,
+    strategy: &mut (dyn Strategy + Send),
+    pair: &PairConfig,
+    intents: Vec<OrderIntent>,
+    reference_price: f64
+) -> Vec<(&'static str, f64, f64)> {
+    let symbol = pair.symbol.as_str();
+    let mut queue: VecDeque<(OrderIntent, f64)> = intents.into_iter().map(|intent| (intent, reference_price)).collect();
+    let mut plan = Vec::new();
+    let mut simulated_held = 0.0;
+
+    while let Some((intent, reference)) = queue.pop_front() {
+        let spec = match intent {
+            OrderIntent::Cancel(order_id) => {
+                if let Err(e) = cancel_order(db, client, symbol, order_id).await {
+                    println!("❌ Failed to cancel order {} for {}: {}", order_id, symbol, e);
+                }
+                continue;
+            }
+            OrderIntent::Place(spec) => spec,
+        };
+
+        let Ok(order) = place_binance_order(db, client, symbol, spec.side, spec.price, spec.quantity, spec.is_marketable(reference)).await else {
+            continue;
+        };
+        record_placed_order(db, &order);
+        plan.push((spec.side, order.price, order.quantity));
+
+        let fill = if dry_run() {
+            if !spec.is_marketable(reference) {
+                continue;
+            }
+            simulated_held += if spec.side == "BUY" { order.quantity } else { -order.quantity };
+            FillEvent {
+                order_id: order.order_id,
+                side: spec.side.to_string(),
+                price: order.price,
+                quantity: order.quantity,
+                net_base: order.quantity,
+                net_quote: order.price * order.quantity,
+                level: spec.level,
+                covers_qty: spec.covers_qty,
+            }
+        } else {
+            db.execute(
+                "UPDATE orders SET level = ?2, covers_qty = ?3 WHERE order_id = ?1",
+                params![order.order_id, spec.level, spec.covers_qty],
+            ).expect("Failed to store order metadata");
+            if order.status != "FILLED" {
+                continue;
+            }
+            let fill = fill_event(db, symbol, order.order_id, spec.side, order.price, order.quantity);
+            record_reverse_cycle(db, symbol, &fill);
+            fill
+        };
+
+        let mut ctx = context(db, pair);
+        ctx.held += simulated_held;
+        for next in strategy.on_fill(&ctx, &fill) {
+            queue.push_back((next, fill.price));
+        }
+    }
+
+    plan
+}
+
+/// Uruchamia strategię pary wybraną w `capital.strategy`
+pub async fn start(db: &mut Connection, client: &Client, symbol: &str) {
+    let pair = strategy::pair_for(db, symbol);
+    let kind = pair.strategy;
+    let price = match get_price(symbol, client).await {
+        Ok(price) if price > 0.0 => price,
+        _ => {
+            println!("❌ Failed to fetch price for {}", symbol);
+            return;
+        }
+    };
+
+    let mut strategy = strategy::build(kind);
+    let intents = strategy.on_start(&context(db, &pair), price);
+    if intents.is_empty() {
+        println!("❌ {} strategy has nothing to place for {} (check capital, base allocation and price range)", kind.as_str(), symbol);
+        return;
+    }
+
+    println!("🚀 Starting {} strategy for {} at {:.8}", kind.as_str(), symbol, price);
+    let plan = execute(db, client, strategy.as_mut(), &pair, intents, price).await;
+
+    if dry_run() {
+        print_order_plan(symbol, &plan);
+        return;
+    }
+
+    db.execute("UPDATE capital SET is_active = 1 WHERE symbol = ?1", params![symbol])
+        .expect("Failed to update trading bot status");
+    println!("✅ Trading bot for {} started successfully!", symbol);
+}
+
+/// Zlecenia otwarte lokalnie, które giełda zamknęła bez wykonania (anulowane, wygasłe)
+async fn close_canceled_orders(db: &mut Connection, client: &Client, symbol: &str) -> Vec<OpenOrder> {
+    let local = open_orders(db, symbol);
+    if local.is_empty() {
+        return Vec::new();
+    }
+
+    let api = credentials::credentials();
+    let params = format!("symbol={}", symbol);
+    let exchange_ids: HashSet<u64> = match send_signed_request(client, reqwest::Method::GET, "/api/v3/openOrders", &params, &api.api_key, &api.secret_key).await {
+        Ok(orders) => orders.as_array()
+            .map(|list| list.iter().filter_map(|o| o["orderId"].as_u64()).collect())
+            .unwrap_or_default(),
+        Err(_) => return Vec::new(),
+    };
+
+    let mut canceled = Vec::new();
+    for order in local.into_iter().filter(|o| !exchange_ids.contains(&o.order_id)) {
+        let params = format!("symbol={}&orderId={}", symbol, order.order_id);
+        let status = match send_signed_request(client, reqwest::Method::GET, "/api/v3/order", &params, &api.api_key, &api.secret_key).await {
+            Ok(response) => response["status"].as_str().unwrap_or("").to_string(),
+            Err(_) => continue,
+        };
+        if matches!(status.as_str(), "CANCELED" | "EXPIRED" | "EXPIRED_IN_MATCH" | "REJECTED") {
+            db.execute("UPDATE orders SET status = ?1 WHERE order_id = ?2", params![status, order.order_id])
+                .expect("Failed to update order status");
+            ledger::release_order(db, symbol, order.order_id);
+            canceled.push(order);
+        }
+    }
+    canceled
+}
+
+/// Przekazuje strategiom aktywnych par wykonania, anulowania i cenę (wywoływane z pętli monitora)
+pub async fn run_active_grids(db: &mut Connection, client: &Client) {
+    let symbols: Vec<String> = {
+        let mut stmt = db.prepare("SELECT DISTINCT symbol FROM capital WHERE is_active = 1 ORDER BY symbol ASC")
+            .expect("Failed to prepare statement");
+        stmt.query_map([], |row| row.get(0))
+            .expect("Failed to query capital pairs")
+            .filter_map(Result::ok)
+            .collect()
+    };
+
+    for symbol in symbols {
+        let pair = strategy::pair_for(db, &symbol);
+        let mut strategy = strategy::build(pair.strategy);
+
+        for (order_id, side, price, quantity) in sync::close_filled_orders(db, &symbol) {
+            let fill = fill_event(db, &symbol, order_id, &side, price, quantity);
+            println!("📥 {} order {} filled: {} {:.8} at {:.8}", symbol, order_id, side, quantity, price);
+            record_reverse_cycle(db, &symbol, &fill);
+            let intents = strategy.on_fill(&context(db, &pair), &fill);
+            execute(db, client, strategy.as_mut(), &pair, intents, price).await;
+        }
+
+        for order in close_canceled_orders(db, client, &symbol).await {
+            println!("🗑️ {} order {} closed on Binance without fill", symbol, order.order_id);
+            let intents = strategy.on_cancel(&context(db, &pair), &order);
+            execute(db, client, strategy.as_mut(), &pair, intents, order.price).await;
+        }
+
+        if let Ok(price) = get_price(&symbol, client).await {
+            let intents = strategy.on_price(&context(db, &pair), price);
+            if !intents.is_empty() {
+                execute(db, client, strategy.as_mut(), &pair, intents, price).await;
+            }
+        }
+    }
+}
+
+/// Zatrzymuje strategię pary (`on_stop`); zwraca liczbę wykonanych poleceń
+pub async fn stop(db: &mut Connection, client: &Client, symbol: &str) -> usize {
+    let pair = strategy::pair_for(db, symbol);
+    let mut strategy = strategy::build(pair.strategy);
+    let intents = strategy.on_stop(&context(db, &pair));
+    let count = intents.len();
+    execute(db, client, strategy.as_mut(), &pair, intents, 0.0).await;
+    count
+}
@@ -1,49 +1,181 @@
 use hmac::KeyInit;
-use tokio::time::{sleep, Duration};
+use tokio::time::Duration;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use rusqlite::{params, Connection};
-use std::env;
-use std::fs;
-use std::collections::HashMap;
-use clap::{Command, Arg};
 use std::io;
-use futures::future::join_all;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use clap::{Arg, Command};
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
 use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
-use std::time::{SystemTime, UNIX_EPOCH};
 use dotenvy::dotenv;
 use serde_json::Value;
 
-use serde_json::json;
+mod breaker;
+mod config;
+mod credentials;
+mod dca;
+mod doctor;
+mod events;
+mod fees;
+mod infinity;
+mod klines;
+mod ledger;
+mod live;
+mod optimize;
+mod portfolio;
+mod ranges;
+mod reconcile;
+mod reverse;
+mod risk;
+mod sim;
+mod sizing;
+mod stops;
+mod strategy;
+mod sync;
+mod trailing;
+mod volatility;
+
+/// Waluty kwotowane rozpoznawane przy dzieleniu symbolu na base/quote
+const QUOTE_ASSETS: [&str; 9] = ["FDUSD", "USDT", "USDC", "BUSD", "TUSD", "EUR", "BTC", "ETH", "BNB"];
+
+/// Profile środowiska Binance Spot
+const MAINNET_BASE_URL: &str = "https://api.binance.com";
+const TESTNET_BASE_URL: &str = "https://testnet.binance.vision";
+
+/// Adres bazowy API ustawiany raz przy starcie (`--env`, `--base-url` lub config.toml)
+static BASE_URL: OnceLock<String> = OnceLock::new();
+
+/// Adres bazowy API Binance (domyślnie mainnet)
+fn base_url() -> &'static str {
+    BASE_URL.get().map(String::as_str).unwrap_or(MAINNET_BASE_URL)
+}
 
-/// Wysyła zlecenie na Binance i zwraca `orderId`
+/// Wybiera adres API: jawny URL ma pierwszeństwo przed profilem środowiska
+fn resolve_base_url(env_profile: Option<&str>, custom_url: Option<&str>) -> Result<String, String> {
+    if let Some(url) = custom_url {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(format!("Invalid base URL `{}` (expected http:// or https://)", url));
+        }
+        return Ok(url.trim_end_matches('/').to_string());
+    }
+    match env_profile.unwrap_or("mainnet") {
+        "mainnet" => Ok(MAINNET_BASE_URL.to_string()),
+        "testnet" => Ok(TESTNET_BASE_URL.to_string()),
+        other => Err(format!("Unknown environment profile `{}` (expected mainnet or testnet)", other)),
+    }
+}
 
+/// Tryb próbny (`--dry-run`): zlecenia walidowane przez `/api/v3/order/test`, nigdy nie wysyłane
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
 
-const TRADING_FEE_RATE: f64 = 0.001; // 0.1% standardowa opłata Binance
+fn dry_run() -> bool {
+    DRY_RUN.load(Ordering::SeqCst)
+}
+
+/// Pojedyncze wykonanie (fill) zlecenia wraz z rzeczywistą prowizją
+#[derive(Debug, Clone)]
+struct Fill {
+    trade_id: u64,
+    price: f64,
+    qty: f64,
+    commission: f64,
+    commission_asset: String,
+    time: u64,
+}
+
+impl Fill {
+    /// Parsuje fill z odpowiedzi `FULL` zlecenia lub z `myTrades`
+    fn from_json(value: &Value, fallback_time: u64) -> Fill {
+        let parse = |key: &str| value[key].as_str().unwrap_or("0.0").parse::<f64>().unwrap_or(0.0);
+        Fill {
+            trade_id: value["tradeId"].as_u64().or_else(|| value["id"].as_u64()).unwrap_or(0),
+            price: parse("price"),
+            qty: parse("qty"),
+            commission: parse("commission"),
+            commission_asset: value["commissionAsset"].as_str().unwrap_or("").to_string(),
+            time: value["time"].as_u64().unwrap_or(fallback_time),
+        }
+    }
+}
+
+/// Zlecenie przyjęte przez Binance razem z natychmiastowymi wykonaniami
+#[derive(Debug, Clone)]
+struct PlacedOrder {
+    order_id: u64,
+    symbol: String,
+    side: String,
+    /// `LIMIT` lub `MARKET`
+    order_type: String,
+    price: f64,
+    quantity: f64,
+    status: String,
+    fills: Vec<Fill>,
+}
+
+impl PlacedOrder {
+    /// Odpowiedź `newOrderRespType=FULL` z `/api/v3/order`
+    fn from_response(json: &Value, symbol: &str, side: &str, price: f64, quantity: f64) -> PlacedOrder {
+        // Wykonania bez własnego `time` dostają czas zlecenia, a gdy i jego brak – bieżący
+        let transact_time = json["transactTime"].as_u64()
+            .or_else(|| json["updateTime"].as_u64())
+            .unwrap_or_else(now_millis);
+        PlacedOrder {
+            order_id: json["orderId"].as_u64().unwrap_or(0),
+            symbol: symbol.to_string(),
+            side: side.to_string(),
+            order_type: json["type"].as_str().unwrap_or("LIMIT").to_string(),
+            price,
+            quantity,
+            status: json["status"].as_str().unwrap_or("NEW").to_string(),
+            fills: json["fills"].as_array()
+                .map(|list| list.iter().map(|f| Fill::from_json(f, transact_time)).collect())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Bieżący czas unix w milisekundach
+fn now_millis() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
+}
+
+/// Dzieli symbol na (base, quote), np. "LTCUSDC" -> ("LTC", "USDC")
+fn split_symbol(symbol: &str) -> (String, String) {
+    for quote in QUOTE_ASSETS {
+        if let Some(base) = symbol.strip_suffix(quote) {
+            if !base.is_empty() {
+                return (base.to_string(), quote.to_string());
+            }
+        }
+    }
+    (symbol.to_string(), String::new())
+}
 
 /// Składa zlecenie kupna lub sprzedaży na Binance
 async fn place_binance_order(
+    db: &mut Connection,
     client: &Client,
-    api_key: &str,
-    secret_key: &str,
     symbol: &str,
     side: &str,
     price: f64,
-    quantity: f64
-) -> Result<u64, String> {
-    let timestamp = get_binance_server_time().await.unwrap_or(0);
-    let mut adjusted_quantity = quantity;
-
-    // ⚠️ Uwzględnienie opłat Binance przy sprzedaży
-    if side == "SELL" {
-        adjusted_quantity *= (1.0 - TRADING_FEE_RATE);
+    quantity: f64,
+    at_market: bool
+) -> Result<PlacedOrder, String> {
+    let creds = credentials::credentials();
+    let (api_key, secret_key) = (creds.api_key.as_str(), creds.secret_key.as_str());
+    if !dry_run() && !doctor::live_trading_allowed() {
+        println!("⛔ Live trading disabled: API key self-check failed (run `doctor` for details)");
+        return Err("Live trading disabled by API key self-check".to_string());
     }
 
+    let timestamp = get_binance_server_time().await.unwrap_or(0);
+
     // 🔄 Pobranie wymaganej wielkości lota (LOT_SIZE)
     let (min_qty, step_size) = get_lot_size(symbol).await.unwrap_or((0.01, 0.01));
-    adjusted_quantity = adjust_quantity(adjusted_quantity, step_size);
+    let adjusted_quantity = adjust_quantity(quantity, step_size);
 
     if adjusted_quantity < min_qty {
         println!(
@@ -53,29 +185,61 @@ async fn place_binance_order(
         return Err("Quantity below minimum LOT_SIZE".to_string());
     }
 
-    // 🔄 Sprawdzenie dostępnego balansu przed sprzedażą
-    if side == "SELL" {
-        let base_asset = symbol.chars().take_while(|&c| c.is_alphabetic()).collect::<String>(); // np. "LTC" z "LTCUSDC"
-        let available_balance = get_available_balance(&base_asset, api_key, secret_key).await.unwrap_or(0.0);
+    let min_notional = get_min_notional(symbol).await.unwrap_or(10.0);
+    if price * adjusted_quantity < min_notional {
+        println!(
+            "⚠️ Skipping order for {} at {:.2}, value {:.2} below min NOTIONAL ({:.2})",
+            symbol, price, price * adjusted_quantity, min_notional
+        );
+        return Err("Order value below minimum NOTIONAL".to_string());
+    }
+
+    // 🛡️ Limity ryzyka – odrzucenie z podaniem powodu
+    let market_price = match get_price(symbol, client).await {
+        Ok(market_price) if market_price > 0.0 => market_price,
+        _ => {
+            println!("🛡️ Risk check rejected {} {}: cannot fetch current price", side, symbol);
+            return Err("Risk check failed: no market price".to_string());
+        }
+    };
+    let open_orders = risk::open_order_counts(&risk::open_orders(db, client).await, symbol);
+    let request = risk::OrderRequest { symbol, side, price, quantity: adjusted_quantity, market_price, at_market, open_orders };
+    if let Err(reason) = risk::check_order(db, &request) {
+        println!("🛡️ Risk check rejected {} {} at {:.8}: {}", side, symbol, price, reason);
+        return Err(format!("Risk check failed: {}", reason));
+    }
+
+    // 🔄 Sprawdzenie dostępnego balansu (base przy sprzedaży, quote przy kupnie)
+    let (base_asset, quote_asset) = split_symbol(symbol); // np. "LTC", "USDC" z "LTCUSDC"
+    let (asset, needed) = if side == "SELL" {
+        (base_asset, adjusted_quantity)
+    } else {
+        (quote_asset, price * adjusted_quantity)
+    };
+    let available_balance = get_available_balance(&asset, api_key, secret_key).await.unwrap_or(0.0);
 
-        if available_balance < adjusted_quantity {
-            println!(
-                "❌ Insufficient balance for selling {}: Available: {:.5}, Needed: {:.5}",
-                base_asset, available_balance, adjusted_quantity
-            );
+    if available_balance < needed {
+        println!(
+            "❌ Insufficient {} balance for {} order: Available: {:.5}, Needed: {:.5}",
+            asset, side, available_balance, needed
+        );
+        // W trybie próbnym plan jest pokazywany w całości (np. sprzedaże zapasu, który dopiero kupimy)
+        if !dry_run() {
             return Err("Insufficient balance for requested action".to_string());
         }
     }
 
+    // `FULL` zwraca wykonania z rzeczywistą prowizją (commission / commissionAsset)
     let query_string = format!(
-        "symbol={}&side={}&type=LIMIT&timeInForce=GTC&quantity={:.6}&price={:.2}&timestamp={}",
+        "symbol={}&side={}&type=LIMIT&timeInForce=GTC&quantity={:.6}&price={:.2}&newOrderRespType=FULL&timestamp={}",
         symbol, side, adjusted_quantity, price, timestamp
     );
 
     let signature = generate_signature(&query_string, secret_key);
+    let endpoint = if dry_run() { "/api/v3/order/test" } else { "/api/v3/order" };
     let url = format!(
-        "https://api.binance.com/api/v3/order?{}&signature={}",
-        query_string, signature
+        "{}{}?{}&signature={}",
+        base_url(), endpoint, query_string, signature
     );
 
     let mut headers = HeaderMap::new();
@@ -83,29 +247,275 @@ async fn place_binance_order(
     headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
 
     println!(
-        "🛑 Attempting to place {} order on Binance:\n  Symbol: {}\n  Price: {:.8}\n  Quantity: {:.8}\n  Total Cost: {:.8}",
+        "{} {} order on Binance:\n  Symbol: {}\n  Price: {:.8}\n  Quantity: {:.8}\n  Total Cost: {:.8}",
+        if dry_run() { "🧪 [DRY-RUN] Validating" } else { "🛑 Attempting to place" },
         side, symbol, price, adjusted_quantity, price * adjusted_quantity
     );
 
     let response = client.post(&url).headers(headers).send().await;
 
     match response {
+        Ok(resp) if resp.status().is_success() && dry_run() => {
+            println!("🧪 [DRY-RUN] Order accepted by /api/v3/order/test (not sent): {}", symbol);
+            Ok(PlacedOrder {
+                order_id: 0,
+                symbol: symbol.to_string(),
+                side: side.to_string(),
+                order_type: "LIMIT".to_string(),
+                price,
+                quantity: adjusted_quantity,
+                status: "DRY_RUN".to_string(),
+                fills: Vec::new(),
+            })
+        }
         Ok(resp) if resp.status().is_success() => {
             let json_resp: Value = resp.json().await.unwrap();
-            let order_id = json_resp["orderId"].as_u64().unwrap_or(0);
-            println!("✅ Order placed on Binance: {} | Order ID: {}", symbol, order_id);
-            Ok(order_id)
+            let order = PlacedOrder::from_response(&json_resp, symbol, side, price, adjusted_quantity);
+            println!("✅ Order placed on Binance: {} | Order ID: {}", symbol, order.order_id);
+            if order.status != "FILLED" {
+                risk::track_open_order(symbol, 1);
+            }
+            Ok(order)
         }
         Ok(resp) => {
-            let error_msg = resp.text().await.unwrap();
+            let error_msg = credentials::redact(&resp.text().await.unwrap_or_default());
             println!("❌ Binance order failed: {}", error_msg);
             Err(error_msg)
         }
         Err(e) => {
-            println!("❌ Request error: {}", e);
-            Err(e.to_string())
+            let error_msg = credentials::redact(&e.to_string());
+            println!("❌ Request error: {}", error_msg);
+            Err(error_msg)
+        }
+    }
+}
+
+/// Sprzedaż zleceniem MARKET (likwidacja zapasu) – pomija limity ryzyka, bo zmniejsza ekspozycję
+async fn place_market_sell(db: &mut Connection, client: &Client, symbol: &str, quantity: f64) -> Result<PlacedOrder, String> {
+    if dry_run() {
+        println!("🧪 [DRY-RUN] Would place MARKET SELL for {} | Quantity: {:.8} (nothing was sent)", symbol, quantity);
+        return Ok(PlacedOrder {
+            order_id: 0,
+            symbol: symbol.to_string(),
+            side: "SELL".to_string(),
+            order_type: "MARKET".to_string(),
+            price: 0.0,
+            quantity,
+            status: "DRY_RUN".to_string(),
+            fills: Vec::new(),
+        });
+    }
+    if !doctor::live_trading_allowed() {
+        return Err("Live trading disabled by API key self-check".to_string());
+    }
+
+    // Zapas wg bazy może przekraczać wolne saldo (prowizje w base, zablokowane zlecenia) – sprzedajemy najwyżej tyle
+    let api = credentials::credentials();
+    let (base_asset, _) = split_symbol(symbol);
+    let free = get_available_balance(&base_asset, &api.api_key, &api.secret_key).await?;
+    let (min_qty, step_size) = get_lot_size(symbol).await.unwrap_or((0.01, 0.01));
+    let adjusted_quantity = adjust_quantity(quantity.min(free), step_size);
+    if adjusted_quantity < min_qty {
+        return Err(format!(
+            "Quantity {:.8} (free {} {:.8}) below minimum LOT_SIZE ({:.8})",
+            adjusted_quantity, base_asset, free, min_qty
+        ));
+    }
+
+    println!("🛑 Placing MARKET SELL on Binance: {} | Quantity: {:.8}", symbol, adjusted_quantity);
+    let params = format!(
+        "symbol={}&side=SELL&type=MARKET&quantity={:.6}&newOrderRespType=FULL",
+        symbol, adjusted_quantity
+    );
+    let response = send_signed_request(client, reqwest::Method::POST, "/api/v3/order", &params, &api.api_key, &api.secret_key).await?;
+
+    let mut order = PlacedOrder::from_response(&response, symbol, "SELL", 0.0, adjusted_quantity);
+    let filled: f64 = order.fills.iter().map(|f| f.qty).sum();
+    if filled > 0.0 {
+        order.price = order.fills.iter().map(|f| f.price * f.qty).sum::<f64>() / filled;
+    }
+    record_placed_order(db, &order);
+    Ok(order)
+}
+
+/// Anuluje otwarte zlecenia pary (opcjonalnie tylko jednej strony) i zwalnia ich rezerwacje.
+/// Zwraca liczbę anulowanych zleceń; w trybie próbnym tylko odczytuje listę (anulowanie pomija `cancel_order`).
+async fn cancel_open_orders(db: &mut Connection, client: &Client, symbol: &str, side: Option<&str>) -> Result<usize, String> {
+    let api = credentials::credentials();
+    let params = format!("symbol={}", symbol);
+    let open = send_signed_request(client, reqwest::Method::GET, "/api/v3/openOrders", &params, &api.api_key, &api.secret_key).await?;
+
+    let mut canceled = 0;
+    for order in open.as_array().cloned().unwrap_or_default() {
+        if side.is_some_and(|side| order["side"] != side) {
+            continue;
+        }
+        let order_id = order["orderId"].as_u64().unwrap_or(0);
+        match cancel_order(db, client, symbol, order_id).await {
+            Ok(()) => canceled += 1,
+            Err(e) => println!("❌ Failed to cancel order {} for {}: {}", order_id, symbol, e),
         }
     }
+    Ok(canceled)
+}
+
+/// Anuluje pojedyncze zlecenie i zwalnia jego rezerwację (w trybie próbnym tylko wypisuje)
+async fn cancel_order(db: &mut Connection, client: &Client, symbol: &str, order_id: u64) -> Result<(), String> {
+    if dry_run() {
+        println!("🧪 [DRY-RUN] Would cancel order {} for {}", order_id, symbol);
+        return Ok(());
+    }
+    let api = credentials::credentials();
+    let params = format!("symbol={}&orderId={}", symbol, order_id);
+    send_signed_request(client, reqwest::Method::DELETE, "/api/v3/order", &params, &api.api_key, &api.secret_key).await?;
+    risk::track_open_order(symbol, -1);
+    db.execute("UPDATE orders SET status = 'CANCELED' WHERE order_id = ?1", params![order_id])
+        .expect("Failed to update order status");
+    ledger::release_order(db, symbol, order_id);
+    println!("🗑️ Canceled order {} for {}", order_id, symbol);
+    Ok(())
+}
+
+/// Wysyła podpisane zapytanie do API Binance i zwraca odpowiedź JSON
+async fn send_signed_request(
+    client: &Client,
+    method: reqwest::Method,
+    path: &str,
+    params: &str,
+    api_key: &str,
+    secret_key: &str
+) -> Result<Value, String> {
+    let timestamp = get_binance_server_time().await.unwrap_or(0);
+    let query_string = if params.is_empty() {
+        format!("timestamp={}", timestamp)
+    } else {
+        format!("{}&timestamp={}", params, timestamp)
+    };
+    let signature = generate_signature(&query_string, secret_key);
+    let url = format!("{}{}?{}&signature={}", base_url(), path, query_string, signature);
+
+    let mut headers = HeaderMap::new();
+    headers.insert("X-MBX-APIKEY", HeaderValue::from_str(api_key).map_err(|e| e.to_string())?);
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+    let resp = client.request(method, &url).headers(headers).send().await
+        .map_err(|e| credentials::redact(&e.to_string()))?;
+    if resp.status().is_success() {
+        resp.json::<Value>().await.map_err(|e| credentials::redact(&e.to_string()))
+    } else {
+        Err(credentials::redact(&resp.text().await.unwrap_or_else(|_| "Unknown error".to_string())))
+    }
+}
+
+/// Wiersz `trades` zapisany przed kolumną `trade_id` pasujący do wykonania (zlecenie, strona, cena, ilość);
+/// przy kilku kandydatach wybiera najbliższy czasowo
+fn legacy_fill_row(db: &Connection, symbol: &str, trade_type: &str, order_id: u64, fill: &Fill) -> Option<i64> {
+    db.query_row(
+        "SELECT id FROM trades
+         WHERE symbol = ?1 AND trade_id IS NULL AND order_id = ?2 AND type = ?3
+           AND ABS(price - ?4) < 1e-9 AND ABS(quantity - ?5) < 1e-12
+         ORDER BY ABS(strftime('%s', timestamp) - ?6 / 1000) ASC, id ASC LIMIT 1",
+        params![symbol, order_id, trade_type, fill.price, fill.qty, fill.time],
+        |row| row.get(0),
+    ).ok()
+}
+
+/// Czy wykonanie z `myTrades` jest już w `trades` (po `trade_id` lub jako wiersz sprzed tej kolumny)
+fn is_recorded_fill(db: &Connection, symbol: &str, trade: &Value) -> bool {
+    let fill = Fill::from_json(trade, now_millis());
+    let known: bool = db.query_row(
+        "SELECT EXISTS(SELECT 1 FROM trades WHERE symbol = ?1 AND trade_id = ?2)",
+        params![symbol, fill.trade_id],
+        |row| row.get(0),
+    ).unwrap_or(false);
+    let trade_type = if trade["isBuyer"].as_bool().unwrap_or(false) { "Buy" } else { "Sell" };
+    known || legacy_fill_row(db, symbol, trade_type, trade["orderId"].as_u64().unwrap_or(0), &fill).is_some()
+}
+
+/// Zapisuje wykonanie do `trades` z zyskiem liczonym z bieżącej pozycji pary.
+/// Ponownie pobrane wykonanie (ten sam `symbol`, `trade_id`) aktualizuje tylko prowizję.
+fn record_fill(db: &Connection, symbol: &str, side: &str, order_id: u64, fill: &Fill) {
+    let trade_type = if side == "BUY" { "Buy" } else { "Sell" };
+    let known = db.execute(
+        "UPDATE trades SET commission = ?3, commission_asset = ?4 WHERE symbol = ?1 AND trade_id = ?2",
+        params![symbol, fill.trade_id, fill.commission, fill.commission_asset],
+    ).expect("Failed to record fill");
+    if known > 0 {
+        return;
+    }
+    // Wiersz sprzed kolumny `trade_id` to to samo wykonanie – uzupełniamy go zamiast dublować
+    if let Some(id) = legacy_fill_row(db, symbol, trade_type, order_id, fill) {
+        db.execute(
+            "UPDATE trades SET trade_id = ?2, commission = ?3, commission_asset = ?4, timestamp = datetime(?5 / 1000, 'unixepoch')
+             WHERE id = ?1",
+            params![id, fill.trade_id, fill.commission, fill.commission_asset, fill.time],
+        ).expect("Failed to record fill");
+        portfolio::refresh_realized_profit(db, symbol);
+        return;
+    }
+
+    // Wykonanie starsze niż już zapisane zmienia kolejność – pozycja jest wtedy odtwarzana od nowa
+    let out_of_order: bool = db.query_row(
+        "SELECT EXISTS(SELECT 1 FROM trades WHERE symbol = ?1 AND timestamp > datetime(?2 / 1000, 'unixepoch'))",
+        params![symbol, fill.time],
+        |row| row.get(0),
+    ).unwrap_or(false);
+    let mut position = portfolio::running_position(db, symbol);
+    let profit = position.apply_fill(trade_type, fill.price, fill.qty, fill.commission, &fill.commission_asset);
+    db.execute(
+        "INSERT INTO trades (symbol, price, quantity, timestamp, type, profit, order_id, trade_id, commission, commission_asset)
+         VALUES (?1, ?2, ?3, datetime(?4 / 1000, 'unixepoch'), ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            symbol, fill.price, fill.qty, fill.time, trade_type, (trade_type == "Sell").then_some(profit),
+            order_id, fill.trade_id, fill.commission, fill.commission_asset
+        ],
+    ).expect("Failed to record fill");
+    portfolio::store_position(db, symbol, &position);
+    if out_of_order {
+        portfolio::refresh_realized_profit(db, symbol);
+    }
+
+    // 📒 Księgowanie wykonania w księdze kapitału pary
+    let (_, quote_asset) = split_symbol(symbol);
+    let quote_fee = if fill.commission_asset == quote_asset { fill.commission } else { 0.0 };
+    if trade_type == "Buy" {
+        ledger::on_buy_fill(db, symbol, order_id, fill.trade_id, fill.price * fill.qty, quote_fee);
+    } else {
+        let profit: f64 = db.query_row(
+            "SELECT COALESCE(profit, 0.0) FROM trades WHERE symbol = ?1 AND trade_id = ?2",
+            params![symbol, fill.trade_id],
+            |row| row.get(0),
+        ).unwrap_or(0.0);
+        ledger::on_sell_fill(db, symbol, fill.trade_id, fill.price * fill.qty - quote_fee, profit);
+        if ledger::profit_mode(db, symbol) == config::ProfitMode::Sweep {
+            ledger::sweep_profit(db, symbol, &format!("trade:{}", fill.trade_id), profit);
+        }
+    }
+}
+
+/// Zapisuje złożone zlecenie w `orders`, a jego natychmiastowe wykonania w `trades`
+fn record_placed_order(db: &Connection, order: &PlacedOrder) {
+    if order.status == "DRY_RUN" {
+        return;
+    }
+
+    db.execute(
+        "INSERT OR REPLACE INTO orders (order_id, symbol, price, stop_price, quantity, type, status, timestamp, side, source)
+         VALUES (?1, ?2, ?3, 0.0, ?4, ?5, ?6, datetime('now'), ?7, 'bot')",
+        params![order.order_id, order.symbol, order.price, order.quantity, order.order_type, order.status, order.side],
+    ).expect("Failed to record order");
+
+    if order.side == "BUY" {
+        ledger::reserve_for_buy(db, &order.symbol, order.order_id, order.price * order.quantity);
+    }
+
+    for fill in &order.fills {
+        record_fill(db, &order.symbol, &order.side, order.order_id, fill);
+    }
+
+    if order.status == "FILLED" {
+        ledger::release_order(db, &order.symbol, order.order_id);
+    }
 }
 
 async fn get_available_balance(asset: &str, api_key: &str, secret_key: &str) -> Result<f64, String> {
@@ -115,8 +525,8 @@ async fn get_available_balance(asset: &str, api_key: &str, secret_key: &str) ->
     let signature = generate_signature(&query_string, secret_key);
 
     let url = format!(
-        "https://api.binance.com/api/v3/account?{}&signature={}",
-        query_string, signature
+        "{}/api/v3/account?{}&signature={}",
+        base_url(), query_string, signature
     );
 
     let mut headers = HeaderMap::new();
@@ -138,14 +548,11 @@ async fn get_available_balance(asset: &str, api_key: &str, secret_key: &str) ->
             }
             Err("Asset not found in balance.".to_string())
         }
-        Ok(resp) => Err(resp.text().await.unwrap_or_else(|_| "Unknown error".to_string())),
-        Err(e) => Err(e.to_string()),
+        Ok(resp) => Err(credentials::redact(&resp.text().await.unwrap_or_else(|_| "Unknown error".to_string()))),
+        Err(e) => Err(credentials::redact(&e.to_string())),
     }
 }
 
-
-
-
 /// Funkcja do generowania sygnatury HMAC-SHA256 dla API Binance
 fn generate_signature(query: &str, secret_key: &str) -> String {
     let mut mac = Hmac::<Sha256>::new_from_slice(secret_key.as_bytes()).expect("HMAC can take key of any size");
@@ -153,42 +560,63 @@ fn generate_signature(query: &str, secret_key: &str) -> String {
     hex::encode(mac.finalize().into_bytes())
 }
 
-
-
-fn load_config(filename: &str) -> HashMap<String, String> {
-    let mut config = HashMap::new();
-    if let Ok(contents) = fs::read_to_string(filename) {
-        for line in contents.lines() {
-            if let Some((key, value)) = line.split_once('=') {
-                config.insert(key.trim().to_string(), value.trim().to_string());
-            }
-        }
-    }
-    config
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 struct BinanceTicker {
     symbol: String,
     price: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct OrderResponse {
-    orderId: u64,
-}
-
 async fn get_price(symbol: &str, client: &Client) -> Result<f64, reqwest::Error> {
-    let url = format!("https://api.binance.com/api/v3/ticker/price?symbol={}", symbol);
+    let url = format!("{}/api/v3/ticker/price?symbol={}", base_url(), symbol);
     let response: BinanceTicker = client.get(&url).send().await?.json().await?;
     Ok(response.price.parse().unwrap_or(0.0))
 }
 
+/// Świeca z `/api/v3/klines`
+#[derive(Debug, Clone, Copy)]
+struct Candle {
+    /// Czas otwarcia świecy (ms)
+    open_time: i64,
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+}
+
+impl Candle {
+    fn from_json(value: &Value) -> Candle {
+        let parse = |i: usize| value[i].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0);
+        Candle { open_time: value[0].as_i64().unwrap_or(0), open: parse(1), high: parse(2), low: parse(3), close: parse(4) }
+    }
+}
+
+/// Długość interwału świec w sekundach, np. "15m" -> 900
+fn interval_secs(interval: &str) -> Option<u64> {
+    let (count, unit) = interval.split_at(interval.len().checked_sub(1)?);
+    let unit_secs = match unit {
+        "m" => 60,
+        "h" => 3_600,
+        "d" => 86_400,
+        "w" => 604_800,
+        _ => return None,
+    };
+    Some(count.parse::<u64>().ok()? * unit_secs)
+}
+
+/// Ostatnie świece pary (od najstarszej), np. `interval` = "1m", "1h"
+async fn get_klines(client: &Client, symbol: &str, interval: &str, limit: usize) -> Result<Vec<Candle>, String> {
+    let url = format!("{}/api/v3/klines?symbol={}&interval={}&limit={}", base_url(), symbol, interval, limit);
+    let response = client.get(&url).send().await.map_err(|e| e.to_string())?
+        .json::<Value>().await.map_err(|e| e.to_string())?;
+    let candles = response.as_array().ok_or_else(|| format!("Unexpected klines response: {}", response))?;
+    Ok(candles.iter().map(Candle::from_json).collect())
+}
+
 async fn get_min_notional(symbol: &str) -> Result<f64, reqwest::Error> {
-    let url = "https://api.binance.com/api/v3/exchangeInfo";
+    let url = format!("{}/api/v3/exchangeInfo", base_url());
     let client = reqwest::Client::new();
 
-    let response = client.get(url).send().await?.json::<serde_json::Value>().await?;
+    let response = client.get(&url).send().await?.json::<serde_json::Value>().await?;
 
     if let Some(symbols) = response["symbols"].as_array() {
         for s in symbols {
@@ -207,21 +635,18 @@ async fn get_min_notional(symbol: &str) -> Result<f64, reqwest::Error> {
 }
 
 fn setup_db() -> Connection {
-    let conn = Connection::open("trades.db").expect("Failed to open DB");
+    // Testnet / mock server nie mieszają danych z kontem produkcyjnym
+    let db_path = if base_url() == MAINNET_BASE_URL { "trades.db" } else { "trades_test.db" };
+    let conn = Connection::open(db_path).expect("Failed to open DB");
+    create_schema(&conn);
+    conn
+}
 
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS trades (
-            id INTEGER PRIMARY KEY,
-            symbol TEXT NOT NULL,
-            price REAL NOT NULL,
-            quantity REAL NOT NULL,
-            timestamp TEXT NOT NULL,
-            type TEXT NOT NULL,
-            profit REAL,
-            order_id INTEGER UNIQUE
-        )",
-        [],
-    ).expect("Failed to create table");
+/// Tworzy i migruje wszystkie tabele bota
+fn create_schema(conn: &Connection) {
+    conn.execute(&format!("CREATE TABLE IF NOT EXISTS trades ({})", TRADES_COLUMNS), [])
+        .expect("Failed to create table");
+    migrate_trades_table(conn);
 
     conn.execute(
         "CREATE TABLE IF NOT EXISTS capital (
@@ -232,7 +657,6 @@ fn setup_db() -> Connection {
             max_price REAL NOT NULL,
             is_active INTEGER DEFAULT 05
 
-
         )",
         [],
     ).expect("Failed to create capital table");
@@ -249,17 +673,114 @@ fn setup_db() -> Connection {
             quantity REAL NOT NULL,
             type TEXT NOT NULL,
             status TEXT NOT NULL,
-            timestamp TEXT NOT NULL
+            timestamp TEXT NOT NULL,
+            side TEXT,
+            source TEXT DEFAULT 'exchange',
+            acknowledged INTEGER NOT NULL DEFAULT 0
         )",
         [],
     ).expect("Failed to create orders table");
+    // Przesunięcia zakresu przez trailing grid
+    add_column_if_missing(conn, "capital", "trail_shifts", "INTEGER NOT NULL DEFAULT 0");
+    add_column_if_missing(conn, "capital", "last_trail_at", "TEXT");
+    // Reverse grid: przydział i zysk w aktywie bazowym
+    add_column_if_missing(conn, "capital", "base_amount", "REAL NOT NULL DEFAULT 0");
+    add_column_if_missing(conn, "capital", "base_profit", "REAL NOT NULL DEFAULT 0");
+    // Wartość przydziału base w quote z chwili przydziału – koszt zapasu reverse gridu
+    add_column_if_missing(conn, "capital", "base_cost", "REAL NOT NULL DEFAULT 0");
+    add_column_if_missing(conn, "orders", "side", "TEXT");
+    add_column_if_missing(conn, "orders", "covers_qty", "REAL");
+    // Zlecenia sprzed tej kolumny złożył bot; nowe wiersze bez źródła pochodzą z giełdy
+    if add_column_if_missing(conn, "orders", "source", "TEXT DEFAULT 'exchange'") {
+        conn.execute("UPDATE orders SET source = 'bot'", []).expect("Failed to migrate orders table");
+    }
+    add_column_if_missing(conn, "orders", "acknowledged", "INTEGER NOT NULL DEFAULT 0");
+    add_column_if_missing(conn, "orders", "level", "INTEGER");
+    add_column_if_missing(conn, "capital", "strategy", "TEXT");
+    // Liczba poziomów poniżej ceny zaproponowana przez `suggest-range` (NULL = z config.toml)
+    add_column_if_missing(conn, "capital", "levels_below", "INTEGER");
+    // Tryb zysku zapisany przy parze – zmiana config.toml nie zmienia go w trakcie działania gridu
+    if add_column_if_missing(conn, "capital", "profit_mode", "TEXT") {
+        let symbols: Vec<String> = {
+            let mut stmt = conn.prepare("SELECT symbol FROM capital").expect("Failed to prepare statement");
+            stmt.query_map([], |row| row.get(0))
+                .expect("Failed to query capital pairs")
+                .filter_map(Result::ok)
+                .collect()
+        };
+        for symbol in symbols {
+            conn.execute(
+                "UPDATE capital SET profit_mode = ?2 WHERE symbol = ?1",
+                params![symbol, config::config().pair(&symbol).profit_mode.as_str()],
+            ).expect("Failed to migrate capital table");
+        }
+    }
+    portfolio::setup(conn);
+    ledger::setup(conn);
+    events::setup(conn);
+    breaker::setup(conn);
+    volatility::setup(conn);
+    klines::setup(conn);
+}
 
-    conn
+/// Dodaje kolumnę do istniejącej tabeli (migracja starszych baz); `true`, jeśli kolumna została dodana
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> bool {
+    let exists: bool = conn.query_row(
+        &format!("SELECT EXISTS(SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1)", table),
+        params![column],
+        |row| row.get(0),
+    ).unwrap_or(false);
+
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])
+            .expect("Failed to migrate table");
+    }
+    !exists
+}
+
+/// Kolumny tabeli `trades` – jeden wiersz na wykonanie; `tradeId` Binance jest unikalny tylko w obrębie pary
+const TRADES_COLUMNS: &str = "
+    id INTEGER PRIMARY KEY,
+    symbol TEXT NOT NULL,
+    price REAL NOT NULL,
+    quantity REAL NOT NULL,
+    timestamp TEXT NOT NULL,
+    type TEXT NOT NULL,
+    profit REAL,
+    order_id INTEGER,
+    trade_id INTEGER,
+    commission REAL NOT NULL DEFAULT 0,
+    commission_asset TEXT,
+    UNIQUE(symbol, trade_id)
+";
+
+/// Przebudowuje starą tabelę `trades` (jeden wiersz na zlecenie, `order_id UNIQUE`)
+/// do postaci jeden wiersz na wykonanie z prowizją
+fn migrate_trades_table(conn: &Connection) {
+    let sql: String = conn.query_row(
+        "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'trades'",
+        [],
+        |row| row.get(0),
+    ).unwrap_or_default();
+
+    if !sql.contains("order_id INTEGER UNIQUE") {
+        return;
+    }
+
+    println!("🔧 Migrating `trades` table to per-fill records with commissions...");
+    conn.execute_batch(&format!(
+        "BEGIN;
+         ALTER TABLE trades RENAME TO trades_old;
+         CREATE TABLE trades ({});
+         INSERT INTO trades (id, symbol, price, quantity, timestamp, type, profit, order_id)
+            SELECT id, symbol, price, quantity, timestamp, type, profit, order_id FROM trades_old;
+         DROP TABLE trades_old;
+         COMMIT;",
+        TRADES_COLUMNS,
+    )).expect("Failed to migrate trades table");
 }
 
 /// Zapisuje zlecenia do bazy danych
-///
-///
 fn save_orders_to_db(db: &mut Connection, orders: &serde_json::Value) {
     let tx = db.transaction().expect("Failed to start transaction");
 
@@ -274,6 +795,7 @@ fn save_orders_to_db(db: &mut Connection, orders: &serde_json::Value) {
             let stop_price = order["stopPrice"].as_str().unwrap_or("0.0").parse::<f64>().unwrap_or(0.0);
             let quantity = order["origQty"].as_str().unwrap_or("0.0").parse::<f64>().unwrap_or(0.0);
             let order_type = order["type"].as_str().unwrap_or("UNKNOWN");
+            let side = order["side"].as_str().unwrap_or("UNKNOWN");
             let status = order["status"].as_str().unwrap_or("UNKNOWN");
             let timestamp = order["time"].as_u64().unwrap_or_else(|| {
                 eprintln!("❌ Błąd: Brak timestamp w zamówieniu: {:?}", order);
@@ -283,25 +805,24 @@ fn save_orders_to_db(db: &mut Connection, orders: &serde_json::Value) {
             active_order_ids.push(order_id);
 
             tx.execute(
-                "INSERT OR IGNORE INTO orders (order_id, symbol, price, stop_price, quantity, type, status, timestamp)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, datetime(?8 / 1000, 'unixepoch'))",
-                params![order_id, symbol, price, stop_price, quantity, order_type, status, timestamp],
+                "INSERT OR IGNORE INTO orders (order_id, symbol, price, stop_price, quantity, type, status, timestamp, side, source)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, datetime(?8 / 1000, 'unixepoch'), ?9, 'exchange')",
+                params![order_id, symbol, price, stop_price, quantity, order_type, status, timestamp, side],
             ).expect("Failed to insert order");
         }
     }
 
-    // Usuwanie zamówień, które już nie istnieją na Binance
+    // Usuwanie zamówień spoza bota, które już nie istnieją na Binance. Zlecenia bota zostają –
+    // ich wykonania, rezerwacje i metadane poziomów rozlicza synchronizacja transakcji
     let active_order_ids_str = active_order_ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(",");
     tx.execute(
-        &format!("DELETE FROM orders WHERE order_id NOT IN ({})", active_order_ids_str),
+        &format!("DELETE FROM orders WHERE source != 'bot' AND order_id NOT IN ({})", active_order_ids_str),
         [],
     ).expect("Failed to delete old orders");
 
     tx.commit().expect("Failed to commit transaction");
 }
 
-
-
 fn get_user_input(prompt: &str) -> String {
     let mut input = String::new();
     println!("{}", prompt);
@@ -309,45 +830,152 @@ fn get_user_input(prompt: &str) -> String {
     input.trim().to_string()
 }
 
+/// Pyta o nieujemną liczbę, dopóki użytkownik nie poda poprawnej
+fn prompt_f64(prompt: &str) -> f64 {
+    loop {
+        match get_user_input(prompt).parse::<f64>() {
+            Ok(value) if value.is_finite() && value >= 0.0 => return value,
+            _ => println!("⚠️ Please enter a non-negative number."),
+        }
+    }
+}
 
-fn set_capital_for_pair(db: &mut Connection) {
+async fn set_capital_for_pair(db: &mut Connection) {
     let symbol = get_user_input("Enter trading pair symbol (e.g., BTCUSDT):");
-    let amount: f64 = get_user_input("Enter capital allocation for this pair:")
-        .parse()
-        .expect("Invalid capital amount");
-    let min_price: f64 = get_user_input("Enter minimum price range:")
-        .parse()
-        .expect("Invalid min price");
-    let max_price: f64 = get_user_input("Enter maximum price range:")
-        .parse()
-        .expect("Invalid max price");
+    let amount = prompt_f64("Enter capital allocation for this pair:");
+    let min_price = prompt_f64("Enter minimum price range:");
+    let max_price = prompt_f64("Enter maximum price range:");
+
+    // 🧩 Strategia pary zapisana w bazie (pusta odpowiedź = domyślna z konfiguracji)
+    let pair = strategy::pair_for(db, &symbol);
+    let names: Vec<&str> = config::StrategyKind::ALL.iter().map(|kind| kind.as_str()).collect();
+    let answer = get_user_input(&format!("Strategy ({}, empty = {}):", names.join("/"), pair.strategy.as_str()));
+    let kind = if answer.is_empty() {
+        pair.strategy
+    } else {
+        match config::StrategyKind::parse(&answer) {
+            Some(kind) => kind,
+            None => {
+                println!("⚠️ Unknown strategy '{}', using {}", answer, pair.strategy.as_str());
+                pair.strategy
+            }
+        }
+    };
 
-    db.execute(
-        "INSERT OR REPLACE INTO capital (symbol, amount, min_price, max_price)
-         VALUES (?1, ?2, ?3, ?4)",
+    // 💰 Tryb zysku pary (pusta odpowiedź = obecny lub domyślny z konfiguracji)
+    let current_mode = ledger::profit_mode(db, &symbol);
+    let modes: Vec<&str> = config::ProfitMode::ALL.iter().map(|mode| mode.as_str()).collect();
+    let answer = get_user_input(&format!("Profit mode ({}, empty = {}):", modes.join("/"), current_mode.as_str()));
+    let profit_mode = match config::ProfitMode::parse(&answer) {
+        Some(mode) => mode,
+        None => {
+            if !answer.is_empty() {
+                println!("⚠️ Unknown profit mode '{}', using {}", answer, current_mode.as_str());
+            }
+            current_mode
+        }
+    };
+
+    // 🧮 Opłacalność poziomów po prowizjach (cena odniesienia: środek zakresu)
+    if kind != config::StrategyKind::Dca && min_price > 0.0 && max_price >= min_price {
+        let fee_config = &config::config().fees;
+        let report = fees::analyze(&pair, amount, (min_price + max_price) / 2.0, fee_config);
+        fees::print_report(db, &pair, &report, fee_config);
+        if report.unprofitable && get_user_input("❌ Grid loses money on every cycle. Type 'force' to save it anyway:") != "force" {
+            println!("❌ Capital allocation for {} not changed. Increase `step` in config.toml.", symbol);
+            return;
+        }
+    }
+
+    let updated = db.execute(
+        "UPDATE capital SET amount = ?2, min_price = ?3, max_price = ?4 WHERE symbol = ?1",
         params![symbol, amount, min_price, max_price],
     ).expect("Failed to set capital allocation for pair");
 
+    if updated == 0 {
+        db.execute(
+            "INSERT INTO capital (symbol, amount, min_price, max_price)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![symbol, amount, min_price, max_price],
+        ).expect("Failed to set capital allocation for pair");
+    }
+    ledger::allocate(db, &symbol, amount);
+
     println!("✅ Capital allocation for {} set to: ${:.2}, price range: {:.2} - {:.2}",
              symbol, amount, min_price, max_price);
-}
 
+    db.execute(
+        "UPDATE capital SET strategy = ?2, profit_mode = ?3 WHERE symbol = ?1",
+        params![symbol, kind.as_str(), profit_mode.as_str()],
+    ).expect("Failed to set strategy for pair");
+    println!("✅ Strategy for {} set to: {}, profit mode: {}", symbol, kind.as_str(), profit_mode.as_str());
+
+    // Reverse grid startuje z posiadanego aktywa bazowego
+    if kind == config::StrategyKind::Reverse {
+        let (base_asset, _) = split_symbol(&symbol);
+        let base_amount = prompt_f64(&format!("Enter {} allocation for the reverse grid:", base_asset));
+        let price = match get_price(&symbol, &Client::new()).await {
+            Ok(price) if price > 0.0 => price,
+            _ => {
+                println!("❌ Failed to fetch price for {}, reverse grid allocation not changed", symbol);
+                return;
+            }
+        };
+        allocate_base(db, &symbol, base_amount, price);
+        println!(
+            "✅ Reverse grid allocation for {} set to: {:.8} {} (cost {:.2} at {:.8})",
+            symbol, base_amount, base_asset, base_amount * price, price
+        );
+    }
+}
 
+/// Zapisuje przydział base reverse gridu i wprowadza go do pozycji i księgi po wartości rynkowej
+fn allocate_base(db: &Connection, symbol: &str, base_amount: f64, price: f64) {
+    db.execute(
+        "UPDATE capital SET base_amount = ?2, base_cost = ?3 WHERE symbol = ?1",
+        params![symbol, base_amount, base_amount * price],
+    ).expect("Failed to set base allocation for pair");
+    ledger::allocate_base(db, symbol, base_amount * price);
+    portfolio::refresh_realized_profit(db, symbol);
+}
 
 fn show_capital_for_pairs(db: &Connection) {
-    let mut stmt = db.prepare("SELECT symbol, amount FROM capital ORDER BY symbol ASC").expect("Failed to prepare statement");
+    let mut stmt = db.prepare(
+        "SELECT symbol, amount, min_price, max_price, trail_shifts, base_amount, base_profit FROM capital ORDER BY symbol ASC"
+    ).expect("Failed to prepare statement");
     let capital_entries = stmt.query_map([], |row| {
-        Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?))
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, f64>(1)?,
+            row.get::<_, f64>(2)?,
+            row.get::<_, f64>(3)?,
+            row.get::<_, i64>(4)?,
+            row.get::<_, f64>(5)?,
+            row.get::<_, f64>(6)?,
+        ))
     }).expect("Failed to query capital entries");
 
     println!("\nCapital Allocations:");
     for entry in capital_entries {
-        let (symbol, amount) = entry.expect("Failed to fetch capital entry");
-        println!("Pair: {}, Capital: ${:.2}", symbol, amount);
+        let (symbol, amount, min_price, max_price, trail_shifts, base_amount, base_profit) = entry.expect("Failed to fetch capital entry");
+        let kind = strategy::kind_for(db, &symbol);
+        println!(
+            "Pair: {}, Strategy: {}, Capital: ${:.2}, Range: {:.2} - {:.2}, Trailing shifts: {}",
+            symbol, kind.as_str(), amount, min_price, max_price, trail_shifts
+        );
+        if kind == config::StrategyKind::Reverse {
+            let (base_asset, _) = split_symbol(&symbol);
+            println!("   Reverse grid: {:.8} {} allocated, profit {:.8} {}", base_amount, base_asset, base_profit, base_asset);
+        }
+        let balances = ledger::balances(db, &symbol);
+        println!(
+            "   Profit mode: {}, Working capital: ${:.2}, Harvested profit: ${:.2}",
+            ledger::profit_mode(db, &symbol).as_str(), balances.working_capital(), balances.harvested
+        );
     }
 }
 
-fn show_open_positions(db: &Connection) {
+fn show_trade_history(db: &Connection) {
     let mut stmt = db.prepare("SELECT id, symbol, price, quantity, timestamp, type FROM trades ORDER BY timestamp DESC").expect("Failed to prepare statement");
     let positions = stmt.query_map([], |row| {
         Ok((
@@ -360,7 +988,7 @@ fn show_open_positions(db: &Connection) {
         ))
     }).expect("Failed to query open positions");
 
-    println!("\nOpen Positions:");
+    println!("\nTrade History:");
     for position in positions {
         let (id, symbol, price, quantity, timestamp, trade_type) = position.expect("Failed to fetch position");
         let trade_direction = if trade_type == "Buy" { "🔵 Buy" } else { "🔴 Sell" };
@@ -368,17 +996,6 @@ fn show_open_positions(db: &Connection) {
     }
 }
 
-fn manage_active_orders(db: &Connection) {
-    let mut stmt = db.prepare("SELECT COUNT(*) FROM trades WHERE type IN ('Buy', 'Sell')").expect("Failed to prepare statement");
-    let active_orders: i32 = stmt.query_row([], |row| row.get(0)).unwrap_or(0);
-
-    if active_orders >= 5 {
-        println!("Max 5 active orders reached. No new orders will be placed until existing ones are closed.");
-    } else {
-        println!("{} active orders. New orders can be placed.", active_orders);
-    }
-}
-
 fn display_orders(db: &Connection) {
     let mut stmt = db.prepare("SELECT symbol, price, stop_price, quantity, type, status, timestamp FROM orders ORDER BY timestamp DESC")
         .expect("Failed to prepare statement");
@@ -407,20 +1024,18 @@ fn display_orders(db: &Connection) {
 
 /// Pobiera aktywne zamówienia z Binance
 async fn show_binance_orders(db: &mut Connection) {
-    dotenv().ok();
-    let config = load_config("config.txt");
-    let api_key = config.get("BINANCE_API_KEY").expect("Missing API key");
-    let secret_key = config.get("BINANCE_SECRET_KEY").expect("Missing secret key");
+    let api_key = &credentials::credentials().api_key;
+    let secret_key = &credentials::credentials().secret_key;
     let client = reqwest::Client::new();
 
     let timestamp = get_binance_server_time().await.unwrap_or(0);
     let query_string = format!("timestamp={}", timestamp);
-    let signature = generate_signature(&query_string, &secret_key);
+    let signature = generate_signature(&query_string, secret_key);
 
-    let url = format!("https://api.binance.com/api/v3/openOrders?{}&signature={}", query_string, signature);
+    let url = format!("{}/api/v3/openOrders?{}&signature={}", base_url(), query_string, signature);
 
     let mut headers = HeaderMap::new();
-    headers.insert("X-MBX-APIKEY", HeaderValue::from_str(&api_key).unwrap());
+    headers.insert("X-MBX-APIKEY", HeaderValue::from_str(api_key).unwrap());
     headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
 
     let response = client.get(&url).headers(headers).send().await;
@@ -432,10 +1047,10 @@ async fn show_binance_orders(db: &mut Connection) {
             display_orders(db);  // ✅ Poprawne użycie `db`
         }
         Ok(resp) => {
-            println!("Failed to fetch orders: {}", resp.text().await.unwrap());
+            println!("Failed to fetch orders: {}", credentials::redact(&resp.text().await.unwrap_or_default()));
         }
         Err(e) => {
-            println!("Request error: {}", e);
+            println!("Request error: {}", credentials::redact(&e.to_string()));
         }
     }
 }
@@ -443,48 +1058,46 @@ async fn show_binance_orders(db: &mut Connection) {
 ///  synchrnizacja czasu
 async fn get_binance_server_time() -> Result<u128, reqwest::Error> {
     let client = reqwest::Client::new();
-    let url = "https://api.binance.com/api/v3/time";
-    let response = client.get(url).send().await?.json::<serde_json::Value>().await?;
+    let url = format!("{}/api/v3/time", base_url());
+    let response = client.get(&url).send().await?.json::<serde_json::Value>().await?;
     Ok(response["serverTime"].as_u64().unwrap_or(0) as u128)
 }
 
-/// Pobiera ostatnie transakcje użytkownika (wykonane zlecenia)
-async fn show_live_execution() {
-    dotenv().ok();
-
-    let config = load_config("config.txt");
-    let api_key = config.get("BINANCE_API_KEY").expect("Missing API key");
-    let secret_key = config.get("BINANCE_SECRET_KEY").expect("Missing secret key");
+/// Synchronizuje historię transakcji z Binance i pokazuje ostatnie wykonania
+async fn show_live_execution(db: &mut Connection) {
+    let api_key = &credentials::credentials().api_key;
+    let secret_key = &credentials::credentials().secret_key;
 
     let client = reqwest::Client::new();
+    sync::sync_all_trades(db, &client, api_key, secret_key).await;
 
-    let symbol = "BTCUSDT"; // Można zmienić na inny symbol
-
-    //let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
-    let timestamp = get_binance_server_time().await.unwrap_or(0);
-
-    let query_string = format!("symbol={}&timestamp={}", symbol, timestamp);
-    let signature = generate_signature(&query_string, &secret_key);
-
-    let url = format!("https://api.binance.com/api/v3/myTrades?{}&signature={}", query_string, signature);
+    let mut stmt = db.prepare(
+        "SELECT symbol, type, price, quantity, commission, COALESCE(commission_asset, ''), COALESCE(profit, 0.0), timestamp, trade_id
+         FROM trades ORDER BY timestamp DESC, id DESC LIMIT 20"
+    ).expect("Failed to prepare statement");
 
-    let mut headers = HeaderMap::new();
-    headers.insert("X-MBX-APIKEY", HeaderValue::from_str(&api_key).unwrap());
-    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-
-    let response = client.get(&url).headers(headers).send().await;
+    let trades = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, f64>(2)?,
+            row.get::<_, f64>(3)?,
+            row.get::<_, f64>(4)?,
+            row.get::<_, String>(5)?,
+            row.get::<_, f64>(6)?,
+            row.get::<_, String>(7)?,
+            row.get::<_, Option<i64>>(8)?,
+        ))
+    }).expect("Failed to query trades");
 
-    match response {
-        Ok(resp) if resp.status().is_success() => {
-            let trades: serde_json::Value = resp.json().await.unwrap();
-            println!("Recent Trades: {}", serde_json::to_string_pretty(&trades).unwrap());
-        }
-        Ok(resp) => {
-            println!("Failed to fetch trades: {}", resp.text().await.unwrap());
-        }
-        Err(e) => {
-            println!("Request error: {}", e);
-        }
+    println!("\n📜 **Recent executions:**\n");
+    for trade in trades {
+        let (symbol, trade_type, price, quantity, commission, commission_asset, profit, timestamp, trade_id) =
+            trade.expect("Failed to fetch trade");
+        println!(
+            "🔹 {} | {} | Trade ID: {} | Price: {:.4} | Qty: {:.6} | Fee: {:.8} {} | Profit: {:+.4} | {}",
+            symbol, trade_type, trade_id.unwrap_or(0), price, quantity, commission, commission_asset, profit, timestamp
+        );
     }
 }
 
@@ -524,41 +1137,31 @@ fn show_remaining_capital(db: &Connection) {
     // Pobranie symbolu dla wybranego ID
     let symbol = symbols.iter().find(|(id, _)| *id == choice).unwrap().1.clone();
 
-    // Sprawdzenie, czy grid trading został już uruchomiony (czy są transakcje dla pary)
-    let has_trades: bool = db.query_row(
-        "SELECT EXISTS(SELECT 1 FROM trades WHERE symbol = ?1)",
-        params![symbol],
-        |row| row.get(0)
-    ).unwrap_or(false);
-
-    let remaining_capital: f64;
-
-    if has_trades {
-        // Pobranie pozostałego kapitału na kolejne zlecenia
-        remaining_capital = db.query_row(
-            "SELECT SUM(quantity) FROM trades WHERE symbol = ?1",
-            params![symbol],
-            |row| row.get(0)
-        ).unwrap_or(0.0);
+    // 📒 Stan księgi kapitału pary
+    let balances = ledger::balances(db, &symbol);
 
-        println!("\n🔹 **Pair:** {} | 💰 **Remaining capital for new orders:** ${:.2}", symbol, remaining_capital);
-    } else {
-        // Pobranie pełnego dostępnego kapitału (gdy grid nie został uruchomiony)
-        remaining_capital = db.query_row(
-            "SELECT amount FROM capital WHERE symbol = ?1",
-            params![symbol],
-            |row| row.get(0)
-        ).unwrap_or(0.0);
+    println!("\n🔹 **Pair:** {}", symbol);
+    println!("   🏦 Allocation:            ${:.2}", balances.allocation);
+    println!("   🔒 Reserved in open buys: ${:.2}", balances.reserved);
+    println!("   📦 Inventory at cost:     ${:.2}", balances.inventory);
+    println!("   📈 Realized profit:       ${:+.2}", balances.realized_profit);
+    println!("   💰 Free capital:          ${:.2}", balances.free);
+    println!("   🌾 Harvested profit:      ${:.2}", balances.harvested);
+    println!(
+        "   🔁 Profit mode:           {} (working capital ${:.2})",
+        ledger::profit_mode(db, &symbol).as_str(), balances.working_capital()
+    );
 
-        println!("\n🔹 **Pair:** {} | 💰 **Available capital (Grid not started yet):** ${:.2}", symbol, remaining_capital);
+    if balances.imbalance().abs() > 1e-6 {
+        println!("   ⚠️ Ledger imbalance: {:.8}", balances.imbalance());
     }
 }
 
 async fn get_lot_size(symbol: &str) -> Result<(f64, f64), String> {
     let client = Client::new();
-    let url = "https://api.binance.com/api/v3/exchangeInfo";
+    let url = format!("{}/api/v3/exchangeInfo", base_url());
 
-    let response = client.get(url).send().await.map_err(|e| e.to_string())?;
+    let response = client.get(&url).send().await.map_err(|e| e.to_string())?;
     let json: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
 
     if let Some(symbols) = json["symbols"].as_array() {
@@ -580,167 +1183,38 @@ async fn get_lot_size(symbol: &str) -> Result<(f64, f64), String> {
     Err("Could not retrieve LOT_SIZE".to_string())
 }
 
-
-fn has_open_positions(db: &Connection, symbol: &str) -> bool {
-    let count: i32 = db.query_row(
-        "SELECT COUNT(*) FROM trades WHERE symbol = ?1",
-        params![symbol],
-        |row| row.get(0),
-    ).unwrap_or(0);
-    count > 0
-}
-
-
-async fn get_account_balance(asset: &str, api_key: &str, secret_key: &str) -> Result<f64, String> {
-    let client = Client::new();
-    let timestamp = get_binance_server_time().await.unwrap_or(0);
-    let query_string = format!("timestamp={}", timestamp);
-    let signature = generate_signature(&query_string, secret_key);
-
-    let url = format!(
-        "https://api.binance.com/api/v3/account?{}&signature={}",
-        query_string, signature
-    );
-
-    let mut headers = HeaderMap::new();
-    headers.insert("X-MBX-APIKEY", HeaderValue::from_str(api_key).unwrap());
-    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-
-    let response = client.get(&url).headers(headers).send().await;
-
-    match response {
-        Ok(resp) if resp.status().is_success() => {
-            let json_resp: serde_json::Value = resp.json().await.unwrap();
-            if let Some(balances) = json_resp["balances"].as_array() {
-                for balance in balances {
-                    if balance["asset"] == asset {
-                        let free_balance = balance["free"].as_str().unwrap_or("0.0").parse::<f64>().unwrap_or(0.0);
-                        let locked_balance = balance["locked"].as_str().unwrap_or("0.0").parse::<f64>().unwrap_or(0.0);
-
-                        println!(
-                            "🔹 {} Balance: Free = {:.2}, Locked = {:.2}",
-                            asset, free_balance, locked_balance
-                        );
-
-                        return Ok(free_balance);
-                    }
-                }
-            }
-            Err("Asset not found in balance.".to_string())
-        }
-        Ok(resp) => Err(resp.text().await.unwrap_or_else(|_| "Unknown error".to_string())),
-        Err(e) => Err(e.to_string()),
-    }
-}
-
 fn adjust_quantity(quantity: f64, step_size: f64) -> f64 {
     (quantity / step_size).trunc() * step_size
 }
 
-
-
-
-async fn get_filled_sell_orders(db: &mut Connection) -> Vec<(String, f64, f64)> {
-    dotenv().ok();
-    let config = load_config("config.txt");
-    let api_key = config.get("BINANCE_API_KEY").expect("Missing API key");
-    let secret_key = config.get("BINANCE_SECRET_KEY").expect("Missing secret key");
-    let client = reqwest::Client::new();
-
-    let timestamp = get_binance_server_time().await.unwrap_or(0);
-    let query_string = format!("timestamp={}", timestamp);
-    let signature = generate_signature(&query_string, &secret_key);
-
-    let url = format!("https://api.binance.com/api/v3/openOrders?{}&signature={}", query_string, signature);
-
-    let mut headers = HeaderMap::new();
-    headers.insert("X-MBX-APIKEY", HeaderValue::from_str(&api_key).unwrap());
-    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-
-    let response = client.get(&url).headers(headers).send().await;
-
-    match response {
-        Ok(resp) if resp.status().is_success() => {
-            let orders: serde_json::Value = resp.json().await.unwrap();
-            let mut filled_sell_orders = Vec::new();
-
-            if let Some(order_list) = orders.as_array() {
-                for order in order_list {
-                    let status = order["status"].as_str().unwrap_or("UNKNOWN");
-                    let order_type = order["side"].as_str().unwrap_or("UNKNOWN");
-                    let symbol = order["symbol"].as_str().unwrap_or("UNKNOWN").to_string();
-                    let price = order["price"].as_str().unwrap_or("0.0").parse::<f64>().unwrap_or(0.0);
-                    let quantity = order["origQty"].as_str().unwrap_or("0.0").parse::<f64>().unwrap_or(0.0);
-
-                    if status == "FILLED" && order_type == "SELL" {
-                        filled_sell_orders.push((symbol, price, quantity));
-                    }
-                }
-            }
-
-            return filled_sell_orders;
-        }
-        Ok(resp) => {
-            println!("Failed to fetch orders: {}", resp.text().await.unwrap());
-        }
-        Err(e) => {
-            println!("Request error: {}", e);
-        }
-    }
-
-    Vec::new()
-}
-
 async fn monitor_and_reinvest(db: &mut Connection) {
     loop {
-        let filled_orders = get_filled_sell_orders(db).await;
+        // 🔄 Lokalna kopia historii transakcji (myTrades) dla wszystkich par
+        let api = credentials::credentials();
+        sync::sync_all_trades(db, &Client::new(), &api.api_key, &api.secret_key).await;
+        stops::check_active_grids(db, &Client::new()).await;
+        breaker::check(db, &Client::new()).await;
+        volatility::check_active_grids(db, &Client::new()).await;
+        trailing::check_active_grids(db, &Client::new()).await;
+        live::run_active_grids(db, &Client::new()).await;
 
-        for (symbol, sell_price, quantity) in filled_orders {
-            let reinvest_price = sell_price * 0.95; // -5% od ceny sprzedaży
-            let reinvest_quantity = quantity * 1.0; // reinwestowanie 105% wartości
-
-            let (min_qty, step_size) = get_lot_size(&symbol).await.unwrap_or((0.01, 0.01));
-            let adjusted_quantity = adjust_quantity(reinvest_quantity, step_size);
-
-            if adjusted_quantity < min_qty {
-                println!(
-                    "⚠️ Skipping reinvestment order for {} at {:.2}, below min LOT_SIZE ({:.5})",
-                    symbol, reinvest_price, min_qty
-                );
-                continue;
-            }
+        tokio::time::sleep(Duration::from_secs(60)).await;
+    }
+}
 
-            println!(
-                "🔄 Reinvesting for {} | Buy at {:.2}, Quantity: {:.5}",
-                symbol, reinvest_price, adjusted_quantity
-            );
-
-            let buy_order_id = place_binance_order(
-                &Client::new(),
-                &load_config("config.txt").get("BINANCE_API_KEY").unwrap(),
-                &load_config("config.txt").get("BINANCE_SECRET_KEY").unwrap(),
-                &symbol,
-                "BUY",
-                reinvest_price,
-                adjusted_quantity
-            ).await.unwrap_or(0);
-
-            if buy_order_id > 0 {
-                db.execute(
-                    "INSERT INTO trades (symbol, price, quantity, timestamp, type, profit, order_id)
-                     VALUES (?1, ?2, ?3, datetime('now'), 'Buy', NULL, ?4)",
-                    params![symbol, reinvest_price, adjusted_quantity, buy_order_id],
-                ).expect("Failed to insert reinvestment buy order");
-
-                db.execute(
-                    "UPDATE capital SET amount = amount + (?1 * ?2) WHERE symbol = ?3",
-                    params![sell_price, quantity, symbol],
-                ).expect("Failed to update capital after reinvestment");
-            }
+/// Wypisuje plan zleceń gridu zwalidowanych w trybie próbnym
+fn print_order_plan(symbol: &str, plan: &[(&str, f64, f64)]) {
+    println!("\n🧪 **[DRY-RUN] Order plan for {}** (nothing was sent)", symbol);
+    let (mut buy_total, mut sell_total) = (0.0, 0.0);
+    for (side, price, quantity) in plan {
+        println!("   {:<4} | Price: {:>14.4} | Qty: {:>14.6} | Value: {:>12.2}", side, price, quantity, price * quantity);
+        if *side == "BUY" {
+            buy_total += price * quantity;
+        } else {
+            sell_total += price * quantity;
         }
-
-        tokio::time::sleep(Duration::from_secs(60)).await;
     }
+    println!("   Total BUY value: {:.2} | Total SELL value: {:.2} | Orders: {}", buy_total, sell_total, plan.len());
 }
 
 async fn execute_grid_trade(db: &mut Connection) {
@@ -782,234 +1256,683 @@ async fn execute_grid_trade(db: &mut Connection) {
         return;
     }
 
-    let (mut capital, min_price, max_price): (f64, f64, f64) = db.query_row(
-        "SELECT amount, min_price, max_price FROM capital WHERE symbol = ?1",
-        params![symbol],
-        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))
-    ).unwrap_or((0.0, 0.0, 0.0));
+    // Przydział z zyskiem pozostawionym w parze (bez puli harvested)
+    let capital = ledger::balances(db, symbol).working_capital();
 
-    if capital < 10.0 {
+    // Reverse grid handluje przydziałem w base, pozostałe strategie kapitałem w quote
+    if strategy::kind_for(db, symbol) != config::StrategyKind::Reverse && capital < 10.0 {
         println!("❌ Insufficient capital for trading this pair.");
         return;
     }
 
-    let api_key = load_config("config.txt").get("BINANCE_API_KEY").unwrap().clone();
-    let secret_key = load_config("config.txt").get("BINANCE_SECRET_KEY").unwrap().clone();
     let client = Client::new();
-
-    let current_price = match get_price(symbol, &client).await {
-        Ok(price) => price,
-        Err(_) => {
-            println!("❌ Failed to fetch price for {}", symbol);
-            return;
-        }
-    };
-
-    // 📌 Pobranie wymagań `LOT_SIZE`
-    let (min_qty, step_size) = get_lot_size(symbol).await.unwrap_or((0.01, 0.01));
-
-    // 📌 Kupno 3 pozycji od razu po aktualnej cenie
-    let order_value = capital * 0.1; // 10% kapitału na każde z 3 zleceń
-    let sell_levels = [0.05, 0.10, 0.15]; // +5%, +10%, +15% od ceny zakupu
-
-    println!(
-        "✅ Buying 3 initial positions for {} at {:.2} | Order Value: {:.2} USDC each",
-        symbol, current_price, order_value
-    );
-
-    for (i, &sell_offset) in sell_levels.iter().enumerate() {
-        let mut buy_quantity = order_value / current_price;
-        buy_quantity = adjust_quantity(buy_quantity, step_size);
-
-        if buy_quantity < min_qty {
-            println!("⚠️ Skipping buy order at {:.2}, below minimum LOT_SIZE ({:.5})", current_price, min_qty);
-            continue;
+    if strategy::kind_for(db, symbol) == config::StrategyKind::Grid {
+        if let Ok(price) = get_price(symbol, &client).await {
+            sizing::print_preview(&strategy::pair_for(db, symbol), capital, price).await;
         }
+    }
 
-        let buy_order_id = place_binance_order(
-            &client, &api_key, &secret_key, symbol, "BUY", current_price, buy_quantity
-        ).await.unwrap_or(0);
-
-        if buy_order_id > 0 {
-            db.execute(
-                "INSERT INTO trades (symbol, price, quantity, timestamp, type, profit, order_id)
-                 VALUES (?1, ?2, ?3, datetime('now'), 'Buy', NULL, ?4)",
-                params![symbol, current_price, buy_quantity, buy_order_id],
-            ).expect("Failed to insert buy order");
-
-            // 📌 Automatyczna sprzedaż
-            let sell_price = current_price * (1.0 + sell_offset);
-            let sell_quantity = buy_quantity;
+    live::start(db, &client, symbol).await;
+}
 
-            let sell_order_id = place_binance_order(
-                &client, &api_key, &secret_key, symbol, "SELL", sell_price, sell_quantity
-            ).await.unwrap_or(0);
+async fn show_menu(db: &mut Connection) {
+    loop {
+        println!("\nMenu:");
+        println!("1. View open positions (portfolio valuation)");
+        println!("2. View orders placed on Binance");
+        println!("3. Sync and view live order execution");
+        println!("4. View risk limits & open orders");
+        println!("5. View remaining capital");
+        println!("6. Set capital for a trading pair");
+        println!("7. View capital allocation per pair");
+        println!("8. Execute grid trade for a pair");
+        println!("9. View trade history");
+        println!("10. Reconcile local DB with Binance");
+        println!("11. Run API key self-check (doctor)");
+        println!("12. View event log");
+        println!("13. Re-arm circuit breaker");
+        println!("14. Preview grid order ladder");
+        println!("0. Exit");
 
-            if sell_order_id > 0 {
-                db.execute(
-                    "INSERT INTO trades (symbol, price, quantity, timestamp, type, profit, order_id)
-                     VALUES (?1, ?2, ?3, datetime('now'), 'Sell', NULL, ?4)",
-                    params![symbol, sell_price, sell_quantity, sell_order_id],
-                ).expect("Failed to insert sell order");
+        let choice: String = get_user_input("Select an option:");
+        match choice.as_str() {
+            "1" => portfolio::show_portfolio(db).await,
+            "2" => show_binance_orders(db).await,
+            "3" => show_live_execution(db).await,
+            "4" => risk::show_status(db, &Client::new()).await,
+            "5" => show_remaining_capital(db),
+            "6" => set_capital_for_pair(db).await,
+            "7" => show_capital_for_pairs(db),
+            "8" => execute_grid_trade(db).await,
+            "9" => show_trade_history(db),
+            "10" => run_reconciliation(db, false).await,
+            "11" => {
+                doctor::run_doctor().await;
             }
+            "12" => events::show_events(db),
+            "13" => breaker::rearm(db),
+            "14" => sizing::show_preview(db).await,
+            "0" => break,
+            _ => println!("Invalid option. Please try again."),
         }
     }
+}
 
-    // 📌 Ustawienie 2 poziomów kupna poniżej aktualnej ceny
-    let buy_grid_levels = [-0.05, -0.10]; // -5%, -10% poniżej aktualnej ceny
-
-    for &buy_offset in &buy_grid_levels {
-        let buy_price = current_price * (1.0 + buy_offset);
-        let mut buy_quantity = order_value / buy_price;
-        buy_quantity = adjust_quantity(buy_quantity, step_size);
+/// Uzgadnia stan bazy z Binance (przy starcie i na żądanie z menu)
+async fn run_reconciliation(db: &mut Connection, assume_yes: bool) {
+    let api = credentials::credentials();
+    reconcile::reconcile(db, &api.api_key, &api.secret_key, assume_yes).await;
+}
 
-        if buy_quantity < min_qty {
-            println!("⚠️ Skipping grid buy order at {:.2}, below minimum LOT_SIZE ({:.5})", buy_price, min_qty);
-            continue;
-        }
+fn offline_arg() -> Arg {
+    Arg::new("offline")
+        .long("offline")
+        .action(clap::ArgAction::SetTrue)
+        .help("Use klines stored by `download` instead of the API")
+}
 
-        place_binance_order(&client, &api_key, &secret_key, symbol, "BUY", buy_price, buy_quantity).await.unwrap_or(0);
+/// Świece z lokalnej bazy (`--offline`) lub z API
+async fn load_candles(sub: &clap::ArgMatches, symbol: &str, interval: &str, limit: usize) -> Result<Vec<Candle>, String> {
+    if !sub.get_flag("offline") {
+        return get_klines(&Client::new(), symbol, interval, limit).await;
     }
+    let candles = klines::load(&setup_db(), symbol, interval, limit);
+    if candles.is_empty() {
+        return Err(format!("no stored {} klines, run `download --symbols {} --intervals {}` first", interval, symbol, interval));
+    }
+    Ok(candles)
+}
 
-    db.execute(
-        "UPDATE capital SET is_active = 1 WHERE symbol = ?1",
-        params![symbol],
-    ).expect("Failed to update trading bot status");
+/// Wspólne argumenty backtestu i paper tradingu
+fn sim_args(command: Command) -> Command {
+    command
+        .arg(
+            Arg::new("symbol")
+                .long("symbol")
+                .required(true)
+                .help("Trading pair, e.g. BTCUSDT"),
+        )
+        .arg(
+            Arg::new("capital")
+                .long("capital")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("1000")
+                .help("Starting capital in the quote asset"),
+        )
+        .arg(
+            Arg::new("fee")
+                .long("fee")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0.001")
+                .help("Fee rate per fill (0.001 = 0.1%)"),
+        )
+        .arg(
+            Arg::new("strategy")
+                .long("strategy")
+                .value_parser(config::StrategyKind::ALL.map(|kind| kind.as_str()))
+                .help("Strategy to run instead of the pair's live strategy (capital table, then config.toml)"),
+        )
+        .arg(
+            Arg::new("min-price")
+                .long("min-price")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0")
+                .help("Lower bound of the price range (infinity grid)"),
+        )
+}
 
-    println!("✅ Trading bot for {} started successfully!", symbol);
+/// Konfiguracja pary dla symulacji (strategia jak na żywo lub z `--strategy`) i (kapitał, prowizja, dolna granica)
+fn sim_setup(sub: &clap::ArgMatches) -> (config::PairConfig, f64, f64, f64) {
+    let symbol = sub.get_one::<String>("symbol").expect("symbol is required").to_uppercase();
+    // Ustawienia jak na żywo (zapisane dla pary w bazie, inaczej z config.toml); `--strategy` je nadpisuje
+    let mut pair = strategy::pair_for(&setup_db(), &symbol);
+    if let Some(kind) = sub.get_one::<String>("strategy").and_then(|name| config::StrategyKind::parse(name)) {
+        pair.strategy = kind;
+    }
+    let capital = *sub.get_one::<f64>("capital").expect("capital has a default value");
+    let fee = *sub.get_one::<f64>("fee").expect("fee has a default value");
+    let min_price = *sub.get_one::<f64>("min-price").expect("min-price has a default value");
+    (pair, capital, fee, min_price)
 }
 
+fn cli() -> Command {
+    Command::new("spot_grid_bot_v3")
+        .about("Binance spot grid trading bot")
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("PATH")
+                .default_value("config.toml")
+                .help("Path to the TOML configuration file"),
+        )
+        .arg(
+            Arg::new("env")
+                .long("env")
+                .value_name("PROFILE")
+                .value_parser(["mainnet", "testnet"])
+                .help("Binance environment profile (testnet = testnet.binance.vision)"),
+        )
+        .arg(
+            Arg::new("base-url")
+                .long("base-url")
+                .value_name("URL")
+                .help("Custom Binance API base URL, e.g. a local mock server"),
+        )
+        .arg(
+            Arg::new("yes")
+                .long("yes")
+                .action(clap::ArgAction::SetTrue)
+                .help("Apply startup reconciliation fixes without asking"),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .action(clap::ArgAction::SetTrue)
+                .help("Validate orders with /api/v3/order/test and print the plan without sending anything"),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Check connectivity and API key permissions (trading on, withdrawals off)"),
+        )
+        .subcommand(
+            Command::new("rearm")
+                .about("Re-arm the circuit breaker after a daily loss / drawdown trip"),
+        )
+        .subcommand(
+            sim_args(Command::new("backtest"))
+                .about("Backtest the pair's strategy on historical klines (nothing is sent)")
+                .arg(offline_arg())
+                .arg(
+                    Arg::new("interval")
+                        .long("interval")
+                        .default_value("1h")
+                        .help("Kline interval, e.g. 1m, 15m, 1h, 1d"),
+                )
+                .arg(
+                    Arg::new("limit")
+                        .long("limit")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("1000")
+                        .help("Number of klines (max 1000 from the API)"),
+                ),
+        )
+        .subcommand(
+            sim_args(Command::new("optimize"))
+                .about("Sweep grid parameters over historical klines with parallel backtests")
+                .arg(offline_arg())
+                .arg(
+                    Arg::new("interval")
+                        .long("interval")
+                        .default_value("1h")
+                        .help("Kline interval, e.g. 1m, 15m, 1h, 1d"),
+                )
+                .arg(
+                    Arg::new("limit")
+                        .long("limit")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("1000")
+                        .help("Number of klines (max 1000 from the API)"),
+                )
+                .arg(
+                    Arg::new("levels")
+                        .long("levels")
+                        .value_parser(clap::value_parser!(u32).range(1..))
+                        .value_delimiter(',')
+                        .default_value("3,5,8,12")
+                        .help("Comma-separated counts of buy levels below the price"),
+                )
+                .arg(
+                    Arg::new("widths")
+                        .long("widths")
+                        .value_parser(clap::value_parser!(f64))
+                        .value_delimiter(',')
+                        .default_value("0.05,0.1,0.2,0.3")
+                        .help("Comma-separated range widths below the price (0.1 = 10%)"),
+                )
+                .arg(
+                    Arg::new("sort")
+                        .long("sort")
+                        .value_parser(optimize::SortBy::NAMES)
+                        .default_value("profit")
+                        .help("Ranking: net profit, lowest drawdown or most fills"),
+                )
+                .arg(
+                    Arg::new("top")
+                        .long("top")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("20")
+                        .help("Rows shown in the table (the CSV has all results)"),
+                )
+                .arg(
+                    Arg::new("csv")
+                        .long("csv")
+                        .value_name("PATH")
+                        .help("CSV output path (default optimize_<SYMBOL>.csv)"),
+                ),
+        )
+        .subcommand(
+            sim_args(Command::new("paper"))
+                .about("Paper trade the pair's strategy on live prices with a simulated balance")
+                .arg(
+                    Arg::new("every")
+                        .long("every")
+                        .value_parser(clap::value_parser!(u64))
+                        .default_value("10")
+                        .help("Seconds between price checks"),
+                ),
+        )
+        .subcommand(
+            Command::new("suggest-range")
+                .about("Suggest a grid range and level count from recent klines")
+                .arg(offline_arg())
+                .arg(
+                    Arg::new("symbol")
+                        .long("symbol")
+                        .required(true)
+                        .help("Trading pair, e.g. BTCUSDT"),
+                )
+                .arg(
+                    Arg::new("interval")
+                        .long("interval")
+                        .default_value("1h")
+                        .help("Kline interval, e.g. 15m, 1h, 4h, 1d"),
+                )
+                .arg(
+                    Arg::new("lookback")
+                        .long("lookback")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("168")
+                        .help("Number of klines to analyse (max 1000 from the API)"),
+                )
+                .arg(
+                    Arg::new("method")
+                        .long("method")
+                        .value_parser(ranges::RangeMethod::NAMES)
+                        .default_value("atr")
+                        .help("atr: close ± k·ATR·√n, bollinger: mean ± k·σ, percentile: p..100-p of closes"),
+                )
+                .arg(
+                    Arg::new("k")
+                        .long("k")
+                        .value_parser(clap::value_parser!(f64))
+                        .help("Width multiplier (default 1 for atr, 2 for bollinger)"),
+                )
+                .arg(
+                    Arg::new("percentile")
+                        .long("percentile")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("5")
+                        .help("Lower percentile for the percentile method"),
+                ),
+        )
+        .subcommand(
+            Command::new("download")
+                .about("Download klines into the local `klines` table (incremental, with gap refill)")
+                .arg(
+                    Arg::new("symbols")
+                        .long("symbols")
+                        .value_delimiter(',')
+                        .help("Comma-separated pairs (default: all [[pairs]] from config.toml)"),
+                )
+                .arg(
+                    Arg::new("intervals")
+                        .long("intervals")
+                        .value_delimiter(',')
+                        .default_value("1h")
+                        .help("Comma-separated kline intervals, e.g. 1m,1h,1d"),
+                )
+                .arg(
+                    Arg::new("days")
+                        .long("days")
+                        .value_parser(clap::value_parser!(u32))
+                        .default_value("30")
+                        .help("History to fetch for a pair/interval that has no stored klines yet"),
+                ),
+        )
+        .subcommand(
+            Command::new("create-keystore")
+                .about("Encrypt API keys into a passphrase-protected keystore file")
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .value_name("PATH")
+                        .default_value("keystore.json")
+                        .help("Where to write the keystore"),
+                ),
+        )
+}
 
+#[tokio::main]
+async fn main() {
+    let matches = cli().get_matches();
 
+    dotenv().ok();
 
+    if let Some(("create-keystore", sub)) = matches.subcommand() {
+        let output = sub.get_one::<String>("output").expect("output has a default value");
+        if let Err(e) = credentials::create_keystore(output) {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
 
+    let config_path = matches.get_one::<String>("config").expect("config has a default value");
+    let bot_config = match config::load(config_path) {
+        Ok(bot_config) => bot_config,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    };
+    config::init(bot_config);
+
+    let environment = &config::config().environment;
+    let env_profile = matches.get_one::<String>("env").or(environment.profile.as_ref());
+    let custom_url = matches.get_one::<String>("base-url").or(environment.base_url.as_ref());
+    match resolve_base_url(env_profile.map(String::as_str), custom_url.map(String::as_str)) {
+        Ok(url) => {
+            println!("🌐 Using Binance API at {}", url);
+            BASE_URL.set(url).expect("Base URL already set");
+        }
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    }
 
+    // 🧪 Backtest i paper trading nie potrzebują kluczy API
+    if let Some(("backtest", sub)) = matches.subcommand() {
+        let (pair, capital, fee, min_price) = sim_setup(sub);
+        let interval = sub.get_one::<String>("interval").expect("interval has a default value");
+        let limit = *sub.get_one::<usize>("limit").expect("limit has a default value");
+        let candles = match load_candles(sub, &pair.symbol, interval, limit).await {
+            Ok(candles) => candles,
+            Err(e) => {
+                eprintln!("❌ Failed to fetch klines for {}: {}", pair.symbol, e);
+                std::process::exit(1);
+            }
+        };
+        println!("🧪 Backtesting {} strategy on {} {} klines of {}", pair.strategy.as_str(), candles.len(), interval, pair.symbol);
+        match sim::run_backtest(&pair, capital, min_price, &candles, fee) {
+            Ok(result) => sim::print_backtest(&pair.symbol, &result),
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
+    if let Some(("download", sub)) = matches.subcommand() {
+        let symbols: Vec<String> = match sub.get_many::<String>("symbols") {
+            Some(symbols) => symbols.map(|s| s.to_uppercase()).collect(),
+            None => config::config().pairs.iter().map(|pair| pair.symbol.clone()).collect(),
+        };
+        if symbols.is_empty() {
+            eprintln!("❌ No symbols given and no [[pairs]] in config.toml");
+            std::process::exit(1);
+        }
+        let days = *sub.get_one::<u32>("days").expect("days has a default value");
+        let db = setup_db();
+        let client = Client::new();
+        let mut failed = false;
+        for interval in sub.get_many::<String>("intervals").expect("intervals has a default value") {
+            let Some(secs) = interval_secs(interval) else {
+                eprintln!("❌ Unknown kline interval `{}`", interval);
+                std::process::exit(1);
+            };
+            for symbol in &symbols {
+                if let Err(e) = klines::sync(&db, &client, symbol, interval, secs as i64 * 1000, days).await {
+                    eprintln!("❌ Failed to download {} {} klines: {}", symbol, interval, e);
+                    failed = true;
+                }
+            }
+        }
+        klines::show_coverage(&db);
+        std::process::exit(if failed { 1 } else { 0 });
+    }
 
+    if let Some(("optimize", sub)) = matches.subcommand() {
+        let (pair, capital, fee, min_price) = sim_setup(sub);
+        let interval = sub.get_one::<String>("interval").expect("interval has a default value");
+        let limit = *sub.get_one::<usize>("limit").expect("limit has a default value");
+        let levels: Vec<u32> = sub.get_many::<u32>("levels").expect("levels has a default value").copied().collect();
+        let widths: Vec<f64> = sub.get_many::<f64>("widths").expect("widths has a default value").copied().collect();
+        let sort_by = optimize::SortBy::parse(sub.get_one::<String>("sort").expect("sort has a default value"))
+            .expect("sort is validated by clap");
+        let top = *sub.get_one::<usize>("top").expect("top has a default value");
+        let csv_path = sub.get_one::<String>("csv").cloned().unwrap_or_else(|| format!("optimize_{}.csv", pair.symbol));
+
+        let candles = match load_candles(sub, &pair.symbol, interval, limit).await {
+            Ok(candles) => candles,
+            Err(e) => {
+                eprintln!("❌ Failed to fetch klines for {}: {}", pair.symbol, e);
+                std::process::exit(1);
+            }
+        };
+        let combos = optimize::combinations(&pair, &levels, &widths);
+        println!(
+            "🔬 Optimizing {} strategy for {}: {} combinations on {} {} klines",
+            pair.strategy.as_str(), pair.symbol, combos.len(), candles.len(), interval
+        );
+        let mut outcomes = optimize::run(combos, capital, min_price, &candles, fee);
+        if outcomes.is_empty() {
+            eprintln!("❌ No backtest produced a result (check --levels / --widths and the klines)");
+            std::process::exit(1);
+        }
+        optimize::rank(&mut outcomes, sort_by);
+        optimize::print_table(&outcomes, top);
+        match optimize::write_csv(&csv_path, &outcomes) {
+            Ok(()) => println!("\n💾 {} results written to {}", outcomes.len(), csv_path),
+            Err(e) => eprintln!("❌ {}", e),
+        }
+        return;
+    }
 
+    if let Some(("suggest-range", sub)) = matches.subcommand() {
+        let symbol = sub.get_one::<String>("symbol").expect("symbol is required").to_uppercase();
+        let interval = sub.get_one::<String>("interval").expect("interval has a default value");
+        let lookback = *sub.get_one::<usize>("lookback").expect("lookback has a default value");
+        let method = ranges::RangeMethod::parse(sub.get_one::<String>("method").expect("method has a default value"))
+            .expect("method is validated by clap");
+        let k = sub.get_one::<f64>("k").copied().unwrap_or(if method == ranges::RangeMethod::Bollinger { 2.0 } else { 1.0 });
+        let percentile = *sub.get_one::<f64>("percentile").expect("percentile has a default value");
+
+        let Some(secs) = interval_secs(interval) else {
+            eprintln!("❌ Unknown kline interval `{}`", interval);
+            std::process::exit(1);
+        };
+        let candles = match load_candles(sub, &symbol, interval, lookback).await {
+            Ok(candles) if candles.len() >= 2 => candles,
+            Ok(_) => {
+                eprintln!("❌ Not enough klines for {}", symbol);
+                std::process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("❌ Failed to fetch klines for {}: {}", symbol, e);
+                std::process::exit(1);
+            }
+        };
+        match ranges::suggest(&candles, method, k, percentile) {
+            Some(range) => ranges::present(&setup_db(), &strategy::pair_for(&setup_db(), &symbol), &candles, secs, range),
+            None => {
+                eprintln!("❌ Cannot derive a positive price range for {} (try a smaller --k)", symbol);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
+    if let Some(("paper", sub)) = matches.subcommand() {
+        let (pair, capital, fee, min_price) = sim_setup(sub);
+        let every = *sub.get_one::<u64>("every").expect("every has a default value");
+        sim::run_paper(pair, capital, min_price, fee, every).await;
+        return;
+    }
 
-/*async fn execute_grid_trade(db: &Connection) {
+    // 🔑 Klucze: zmienne środowiskowe → config.toml → zaszyfrowany keystore
+    if let Err(e) = credentials::init(config_path) {
+        eprintln!("❌ {}", credentials::redact(&e));
+        std::process::exit(1);
+    }
 
-    let symbol = get_user_input("Enter trading pair symbol to execute grid trade (e.g., BTCUSDT):");
-    let min_price: f64 = get_user_input("Enter minimum price range:").parse().expect("Invalid min price");
-    let max_price: f64 = get_user_input("Enter maximum price range:").parse().expect("Invalid max price");
-    let capital: f64 = db.query_row(
-        "SELECT amount FROM capital WHERE symbol = ?1",
-        params![symbol],
-        |row| row.get(0),
-    ).unwrap_or(0.0);
+    if let Some(("doctor", _)) = matches.subcommand() {
+        let healthy = doctor::run_doctor().await;
+        std::process::exit(if healthy { 0 } else { 1 });
+    }
 
-    if capital < 10.0 {
-        println!("Insufficient capital for trading this pair.");
+    if let Some(("rearm", _)) = matches.subcommand() {
+        breaker::rearm(&setup_db());
         return;
     }
 
-    let grid_step = 0.03;
-    let order_size = 2.0;
-    let mut buy_orders = 0;
-    let mut sell_orders = 0;
-    let client = Client::new();
-    let current_price = match get_price(&symbol, &client).await {
-        Ok(price) => price,
-        Err(_) => {
-            println!("Failed to fetch price for {}", symbol);
-            return;
-        }
-    };
+    if matches.get_flag("dry-run") {
+        DRY_RUN.store(true, Ordering::SeqCst);
+        println!("🧪 Dry-run mode: orders are validated but never sent");
+    }
 
-    if !has_open_positions(db, &symbol) {
-        println!("No open positions found for {}. Initializing starting grid with 3 buy and 3 sell orders.", symbol);
-        println!("Initializing grid for {} at price {:.2}", symbol, current_price);
+    // 🩺 Handel na żywo tylko z kluczem bez wypłat i z uprawnieniem do spot
+    doctor::startup_check(&Client::new()).await;
 
-        for i in 0..3 {
-            let buy_price = current_price * (1.0 - (grid_step * (i + 1) as f64));
-            let sell_price = current_price * (1.0 + (grid_step * (i + 1) as f64));
+    let mut db = setup_db();
 
-            println!("Placing Buy order at {:.2} for ${:.2}", buy_price, order_size);
-            db.execute(
-                "INSERT INTO trades (symbol, price, quantity, timestamp, type, profit) VALUES (?1, ?2, ?3, datetime('now'), 'Buy', NULL)",
-                params![symbol, buy_price, order_size],
-            ).expect("Failed to insert buy order");
+    // 🔍 Uzgodnienie stanu po restarcie / awarii
+    run_reconciliation(&mut db, matches.get_flag("yes")).await;
 
-            println!("Placing Sell order at {:.2} for ${:.2}", sell_price, order_size);
-            db.execute(
-                "INSERT INTO trades (symbol, price, quantity, timestamp, type, profit) VALUES (?1, ?2, ?3, datetime('now'), 'Sell', NULL)",
-                params![symbol, sell_price, order_size],
-            ).expect("Failed to insert sell order");
-        }
-    } else {
-        println!("Executing grid strategy for {} in price range {} - {}", symbol, min_price, max_price);
-        let mut price = min_price;
-        while price <= max_price && (buy_orders + sell_orders) < 5 {
-            if buy_orders < 3 {
-                println!("Placing Buy order at {:.2} for ${:.2}", price, order_size);
-                db.execute(
-                    "INSERT INTO trades (symbol, price, quantity, timestamp, type, profit) VALUES (?1, ?2, ?3, datetime('now'), 'Buy', NULL)",
-                    params![symbol, price, order_size],
-                ).expect("Failed to insert buy order");
-                buy_orders += 1;
-            }
-            if sell_orders < 2 {
-                let sell_price = price * (1.0 + grid_step);
-                println!("Placing Sell order at {:.2} for ${:.2}", sell_price, order_size);
-                db.execute(
-                    "INSERT INTO trades (symbol, price, quantity, timestamp, type, profit) VALUES (?1, ?2, ?3, datetime('now'), 'Sell', NULL)",
-                    params![symbol, sell_price, order_size],
-                ).expect("Failed to insert sell order");
-                sell_orders += 1;
-            }
-            price += price * grid_step;
-        }
-    }
+    // 🚀 Uruchomienie reinwestowania w osobnym zadaniu (z własnym połączeniem do bazy)
+    tokio::spawn(async {
+        let mut monitor_db = setup_db();
+        monitor_and_reinvest(&mut monitor_db).await;
+    });
+
+    show_menu(&mut db).await;
 }
 
-*/
-async fn show_menu(db: &mut Connection) {
-    loop {
-        println!("\nMenu:");
-        println!("1. View open positions");
-        println!("2. View orders placed on Binance");
-        println!("3. View live order execution");
-        println!("4. View active orders management");
-        println!("5. View remaining capital");
-        println!("6. Set capital for a trading pair");
-        println!("7. View capital allocation per pair");
-        println!("8. Execute grid trade for a pair");
-        println!("9. Exit");
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let choice: String = get_user_input("Select an option:");
-        match choice.as_str() {
-            "1" => show_open_positions(db),
-            "2" => show_binance_orders(db).await,
-            "3" => show_live_execution().await,
-            "4" => manage_active_orders(db),
-            "5" => show_remaining_capital(db),
-            "6" => set_capital_for_pair(db),
-            "7" => show_capital_for_pairs(db),
-            "8" => execute_grid_trade(db).await,
-            "9" => break,
-            _ => println!("Invalid option. Please try again."),
-        }
+    fn buy_fill(trade_id: u64) -> Fill {
+        Fill { trade_id, price: 100.0, qty: 0.5, commission: 0.05, commission_asset: "USDT".to_string(), time: 1_700_000_000_000 }
     }
-}
 
-use std::sync::{Arc, Mutex};
+    fn trade_count(db: &Connection) -> i64 {
+        db.query_row("SELECT COUNT(*) FROM trades", [], |row| row.get(0)).unwrap()
+    }
 
-use tokio::task;
+    #[test]
+    fn repeated_fill_is_recorded_once() {
+        let db = Connection::open_in_memory().unwrap();
+        create_schema(&db);
+        record_fill(&db, "BTCUSDT", "BUY", 1, &buy_fill(10));
+        record_fill(&db, "BTCUSDT", "BUY", 1, &buy_fill(10));
+        assert_eq!(trade_count(&db), 1);
+    }
 
-#[tokio::main]
-async fn main() {
-    let db = Arc::new(Mutex::new(setup_db()));
+    #[test]
+    fn legacy_row_is_claimed_instead_of_duplicated() {
+        let db = Connection::open_in_memory().unwrap();
+        create_schema(&db);
+        db.execute(
+            "INSERT INTO trades (symbol, price, quantity, timestamp, type, order_id) VALUES ('BTCUSDT', 100.0, 0.5, '2023-11-14 22:15:00', 'Buy', 1)",
+            [],
+        ).unwrap();
+
+        record_fill(&db, "BTCUSDT", "BUY", 1, &buy_fill(10));
+        assert_eq!(trade_count(&db), 1);
+        let (trade_id, commission): (u64, f64) = db.query_row(
+            "SELECT trade_id, commission FROM trades", [], |row| Ok((row.get(0)?, row.get(1)?)),
+        ).unwrap();
+        assert_eq!(trade_id, 10);
+        assert!((commission - 0.05).abs() < 1e-12);
+
+        // Inne wykonanie tego samego zlecenia to już nowy wiersz
+        record_fill(&db, "BTCUSDT", "BUY", 1, &Fill { qty: 0.25, ..buy_fill(11) });
+        assert_eq!(trade_count(&db), 2);
+    }
 
-    // 🚀 Uruchomienie reinwestowania w osobnym wątku
-    let db_clone = Arc::clone(&db);
-    task::spawn_blocking(move || {
-        let mut db = db_clone.lock().expect("Failed to acquire DB lock");
-        monitor_and_reinvest(&mut db);
-    });
+    #[tokio::test]
+    async fn dry_run_sends_no_cancel_or_market_order() {
+        // Jedyny test włączający tryb próbny; gdyby zapytanie wyszło, adres bez serwera zwróciłby błąd
+        DRY_RUN.store(true, Ordering::SeqCst);
+        let _ = BASE_URL.set("http://127.0.0.1:9".to_string());
+        let mut db = Connection::open_in_memory().unwrap();
+        create_schema(&db);
+        db.execute(
+            "INSERT INTO orders (order_id, symbol, price, stop_price, quantity, type, status, timestamp, side, source)
+             VALUES (1, 'BTCUSDT', 100.0, 0.0, 1.0, 'LIMIT', 'NEW', datetime('now'), 'BUY', 'bot')",
+            [],
+        ).unwrap();
+        let client = Client::new();
+
+        assert!(cancel_order(&mut db, &client, "BTCUSDT", 1).await.is_ok());
+        let status: String = db.query_row("SELECT status FROM orders WHERE order_id = 1", [], |row| row.get(0)).unwrap();
+        assert_eq!(status, "NEW");
+
+        let order = place_market_sell(&mut db, &client, "BTCUSDT", 0.5).await.unwrap();
+        assert_eq!(order.status, "DRY_RUN");
+        record_placed_order(&db, &order);
+        assert_eq!(trade_count(&db), 0);
+    }
 
-    let mut db_lock = db.lock().expect("Failed to acquire DB lock");
-    show_menu(&mut db_lock).await;
-}
+    #[test]
+    fn reverse_cycle_realizes_only_the_price_difference() {
+        config::init_for_tests();
+        let db = Connection::open_in_memory().unwrap();
+        create_schema(&db);
+        db.execute(
+            "INSERT INTO capital (symbol, amount, min_price, max_price, strategy, profit_mode)
+             VALUES ('BTCUSDT', 0.0, 50000.0, 70000.0, 'reverse', 'sweep')",
+            [],
+        ).unwrap();
+        allocate_base(&db, "BTCUSDT", 1.0, 60000.0);
+        let fill = |trade_id, price, qty| Fill { trade_id, price, qty, commission: 0.0, commission_asset: "USDT".to_string(), time: 1_700_000_000_000 + trade_id };
+
+        record_fill(&db, "BTCUSDT", "SELL", 1, &fill(10, 61000.0, 0.5));
+        let after_sell = ledger::balances(&db, "BTCUSDT");
+        assert!((after_sell.realized_profit - 500.0).abs() < 1e-6);
+        assert!((after_sell.harvested - 500.0).abs() < 1e-6);
+        assert!((after_sell.free - 30000.0).abs() < 1e-6);
+
+        // Odkup za cały przychód pozostały w parze
+        ledger::reserve_for_buy(&db, "BTCUSDT", 2, 30000.0);
+        record_fill(&db, "BTCUSDT", "BUY", 2, &fill(11, 60000.0, 0.5));
+        ledger::release_order(&db, "BTCUSDT", 2);
+        let after_buy = ledger::balances(&db, "BTCUSDT");
+        assert!(after_buy.free.abs() < 1e-6);
+        assert!(after_buy.imbalance().abs() < 1e-6);
+        assert!((after_buy.allocation - 60000.0).abs() < 1e-6);
+
+        let position = portfolio::load_position(&db, "BTCUSDT");
+        assert!((position.held_qty - 1.0).abs() < 1e-9);
+        assert!((position.realized_pnl - 500.0).abs() < 1e-6);
+    }
 
+    #[test]
+    fn open_orders_snapshot_keeps_bot_orders_and_imports_others_as_exchange() {
+        let mut db = Connection::open_in_memory().unwrap();
+        create_schema(&db);
+        db.execute(
+            "INSERT INTO orders (order_id, symbol, price, stop_price, quantity, type, status, timestamp, side, source, level)
+             VALUES (5, 'BTCUSDT', 100.0, 0.0, 0.5, 'LIMIT', 'NEW', datetime('now'), 'BUY', 'bot', 2)",
+            [],
+        ).unwrap();
+        let manual = serde_json::json!([{
+            "orderId": 7, "symbol": "BTCUSDT", "price": "90.0", "stopPrice": "0.0", "origQty": "1.0",
+            "type": "LIMIT", "side": "BUY", "status": "NEW", "time": 1_700_000_000_000u64
+        }]);
+
+        save_orders_to_db(&mut db, &manual);
+        let source: String = db.query_row("SELECT source FROM orders WHERE order_id = 7", [], |row| row.get(0)).unwrap();
+        assert_eq!(source, "exchange");
+
+        // Oba zlecenia zniknęły z giełdy: ręczne jest usuwane, zlecenie bota zostaje z metadanymi
+        save_orders_to_db(&mut db, &serde_json::json!([]));
+        let remaining: Vec<(u64, Option<i32>)> = {
+            let mut stmt = db.prepare("SELECT order_id, level FROM orders").unwrap();
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap().filter_map(Result::ok).collect()
+        };
+        assert_eq!(remaining, vec![(5, Some(2))]);
+    }
+}
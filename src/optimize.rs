@@ -0,0 +1,195 @@
+use std::fs;
+use std::thread;
+
+use crate::config::{PairConfig, SizingScheme, SpacingType};
+use crate::sim::{self, BacktestResult};
+use crate::Candle;
+
+/// Wynik backtestu jednej kombinacji parametrów
+pub struct Outcome {
+    pub pair: PairConfig,
+    /// Szerokość zakresu poniżej ceny (ułamek), `step` = szerokość / liczba poziomów
+    pub width: f64,
+    pub result: BacktestResult,
+}
+
+impl Outcome {
+    pub fn net_profit(&self) -> f64 {
+        self.result.final_equity - self.result.start_equity
+    }
+}
+
+/// Kryterium rankingu wyników
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    Profit,
+    Drawdown,
+    Fills,
+}
+
+impl SortBy {
+    pub const NAMES: [&'static str; 3] = ["profit", "drawdown", "fills"];
+
+    pub fn parse(name: &str) -> Option<SortBy> {
+        match name {
+            "profit" => Some(SortBy::Profit),
+            "drawdown" => Some(SortBy::Drawdown),
+            "fills" => Some(SortBy::Fills),
+            _ => None,
+        }
+    }
+}
+
+/// Wszystkie poprawne kombinacje: liczba poziomów × spacing × szerokość × sizing
+pub fn combinations(base: &PairConfig, levels: &[u32], widths: &[f64]) -> Vec<(PairConfig, f64)> {
+    let mut combos = Vec::new();
+    for &levels_below in levels {
+        for spacing in SpacingType::ALL {
+            for &width in widths {
+                for sizing in SizingScheme::ALL {
+                    let mut pair = base.clone();
+                    pair.levels_below = levels_below;
+                    pair.spacing = spacing;
+                    pair.step = width / levels_below as f64;
+                    pair.sizing = sizing;
+                    // Zlecenia muszą się zmieścić w przydziale także przy większej liczbie poziomów
+                    pair.order_size = pair.order_size.min(1.0 / (pair.levels_above + levels_below) as f64);
+                    if pair.validate(0).is_ok() {
+                        combos.push((pair, width));
+                    }
+                }
+            }
+        }
+    }
+    combos
+}
+
+/// Backtesty wszystkich kombinacji równolegle (po jednym wątku na rdzeń)
+pub fn run(combos: Vec<(PairConfig, f64)>, capital: f64, min_price: f64, candles: &[Candle], fee_rate: f64) -> Vec<Outcome> {
+    let workers = thread::available_parallelism().map_or(4, |n| n.get());
+    let chunk_size = combos.len().div_ceil(workers).max(1);
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = combos.chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || {
+                chunk.iter()
+                    .filter_map(|(pair, width)| {
+                        sim::run_backtest(pair, capital, min_price, candles, fee_rate).ok()
+                            .map(|result| Outcome { pair: pair.clone(), width: *width, result })
+                    })
+                    .collect::<Vec<_>>()
+            }))
+            .collect();
+        handles.into_iter()
+            .flat_map(|handle| handle.join().expect("Backtest worker panicked"))
+            .collect()
+    })
+}
+
+pub fn rank(outcomes: &mut [Outcome], sort_by: SortBy) {
+    match sort_by {
+        SortBy::Profit => outcomes.sort_by(|a, b| b.net_profit().total_cmp(&a.net_profit())),
+        SortBy::Drawdown => outcomes.sort_by(|a, b| {
+            a.result.max_drawdown_pct.total_cmp(&b.result.max_drawdown_pct)
+                .then(b.net_profit().total_cmp(&a.net_profit()))
+        }),
+        SortBy::Fills => outcomes.sort_by(|a, b| {
+            b.result.trades.cmp(&a.result.trades).then(b.net_profit().total_cmp(&a.net_profit()))
+        }),
+    }
+}
+
+pub fn print_table(outcomes: &[Outcome], top: usize) {
+    println!(
+        "\n{:>3} | {:>6} | {:<10} | {:>6} | {:>7} | {:<11} | {:>12} | {:>8} | {:>8} | {:>6}",
+        "#", "Levels", "Spacing", "Width", "Step", "Sizing", "Net profit", "Return", "Max DD", "Fills"
+    );
+    for (index, outcome) in outcomes.iter().take(top).enumerate() {
+        let pair = &outcome.pair;
+        println!(
+            "{:>3} | {:>6} | {:<10} | {:>5.1}% | {:>6.3}% | {:<11} | {:>+12.2} | {:>+7.2}% | {:>7.2}% | {:>6}",
+            index + 1, pair.levels_below, pair.spacing.as_str(), outcome.width * 100.0, pair.step * 100.0,
+            pair.sizing.as_str(), outcome.net_profit(), outcome.result.return_pct,
+            outcome.result.max_drawdown_pct, outcome.result.trades
+        );
+    }
+}
+
+pub fn write_csv(path: &str, outcomes: &[Outcome]) -> Result<(), String> {
+    let mut csv = String::from(
+        "rank,symbol,strategy,levels_above,levels_below,spacing,width,step,sizing,order_size,net_profit,return_pct,max_drawdown_pct,fills,rejected,fees_paid\n"
+    );
+    for (index, outcome) in outcomes.iter().enumerate() {
+        let (pair, result) = (&outcome.pair, &outcome.result);
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{:.6},{:.6},{},{:.6},{:.8},{:.4},{:.4},{},{},{:.8}\n",
+            index + 1, pair.symbol, pair.strategy.as_str(), pair.levels_above, pair.levels_below,
+            pair.spacing.as_str(), outcome.width, pair.step, pair.sizing.as_str(), pair.order_size,
+            outcome.net_profit(), result.return_pct, result.max_drawdown_pct, result.trades, result.rejected, result.fees_paid
+        ));
+    }
+    fs::write(path, csv).map_err(|e| format!("Cannot write `{}`: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(levels_below: u32, net_profit: f64, max_drawdown_pct: f64, trades: usize) -> Outcome {
+        Outcome {
+            pair: PairConfig { levels_below, ..PairConfig::default_for("BTCUSDT") },
+            width: 0.1,
+            result: BacktestResult {
+                trades,
+                rejected: 0,
+                fees_paid: 0.0,
+                start_equity: 1000.0,
+                final_equity: 1000.0 + net_profit,
+                return_pct: net_profit / 10.0,
+                max_drawdown_pct,
+                final_quote: 1000.0 + net_profit,
+                final_base: 0.0,
+                harvested: 0.0,
+            },
+        }
+    }
+
+    fn order(outcomes: &[Outcome]) -> Vec<u32> {
+        outcomes.iter().map(|outcome| outcome.pair.levels_below).collect()
+    }
+
+    fn sample() -> Vec<Outcome> {
+        vec![outcome(1, 10.0, 5.0, 20), outcome(2, 30.0, 8.0, 10), outcome(3, -5.0, 2.0, 20), outcome(4, 20.0, 2.0, 40)]
+    }
+
+    #[test]
+    fn ranks_by_net_profit() {
+        let mut outcomes = sample();
+        rank(&mut outcomes, SortBy::Profit);
+        assert_eq!(order(&outcomes), vec![2, 4, 1, 3]);
+    }
+
+    #[test]
+    fn ranks_by_drawdown_then_profit() {
+        let mut outcomes = sample();
+        rank(&mut outcomes, SortBy::Drawdown);
+        assert_eq!(order(&outcomes), vec![4, 3, 1, 2]);
+    }
+
+    #[test]
+    fn ranks_by_fills_then_profit() {
+        let mut outcomes = sample();
+        rank(&mut outcomes, SortBy::Fills);
+        assert_eq!(order(&outcomes), vec![4, 1, 3, 2]);
+    }
+
+    #[test]
+    fn combinations_fit_the_allocation() {
+        let combos = combinations(&PairConfig::default_for("BTCUSDT"), &[2, 10], &[0.1, 0.3]);
+        assert!(!combos.is_empty());
+        for (pair, width) in &combos {
+            assert!(pair.validate(0).is_ok());
+            assert!((pair.step * pair.levels_below as f64 - width).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use std::collections::HashMap;
+use reqwest::Client;
+use rusqlite::{params, Connection};
+
+use crate::{get_price, get_user_input, ledger, split_symbol};
+
+/// Pozycja w parze wyliczona z wykonań metodą średniego kosztu
+#[derive(Debug, Clone, Default)]
+pub struct Position {
+    pub base_asset: String,
+    pub quote_asset: String,
+    pub held_qty: f64,
+    pub cost_basis: f64,
+    pub realized_pnl: f64,
+    /// Prowizje zapłacone w trzecim aktywie (np. BNB) – pokazywane osobno, poza PnL
+    pub other_fees: HashMap<String, f64>,
+}
+
+impl Position {
+    pub fn new(symbol: &str) -> Position {
+        let (base_asset, quote_asset) = split_symbol(symbol);
+        Position { base_asset, quote_asset, ..Default::default() }
+    }
+
+    pub fn avg_entry_price(&self) -> f64 {
+        if self.held_qty > 0.0 { self.cost_basis / self.held_qty } else { 0.0 }
+    }
+
+    pub fn unrealized_pnl(&self, price: f64) -> f64 {
+        self.held_qty * price - self.cost_basis
+    }
+
+    /// Nakłada wykonanie na pozycję i zwraca zrealizowany zysk tego wykonania (dla sprzedaży)
+    pub fn apply_fill(&mut self, trade_type: &str, price: f64, qty: f64, commission: f64, commission_asset: &str) -> f64 {
+        let quote_fee = if commission_asset == self.quote_asset { commission } else { 0.0 };
+        let base_fee = if commission_asset == self.base_asset { commission } else { 0.0 };
+        if quote_fee == 0.0 && base_fee == 0.0 && commission > 0.0 {
+            *self.other_fees.entry(commission_asset.to_string()).or_insert(0.0) += commission;
+        }
+
+        if trade_type == "Buy" {
+            self.held_qty += qty - base_fee;
+            self.cost_basis += price * qty + quote_fee;
+            return 0.0;
+        }
+
+        // Sprzedaż ponad posiadaną ilość (base spoza wykonań) nie ma kosztu – wynik tylko z posiadanej części
+        let sold = (qty + base_fee).min(self.held_qty);
+        let cost_of_sold = self.avg_entry_price() * sold;
+        let share = if qty + base_fee > 0.0 { sold / (qty + base_fee) } else { 0.0 };
+        let profit = (price * qty - quote_fee) * share - cost_of_sold;
+
+        self.held_qty -= sold;
+        self.cost_basis -= cost_of_sold;
+        if self.held_qty <= f64::EPSILON {
+            self.held_qty = 0.0;
+            self.cost_basis = 0.0;
+        }
+        self.realized_pnl += profit;
+        profit
+    }
+}
+
+/// Pozycja przed pierwszym wykonaniem: przydział base reverse gridu po koszcie z chwili przydziału
+fn opening_position(db: &Connection, symbol: &str) -> Position {
+    let (held_qty, cost_basis) = db.query_row(
+        "SELECT base_amount, base_cost FROM capital WHERE symbol = ?1",
+        params![symbol],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).unwrap_or((0.0, 0.0));
+    Position { held_qty, cost_basis, ..Position::new(symbol) }
+}
+
+/// Odtwarza pozycję z tabeli `trades`; zwraca też zysk każdego wykonania sprzedaży (id, trade_id, profit)
+fn replay_trades(db: &Connection, symbol: &str) -> (Position, Vec<(i64, Option<u64>, f64)>) {
+    let mut position = opening_position(db, symbol);
+    let mut sell_profits = Vec::new();
+
+    let mut stmt = db.prepare(
+        "SELECT id, type, price, quantity, commission, COALESCE(commission_asset, ''), trade_id
+         FROM trades WHERE symbol = ?1 ORDER BY timestamp ASC, id ASC",
+    ).expect("Failed to prepare statement");
+
+    let rows = stmt.query_map(params![symbol], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, f64>(2)?,
+            row.get::<_, f64>(3)?,
+            row.get::<_, f64>(4)?,
+            row.get::<_, String>(5)?,
+            row.get::<_, Option<u64>>(6)?,
+        ))
+    }).expect("Failed to query trades");
+
+    for (id, trade_type, price, qty, commission, commission_asset, trade_id) in rows.filter_map(Result::ok) {
+        let profit = position.apply_fill(&trade_type, price, qty, commission, &commission_asset);
+        if trade_type == "Sell" {
+            sell_profits.push((id, trade_id, profit));
+        }
+    }
+
+    (position, sell_profits)
+}
+
+pub fn load_position(db: &Connection, symbol: &str) -> Position {
+    replay_trades(db, symbol).0
+}
+
+/// Pozycja po ostatnim zapisanym wykonaniu – aktualizowana przy każdym wykonaniu,
+/// żeby zysk sprzedaży nie wymagał odtwarzania całej historii pary
+pub fn setup(conn: &Connection) {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS positions (
+            symbol TEXT PRIMARY KEY,
+            held_qty REAL NOT NULL,
+            cost_basis REAL NOT NULL,
+            realized_pnl REAL NOT NULL
+        )",
+        [],
+    ).expect("Failed to create positions table");
+}
+
+/// Bieżąca pozycja pary z tabeli `positions` (przy pierwszym użyciu odtwarzana z `trades`)
+pub fn running_position(db: &Connection, symbol: &str) -> Position {
+    let stored = db.query_row(
+        "SELECT held_qty, cost_basis, realized_pnl FROM positions WHERE symbol = ?1",
+        params![symbol],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    ).ok();
+    match stored {
+        Some((held_qty, cost_basis, realized_pnl)) => Position { held_qty, cost_basis, realized_pnl, ..Position::new(symbol) },
+        None => load_position(db, symbol),
+    }
+}
+
+pub fn store_position(db: &Connection, symbol: &str, position: &Position) {
+    db.execute(
+        "INSERT OR REPLACE INTO positions (symbol, held_qty, cost_basis, realized_pnl) VALUES (?1, ?2, ?3, ?4)",
+        params![symbol, position.held_qty, position.cost_basis, position.realized_pnl],
+    ).expect("Failed to store position");
+}
+
+/// Odtwarza pozycję pary od nowa i zapisuje zrealizowany zysk (`profit`) wykonań sprzedaży
+/// (po wykonaniu starszym niż już zapisane lub po zmianie historii); zmiany zysku koryguje w księdze
+pub fn refresh_realized_profit(db: &Connection, symbol: &str) {
+    let (position, sell_profits) = replay_trades(db, symbol);
+    for (id, trade_id, profit) in sell_profits {
+        db.execute("UPDATE trades SET profit = ?1 WHERE id = ?2", params![profit, id])
+            .expect("Failed to update realized profit");
+        // Zmieniony zysk wcześniej zaksięgowanej sprzedaży – korekta w księdze kapitału
+        if let Some(trade_id) = trade_id {
+            ledger::adjust_sell_profit(db, symbol, trade_id, profit);
+        }
+    }
+    store_position(db, symbol, &position);
+}
+
+/// Kurs przeliczenia `from` -> `to` (bezpośrednio, odwrotnie lub przez USDT)
+pub async fn conversion_rate(
+    client: &Client,
+    from: &str,
+    to: &str,
+    cache: &mut HashMap<String, Option<f64>>
+) -> Option<f64> {
+    if from == to {
+        return Some(1.0);
+    }
+    if let Some(rate) = direct_rate(client, from, to, cache).await {
+        return Some(rate);
+    }
+    if from != "USDT" && to != "USDT" {
+        let to_usdt = direct_rate(client, from, "USDT", cache).await?;
+        let from_usdt = direct_rate(client, "USDT", to, cache).await?;
+        return Some(to_usdt * from_usdt);
+    }
+    None
+}
+
+async fn direct_rate(client: &Client, from: &str, to: &str, cache: &mut HashMap<String, Option<f64>>) -> Option<f64> {
+    for (pair, inverse) in [(format!("{}{}", from, to), false), (format!("{}{}", to, from), true)] {
+        let price = match cache.get(&pair) {
+            Some(price) => *price,
+            None => {
+                let price = get_price(&pair, client).await.ok().filter(|p| *p > 0.0);
+                cache.insert(pair.clone(), price);
+                price
+            }
+        };
+        if let Some(price) = price {
+            return Some(if inverse { 1.0 / price } else { price });
+        }
+    }
+    None
+}
+
+/// Wycena portfela: pozycje, PnL i niewykorzystany kapitał w wybranej walucie raportowej
+pub async fn show_portfolio(db: &Connection) {
+    let input = get_user_input("Enter reporting currency (e.g., USDT, USDC, EUR) [USDT]:").to_uppercase();
+    let reporting = if input.is_empty() { "USDT".to_string() } else { input };
+
+    let symbols: Vec<String> = {
+        let mut stmt = db.prepare("SELECT symbol FROM capital UNION SELECT symbol FROM trades ORDER BY symbol ASC")
+            .expect("Failed to prepare statement");
+        stmt.query_map([], |row| row.get(0))
+            .expect("Failed to query symbols")
+            .filter_map(Result::ok)
+            .collect()
+    };
+
+    if symbols.is_empty() {
+        println!("❌ No trading pairs or trades found.");
+        return;
+    }
+
+    let client = Client::new();
+    let mut rates = HashMap::new();
+    let (mut total_value, mut total_cost, mut total_unrealized, mut total_realized, mut total_free) = (0.0, 0.0, 0.0, 0.0, 0.0);
+
+    println!("\n📊 **Portfolio valuation** (reporting currency: {})\n", reporting);
+    for symbol in symbols {
+        let position = load_position(db, &symbol);
+        let current_price = match get_price(&symbol, &client).await {
+            Ok(price) => price,
+            Err(_) => {
+                println!("❌ Failed to fetch price for {}", symbol);
+                continue;
+            }
+        };
+
+        let market_value = position.held_qty * current_price;
+        let unrealized = position.unrealized_pnl(current_price);
+        let unallocated = ledger::balances(db, &symbol).free;
+
+        println!(
+            "🔹 **{}** | Held: {:.6} {} | Avg entry: {:.4} | Price: {:.4}\n   💰 Value: {:.2} {} | Unrealized: {:+.2} | Realized: {:+.2} | Unallocated: {:.2}",
+            symbol, position.held_qty, position.base_asset, position.avg_entry_price(), current_price,
+            market_value, position.quote_asset, unrealized, position.realized_pnl, unallocated
+        );
+        // Prowizje w trzecim aktywie (np. BNB) nie obciążają PnL – tak jak w stop-lossie, bezpieczniku i księdze
+        for (asset, amount) in &position.other_fees {
+            println!("   🧾 Fees paid in {}: {:.8} (not included in PnL)", asset, amount);
+        }
+
+        match conversion_rate(&client, &position.quote_asset, &reporting, &mut rates).await {
+            Some(rate) => {
+                total_value += market_value * rate;
+                total_cost += position.cost_basis * rate;
+                total_unrealized += unrealized * rate;
+                total_realized += position.realized_pnl * rate;
+                total_free += unallocated * rate;
+            }
+            None => println!("⚠️ Cannot convert {} to {}, {} excluded from totals", position.quote_asset, reporting, symbol),
+        }
+    }
+
+    println!(
+        "\n📈 **Totals ({})** | Value: {:.2} | Cost: {:.2} | Unrealized: {:+.2} | Realized: {:+.2} | Unallocated: {:.2}",
+        reporting, total_value, total_cost, total_unrealized, total_realized, total_free
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "expected {}, got {}", expected, actual);
+    }
+
+    fn insert_trade(db: &Connection, timestamp: &str, trade_type: &str, price: f64, quantity: f64) {
+        db.execute(
+            "INSERT INTO trades (symbol, price, quantity, timestamp, type, commission, commission_asset)
+             VALUES ('BTCUSDT', ?1, ?2, ?3, ?4, 0, 'USDT')",
+            params![price, quantity, timestamp, trade_type],
+        ).unwrap();
+    }
+
+    #[test]
+    fn base_fee_reduces_held_quantity_and_sell_realizes_profit() {
+        let mut position = Position::new("BTCUSDT");
+        assert_close(position.apply_fill("Buy", 100.0, 1.0, 0.001, "BTC"), 0.0);
+        assert_close(position.held_qty, 0.999);
+        assert_close(position.cost_basis, 100.0);
+
+        let profit = position.apply_fill("Sell", 110.0, 0.999, 0.10989, "USDT");
+        assert_close(profit, 110.0 * 0.999 - 0.10989 - 100.0);
+        assert_close(position.held_qty, 0.0);
+        assert_close(position.cost_basis, 0.0);
+        assert_close(position.realized_pnl, profit);
+    }
+
+    #[test]
+    fn quote_fee_on_buy_is_part_of_cost_basis() {
+        let mut position = Position::new("BTCUSDT");
+        position.apply_fill("Buy", 50.0, 2.0, 0.1, "USDT");
+        assert_close(position.held_qty, 2.0);
+        assert_close(position.avg_entry_price(), 50.05);
+        assert_close(position.unrealized_pnl(60.0), 120.0 - 100.1);
+    }
+
+    #[test]
+    fn partial_sell_keeps_average_entry() {
+        let mut position = Position::new("BTCUSDT");
+        position.apply_fill("Buy", 100.0, 1.0, 0.0, "USDT");
+        position.apply_fill("Buy", 80.0, 1.0, 0.0, "USDT");
+        let profit = position.apply_fill("Sell", 100.0, 1.0, 0.0, "USDT");
+        assert_close(profit, 10.0);
+        assert_close(position.held_qty, 1.0);
+        assert_close(position.avg_entry_price(), 90.0);
+    }
+
+    #[test]
+    fn third_asset_fee_is_kept_out_of_pnl() {
+        let mut position = Position::new("BTCUSDT");
+        position.apply_fill("Buy", 100.0, 1.0, 0.01, "BNB");
+        let profit = position.apply_fill("Sell", 110.0, 1.0, 0.01, "BNB");
+        assert_close(profit, 10.0);
+        assert_close(position.other_fees["BNB"], 0.02);
+    }
+
+    #[test]
+    fn sell_above_held_quantity_closes_the_position() {
+        let mut position = Position::new("BTCUSDT");
+        position.apply_fill("Buy", 100.0, 1.0, 0.0, "USDT");
+        let profit = position.apply_fill("Sell", 120.0, 1.5, 0.0, "USDT");
+        assert_close(profit, 20.0);
+        assert_close(position.held_qty, 0.0);
+        assert_close(position.cost_basis, 0.0);
+
+        // Bez posiadanego base cała sprzedaż jest poza pozycją – bez zysku
+        assert_close(position.apply_fill("Sell", 120.0, 1.0, 0.0, "USDT"), 0.0);
+        assert_close(position.realized_pnl, 20.0);
+    }
+
+    #[test]
+    fn refresh_replays_fills_in_time_order_and_updates_running_position() {
+        let db = Connection::open_in_memory().unwrap();
+        crate::create_schema(&db);
+        insert_trade(&db, "2024-01-01 00:00:00", "Buy", 100.0, 1.0);
+        insert_trade(&db, "2024-01-03 00:00:00", "Sell", 120.0, 1.0);
+        // Kupno zapisane później, ale wykonane przed sprzedażą
+        insert_trade(&db, "2024-01-02 00:00:00", "Buy", 80.0, 1.0);
+
+        refresh_realized_profit(&db, "BTCUSDT");
+
+        let profit: f64 = db.query_row("SELECT profit FROM trades WHERE type = 'Sell'", [], |row| row.get(0)).unwrap();
+        assert_close(profit, 30.0);
+        let running = running_position(&db, "BTCUSDT");
+        let replayed = load_position(&db, "BTCUSDT");
+        assert_close(running.held_qty, replayed.held_qty);
+        assert_close(running.cost_basis, replayed.cost_basis);
+        assert_close(running.realized_pnl, 30.0);
+    }
+
+    #[test]
+    fn running_position_falls_back_to_replay() {
+        let db = Connection::open_in_memory().unwrap();
+        crate::create_schema(&db);
+        insert_trade(&db, "2024-01-01 00:00:00", "Buy", 100.0, 2.0);
+
+        let position = running_position(&db, "BTCUSDT");
+        assert_close(position.held_qty, 2.0);
+        assert_close(position.cost_basis, 200.0);
+    }
+}
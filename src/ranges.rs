@@ -0,0 +1,197 @@
+use rusqlite::{params, Connection};
+
+use crate::config::{self, PairConfig};
+use crate::{get_user_input, ledger, prompt_f64, split_symbol, volatility, Candle};
+
+/// Metoda wyznaczania zakresu gridu z historii cen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeMethod {
+    /// Ostatnie zamknięcie ± k × ATR × √n (oczekiwany ruch w horyzoncie historii)
+    Atr,
+    /// Średnia zamknięć ± k × odchylenie standardowe
+    Bollinger,
+    /// Percentyle zamknięć: p .. 100 - p
+    Percentile,
+}
+
+impl RangeMethod {
+    pub const NAMES: [&'static str; 3] = ["atr", "bollinger", "percentile"];
+
+    pub fn parse(name: &str) -> Option<RangeMethod> {
+        match name {
+            "atr" => Some(RangeMethod::Atr),
+            "bollinger" => Some(RangeMethod::Bollinger),
+            "percentile" => Some(RangeMethod::Percentile),
+            _ => None,
+        }
+    }
+}
+
+/// Proponowany zakres (min, max); `k` dla ATR / Bollingera, `percentile` dla metody percentylowej
+pub fn suggest(candles: &[Candle], method: RangeMethod, k: f64, percentile: f64) -> Option<(f64, f64)> {
+    let last = candles.last()?.close;
+    let n = candles.len() as f64;
+    let (min, max) = match method {
+        RangeMethod::Atr => {
+            let atr = candles.windows(2).map(|w| volatility::true_range(&w[1], w[0].close)).sum::<f64>() / (n - 1.0).max(1.0);
+            let half_width = k * atr * n.sqrt();
+            (last - half_width, last + half_width)
+        }
+        RangeMethod::Bollinger => {
+            let mean = candles.iter().map(|c| c.close).sum::<f64>() / n;
+            let variance = candles.iter().map(|c| (c.close - mean).powi(2)).sum::<f64>() / n;
+            (mean - k * variance.sqrt(), mean + k * variance.sqrt())
+        }
+        RangeMethod::Percentile => {
+            let mut closes: Vec<f64> = candles.iter().map(|c| c.close).collect();
+            closes.sort_by(|a, b| a.total_cmp(b));
+            let at = |p: f64| closes[((p / 100.0 * (closes.len() - 1) as f64).round() as usize).min(closes.len() - 1)];
+            (at(percentile), at(100.0 - percentile))
+        }
+    };
+    (min > 0.0 && max > min).then_some((min, max))
+}
+
+/// Ceny poziomów od `min` do `max` co `step` pary
+pub fn grid_levels(pair: &PairConfig, min: f64, max: f64) -> Vec<f64> {
+    (0..)
+        .map(|k| pair.level_price(min, k))
+        .take_while(|&price| price <= max)
+        .collect()
+}
+
+/// Liczba przecięć poziomów przez cenę; świeca idzie open → low → high → close na wzrostowej
+/// i open → high → low → close na spadkowej, a od close do następnego open
+pub fn count_crossings(candles: &[Candle], levels: &[f64]) -> usize {
+    let mut path = Vec::with_capacity(candles.len() * 4);
+    for candle in candles {
+        let (first, second) = if candle.close >= candle.open { (candle.low, candle.high) } else { (candle.high, candle.low) };
+        path.extend([candle.open, first, second, candle.close]);
+    }
+    path.windows(2)
+        .map(|w| {
+            let (low, high) = if w[0] < w[1] { (w[0], w[1]) } else { (w[1], w[0]) };
+            levels.iter().filter(|&&level| level > low && level <= high).count()
+        })
+        .sum()
+}
+
+/// Wyświetla propozycję i opcjonalnie zapisuje zakres w tabeli `capital`
+pub fn present(db: &Connection, pair: &PairConfig, candles: &[Candle], interval_secs: u64, range: (f64, f64)) {
+    let (min, max) = range;
+    let (_, quote_asset) = split_symbol(&pair.symbol);
+    let last = candles.last().map_or(0.0, |c| c.close);
+    let levels = grid_levels(pair, min, max);
+    let below = levels.iter().filter(|&&level| level < last).count();
+    let days = candles.len() as f64 * interval_secs as f64 / 86_400.0;
+    // Pełny cykl (kupno + sprzedaż) to dwa przecięcia poziomu
+    let cycles_per_day = if days > 0.0 { count_crossings(candles, &levels) as f64 / 2.0 / days } else { 0.0 };
+
+    println!("\n📐 **Suggested range for {}** (last price {:.4} {})", pair.symbol, last, quote_asset);
+    println!("   Range: {:.4} - {:.4} ({:+.2}% / {:+.2}%)", min, max, (min / last - 1.0) * 100.0, (max / last - 1.0) * 100.0);
+    println!(
+        "   Levels at step {:.2}%: {} ({} below the price, {} above) – levels_below = {}",
+        pair.step * 100.0, levels.len(), below, levels.len() - below, below
+    );
+    println!("   Expected cycles per day: {:.2} (from {:.1} days of history)", cycles_per_day, days);
+
+    let answer = get_user_input(&format!("Save this range for {} in the capital table? (y/N):", pair.symbol));
+    if !answer.eq_ignore_ascii_case("y") {
+        return;
+    }
+    let updated = db.execute(
+        "UPDATE capital SET min_price = ?2, max_price = ?3 WHERE symbol = ?1",
+        params![pair.symbol, min, max],
+    ).expect("Failed to update price range");
+    if updated == 0 {
+        let amount = prompt_f64("Enter capital allocation for this pair:");
+        db.execute(
+            "INSERT INTO capital (symbol, amount, min_price, max_price) VALUES (?1, ?2, ?3, ?4)",
+            params![pair.symbol, amount, min, max],
+        ).expect("Failed to set capital allocation for pair");
+        ledger::allocate(db, &pair.symbol, amount);
+    }
+    println!("✅ Price range for {} set to: {:.4} - {:.4}", pair.symbol, min, max);
+
+    // `levels_below` zapisywane przy parze (ma pierwszeństwo przed config.toml), o ile przechodzi walidację
+    let adjusted = PairConfig { levels_below: below as u32, ..pair.clone() };
+    let index = config::config().pairs.iter().position(|p| p.symbol == pair.symbol).unwrap_or(0);
+    match adjusted.validate(index) {
+        Ok(()) => {
+            db.execute("UPDATE capital SET levels_below = ?2 WHERE symbol = ?1", params![pair.symbol, below as u32])
+                .expect("Failed to set levels for pair");
+            println!("✅ levels_below for {} set to: {}", pair.symbol, below);
+        }
+        Err(e) => println!(
+            "⚠️ levels_below = {} not saved ({}); keeping {} – pick a narrower range or adjust `step` / `order_size` in config.toml",
+            below, e, pair.levels_below
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candle(open: f64, high: f64, low: f64, close: f64) -> Candle {
+        Candle { open_time: 0, open, high, low, close }
+    }
+
+    fn closes(values: &[f64]) -> Vec<Candle> {
+        values.iter().map(|&close| candle(close, close, close, close)).collect()
+    }
+
+    #[test]
+    fn percentile_range_uses_sorted_closes() {
+        let candles = closes(&[5.0, 1.0, 3.0, 2.0, 4.0]);
+        assert_eq!(suggest(&candles, RangeMethod::Percentile, 0.0, 25.0), Some((2.0, 4.0)));
+        assert_eq!(suggest(&candles, RangeMethod::Percentile, 0.0, 0.0), Some((1.0, 5.0)));
+    }
+
+    #[test]
+    fn bollinger_range_is_mean_plus_minus_k_sigma() {
+        let candles = closes(&[90.0, 110.0, 90.0, 110.0]);
+        let (min, max) = suggest(&candles, RangeMethod::Bollinger, 2.0, 0.0).unwrap();
+        assert!((min - 80.0).abs() < 1e-9);
+        assert!((max - 120.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn atr_range_is_centered_on_the_last_close() {
+        let candles: Vec<Candle> = (0..4).map(|_| candle(100.0, 101.0, 99.0, 100.0)).collect();
+        let (min, max) = suggest(&candles, RangeMethod::Atr, 1.0, 0.0).unwrap();
+        assert!((min - 96.0).abs() < 1e-9);
+        assert!((max - 104.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn empty_or_degenerate_history_gives_no_range() {
+        assert!(suggest(&[], RangeMethod::Atr, 1.0, 0.0).is_none());
+        assert!(suggest(&closes(&[100.0, 100.0]), RangeMethod::Bollinger, 2.0, 0.0).is_none());
+        assert!(suggest(&closes(&[1.0, 100.0]), RangeMethod::Bollinger, 3.0, 0.0).is_none());
+    }
+
+    #[test]
+    fn crossings_follow_the_candle_path() {
+        // Wzrostowa: 100 -> 94 -> 106 -> 104 przecina 95 dwa razy i 105 dwa razy
+        let green = [candle(100.0, 106.0, 94.0, 104.0)];
+        assert_eq!(count_crossings(&green, &[95.0, 105.0]), 4);
+        // Spadkowa: 100 -> 103 -> 97 -> 98 przecina tylko 99 (raz w dół)
+        let red = [candle(100.0, 103.0, 97.0, 98.0)];
+        assert_eq!(count_crossings(&red, &[99.0, 110.0]), 1);
+    }
+
+    #[test]
+    fn crossings_include_gaps_between_candles() {
+        let candles = [candle(100.0, 100.0, 100.0, 100.0), candle(110.0, 110.0, 110.0, 110.0)];
+        assert_eq!(count_crossings(&candles, &[105.0]), 1);
+    }
+
+    #[test]
+    fn grid_levels_cover_the_range_by_step() {
+        let pair = PairConfig { step: 0.1, ..PairConfig::default_for("BTCUSDT") };
+        let levels = grid_levels(&pair, 100.0, 130.0);
+        assert_eq!(levels.len(), 4);
+        assert!((levels[3] - 130.0).abs() < 1e-9);
+    }
+}
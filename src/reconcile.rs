@@ -0,0 +1,378 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, IsTerminal};
+use reqwest::Client;
+use rusqlite::{params, Connection};
+use serde_json::Value;
+
+use crate::{get_user_input, is_recorded_fill, ledger, portfolio, send_signed_request, split_symbol, sync};
+
+/// Różnice między lokalną bazą a stanem konta na Binance
+#[derive(Debug, Default)]
+struct ReconcileReport {
+    /// Otwarte na giełdzie, brak w `orders`
+    missing_orders: Vec<Value>,
+    /// Otwarte na giełdzie, ale nie złożone przez bota
+    orphan_orders: Vec<(u64, String, String, f64, f64)>,
+    /// Otwarte lokalnie, zamknięte na giełdzie: (order_id, symbol, status z giełdy)
+    stale_orders: Vec<(u64, String, String)>,
+    /// Liczba transakcji z `myTrades` brakujących w `trades`
+    missing_fills: Vec<(String, usize)>,
+    /// (asset, saldo na giełdzie, zapas wg bazy)
+    balance_diffs: Vec<(String, f64, f64)>,
+    /// (symbol, is_active w bazie, oczekiwany is_active)
+    activity_fixes: Vec<(String, i32, i32)>,
+}
+
+impl ReconcileReport {
+    fn is_empty(&self) -> bool {
+        self.missing_orders.is_empty()
+            && self.orphan_orders.is_empty()
+            && self.stale_orders.is_empty()
+            && self.missing_fills.is_empty()
+            && self.balance_diffs.is_empty()
+            && self.activity_fixes.is_empty()
+    }
+
+    fn print(&self) {
+        println!("\n🧾 **Reconciliation diff (DB vs Binance):**");
+        for order in &self.missing_orders {
+            println!(
+                "  + order {} {} {} {} @ {} missing in DB",
+                order["orderId"], order["symbol"].as_str().unwrap_or(""), order["side"].as_str().unwrap_or(""),
+                order["origQty"].as_str().unwrap_or("0"), order["price"].as_str().unwrap_or("0")
+            );
+        }
+        for (order_id, symbol, side, price, quantity) in &self.orphan_orders {
+            println!("  ⚠️ orphan exchange order {} {} {} {:.6} @ {:.4} (not placed by the bot, no longer reported once fixes are applied)", order_id, symbol, side, quantity, price);
+        }
+        for (order_id, symbol, status) in &self.stale_orders {
+            println!("  ~ order {} {} open in DB, {} on Binance", order_id, symbol, status);
+        }
+        for (symbol, count) in &self.missing_fills {
+            let suffix = if *count >= 1000 { "+" } else { "" };
+            println!("  + {}{} fills for {} missing in DB", count, suffix, symbol);
+        }
+        for (asset, exchange, local) in &self.balance_diffs {
+            println!("  ⚠️ {} balance on Binance {:.8} < held by grids {:.8}", asset, exchange, local);
+        }
+        for (symbol, current, expected) in &self.activity_fixes {
+            println!("  ~ capital.is_active for {}: {} -> {}", symbol, current, expected);
+        }
+    }
+}
+
+async fn fetch_open_orders(client: &Client, api_key: &str, secret_key: &str) -> Result<Vec<Value>, String> {
+    let orders = send_signed_request(client, reqwest::Method::GET, "/api/v3/openOrders", "", api_key, secret_key).await?;
+    Ok(orders.as_array().cloned().unwrap_or_default())
+}
+
+/// Pobiera łączne saldo (free + locked) wszystkich aktywów
+pub async fn fetch_balances(client: &Client, api_key: &str, secret_key: &str) -> Result<HashMap<String, f64>, String> {
+    let account = send_signed_request(client, reqwest::Method::GET, "/api/v3/account", "", api_key, secret_key).await?;
+    let parse = |v: &Value| v.as_str().unwrap_or("0.0").parse::<f64>().unwrap_or(0.0);
+    Ok(account["balances"].as_array()
+        .map(|list| list.iter()
+            .map(|b| (b["asset"].as_str().unwrap_or("").to_string(), parse(&b["free"]) + parse(&b["locked"])))
+            .collect())
+        .unwrap_or_default())
+}
+
+/// Zlecenia w bazie: order_id -> (źródło, czy użytkownik już przyjął zlecenie do wiadomości)
+fn local_orders(db: &Connection) -> HashMap<u64, (String, bool)> {
+    let mut stmt = db.prepare("SELECT order_id, COALESCE(source, 'exchange'), acknowledged FROM orders")
+        .expect("Failed to prepare statement");
+    stmt.query_map([], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))
+        .expect("Failed to query orders")
+        .filter_map(Result::ok)
+        .collect()
+}
+
+/// Zlecenie z giełdy, którego nie złożył bot i którego użytkownik jeszcze nie potwierdził
+fn is_orphan(local_orders: &HashMap<u64, (String, bool)>, order_id: u64) -> bool {
+    !matches!(local_orders.get(&order_id), Some((source, acknowledged)) if source == "bot" || *acknowledged)
+}
+
+/// Zapisuje otwarte zlecenie z giełdy nieznane bazie (źródło `exchange`)
+fn insert_missing_order(db: &Connection, order: &Value) {
+    let parse = |key: &str| order[key].as_str().unwrap_or("0.0").parse::<f64>().unwrap_or(0.0);
+    db.execute(
+        "INSERT OR IGNORE INTO orders (order_id, symbol, price, stop_price, quantity, type, status, timestamp, side, source)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, datetime(?8 / 1000, 'unixepoch'), ?9, 'exchange')",
+        params![
+            order["orderId"].as_u64().unwrap_or(0),
+            order["symbol"].as_str().unwrap_or(""),
+            parse("price"),
+            parse("stopPrice"),
+            parse("origQty"),
+            order["type"].as_str().unwrap_or("UNKNOWN"),
+            order["status"].as_str().unwrap_or("UNKNOWN"),
+            order["time"].as_u64().unwrap_or(0),
+            order["side"].as_str().unwrap_or("UNKNOWN"),
+        ],
+    ).expect("Failed to insert missing order");
+}
+
+async fn build_report(db: &Connection, client: &Client, api_key: &str, secret_key: &str) -> Result<ReconcileReport, String> {
+    let mut report = ReconcileReport::default();
+
+    let symbols: Vec<(String, i32)> = {
+        let mut stmt = db.prepare("SELECT symbol, MAX(is_active) FROM capital GROUP BY symbol ORDER BY symbol ASC")
+            .expect("Failed to prepare statement");
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .expect("Failed to query capital pairs")
+            .filter_map(Result::ok)
+            .collect()
+    };
+
+    // 📌 Zlecenia otwarte na giełdzie vs lokalnie
+    let exchange_orders = fetch_open_orders(client, api_key, secret_key).await?;
+    let exchange_ids: HashSet<u64> = exchange_orders.iter().filter_map(|o| o["orderId"].as_u64()).collect();
+
+    let local_orders = local_orders(db);
+    for order in &exchange_orders {
+        let order_id = order["orderId"].as_u64().unwrap_or(0);
+        if !local_orders.contains_key(&order_id) {
+            report.missing_orders.push(order.clone());
+        }
+        if is_orphan(&local_orders, order_id) {
+            let parse = |key: &str| order[key].as_str().unwrap_or("0.0").parse::<f64>().unwrap_or(0.0);
+            report.orphan_orders.push((
+                order_id,
+                order["symbol"].as_str().unwrap_or("").to_string(),
+                order["side"].as_str().unwrap_or("").to_string(),
+                parse("price"),
+                parse("origQty"),
+            ));
+        }
+    }
+
+    let stale: Vec<(u64, String)> = {
+        let mut stmt = db.prepare("SELECT order_id, symbol FROM orders WHERE status IN ('NEW', 'PARTIALLY_FILLED')")
+            .expect("Failed to prepare statement");
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .expect("Failed to query orders")
+            .filter_map(Result::ok)
+            .filter(|(order_id, _)| !exchange_ids.contains(order_id))
+            .collect()
+    };
+    for (order_id, symbol) in stale {
+        let params = format!("symbol={}&orderId={}", symbol, order_id);
+        let status = match send_signed_request(client, reqwest::Method::GET, "/api/v3/order", &params, api_key, secret_key).await {
+            Ok(order) => order["status"].as_str().unwrap_or("UNKNOWN").to_string(),
+            Err(_) => "UNKNOWN".to_string(),
+        };
+        report.stale_orders.push((order_id, symbol, status));
+    }
+
+    // 📌 Wykonania brakujące w lokalnej kopii `myTrades`
+    for (symbol, _) in &symbols {
+        let from_id: i64 = db.query_row(
+            "SELECT COALESCE(MAX(trade_id), -1) + 1 FROM trades WHERE symbol = ?1",
+            params![symbol],
+            |row| row.get(0),
+        ).unwrap_or(0);
+        let params = format!("symbol={}&fromId={}&limit=1000", symbol, from_id);
+        let trades = send_signed_request(client, reqwest::Method::GET, "/api/v3/myTrades", &params, api_key, secret_key).await?;
+        // Wiersze sprzed kolumny `trade_id` nie przesuwają kursora – pomijamy wykonania, które już odpowiadają
+        let count = trades.as_array()
+            .map(|list| list.iter().filter(|trade| !is_recorded_fill(db, symbol, trade)).count())
+            .unwrap_or(0);
+        if count > 0 {
+            report.missing_fills.push((symbol.clone(), count));
+        }
+    }
+
+    // 📌 Salda: giełda musi pokrywać zapas trzymany przez gridy
+    let balances = fetch_balances(client, api_key, secret_key).await?;
+    let mut held: HashMap<String, f64> = HashMap::new();
+    for (symbol, _) in &symbols {
+        let (base_asset, _) = split_symbol(symbol);
+        *held.entry(base_asset).or_insert(0.0) += portfolio::load_position(db, symbol).held_qty;
+    }
+    for (asset, local) in held {
+        let exchange = balances.get(&asset).copied().unwrap_or(0.0);
+        if exchange + 1e-8 < local {
+            report.balance_diffs.push((asset, exchange, local));
+        }
+    }
+
+    // 📌 Flaga is_active zgodna z otwartymi zleceniami na giełdzie (wykonania sprzed restartu czekają na strategię)
+    for (symbol, is_active) in &symbols {
+        let has_open = exchange_orders.iter().any(|o| o["symbol"] == symbol.as_str())
+            || report.stale_orders.iter().any(|(_, s, status)| s == symbol && status == "FILLED");
+        if *is_active == 1 && !has_open && portfolio::load_position(db, symbol).held_qty <= 0.0 {
+            report.activity_fixes.push((symbol.clone(), *is_active, 0));
+        } else if *is_active != 1 && has_open {
+            report.activity_fixes.push((symbol.clone(), *is_active, 1));
+        }
+    }
+
+    Ok(report)
+}
+
+async fn apply_fixes(db: &mut Connection, client: &Client, api_key: &str, secret_key: &str, report: &ReconcileReport) {
+    for order in &report.missing_orders {
+        insert_missing_order(db, order);
+    }
+
+    // Zlecenia spoza bota zostają na giełdzie – po potwierdzeniu nie są już zgłaszane
+    for (order_id, _, _, _, _) in &report.orphan_orders {
+        db.execute("UPDATE orders SET acknowledged = 1 WHERE order_id = ?1", params![order_id])
+            .expect("Failed to acknowledge orphan order");
+    }
+
+    // Najpierw wykonania (zużywają rezerwacje), potem zamknięcie nieaktualnych zleceń
+    for (symbol, _) in &report.missing_fills {
+        match sync::sync_symbol_trades(db, client, api_key, secret_key, symbol).await {
+            Ok(count) => println!("🔄 Imported {} fills for {}", count, symbol),
+            Err(e) => println!("❌ Failed to import fills for {}: {}", symbol, e),
+        }
+    }
+
+    for (order_id, symbol, status) in &report.stale_orders {
+        close_stale_order(db, *order_id, symbol, status);
+    }
+
+    for (symbol, _, expected) in &report.activity_fixes {
+        db.execute("UPDATE capital SET is_active = ?1 WHERE symbol = ?2", params![expected, symbol])
+            .expect("Failed to update trading bot status");
+    }
+
+    println!("✅ Reconciliation fixes applied.");
+}
+
+/// Aktualizuje status zlecenia zamkniętego na giełdzie i zwalnia jego rezerwację.
+/// Wykonane zlecenie bota zostaje otwarte lokalnie – po imporcie wykonań zamknie je
+/// `sync::close_filled_orders`, a runner przekaże je strategii (`on_fill`), jak przy pracy bota
+fn close_stale_order(db: &Connection, order_id: u64, symbol: &str, status: &str) {
+    let from_bot: bool = db.query_row(
+        "SELECT source = 'bot' FROM orders WHERE order_id = ?1",
+        params![order_id],
+        |row| row.get(0),
+    ).unwrap_or(false);
+    if status == "UNKNOWN" || (status == "FILLED" && from_bot) {
+        return;
+    }
+    db.execute("UPDATE orders SET status = ?1 WHERE order_id = ?2", params![status, order_id])
+        .expect("Failed to update order status");
+    if status != "NEW" && status != "PARTIALLY_FILLED" {
+        ledger::release_order(db, symbol, order_id);
+    }
+}
+
+/// Porównuje bazę z Binance, wypisuje różnice i (po potwierdzeniu lub z `assume_yes`) je naprawia.
+/// Bez terminala i bez `assume_yes` tylko wypisuje raport.
+pub async fn reconcile(db: &mut Connection, api_key: &str, secret_key: &str, assume_yes: bool) {
+    let client = Client::new();
+    println!("🔍 Reconciling local DB with Binance...");
+
+    let report = match build_report(db, &client, api_key, secret_key).await {
+        Ok(report) => report,
+        Err(e) => {
+            println!("❌ Reconciliation failed: {}", e);
+            return;
+        }
+    };
+
+    if report.is_empty() {
+        println!("✅ Local DB is in sync with Binance.");
+        return;
+    }
+
+    report.print();
+
+    if !assume_yes && !io::stdin().is_terminal() {
+        println!("ℹ️ Non-interactive session: no changes applied (run with --yes to apply them).");
+        return;
+    }
+    if assume_yes || get_user_input("Apply these fixes? (y/n):").eq_ignore_ascii_case("y") {
+        apply_fixes(db, &client, api_key, secret_key, &report).await;
+    } else {
+        println!("ℹ️ No changes applied.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config, record_fill, Fill};
+
+    const SYMBOL: &str = "BTCUSDT";
+
+    fn test_db() -> Connection {
+        config::init_for_tests();
+        let db = Connection::open_in_memory().unwrap();
+        crate::create_schema(&db);
+        ledger::allocate(&db, SYMBOL, 1000.0);
+        db
+    }
+
+    fn insert_buy(db: &Connection, order_id: u64, source: &str) {
+        db.execute(
+            "INSERT INTO orders (order_id, symbol, price, stop_price, quantity, type, status, timestamp, side, source, level)
+             VALUES (?1, ?2, 100.0, 0.0, 1.0, 'LIMIT', 'NEW', datetime('now'), 'BUY', ?3, 1)",
+            params![order_id, SYMBOL, source],
+        ).unwrap();
+        ledger::reserve_for_buy(db, SYMBOL, order_id, 100.0);
+    }
+
+    fn status(db: &Connection, order_id: u64) -> String {
+        db.query_row("SELECT status FROM orders WHERE order_id = ?1", params![order_id], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn bot_order_filled_while_offline_is_handed_to_the_strategy() {
+        let db = test_db();
+        insert_buy(&db, 1, "bot");
+        let fill = Fill { trade_id: 10, price: 100.0, qty: 1.0, commission: 0.0, commission_asset: "USDT".to_string(), time: 1_700_000_000_000 };
+        record_fill(&db, SYMBOL, "BUY", 1, &fill);
+
+        close_stale_order(&db, 1, SYMBOL, "FILLED");
+        assert_eq!(status(&db, 1), "NEW");
+
+        let filled = sync::close_filled_orders(&db, SYMBOL);
+        assert_eq!(filled.len(), 1);
+        assert_eq!((filled[0].0, filled[0].1.as_str()), (1, "BUY"));
+        assert_eq!(status(&db, 1), "FILLED");
+        assert!(ledger::balances(&db, SYMBOL).reserved.abs() < 1e-9);
+    }
+
+    #[test]
+    fn canceled_and_foreign_orders_are_closed_and_released() {
+        let db = test_db();
+        insert_buy(&db, 1, "bot");
+        insert_buy(&db, 2, "exchange");
+
+        close_stale_order(&db, 1, SYMBOL, "CANCELED");
+        close_stale_order(&db, 2, SYMBOL, "FILLED");
+        assert_eq!(status(&db, 1), "CANCELED");
+        assert_eq!(status(&db, 2), "FILLED");
+        assert!(ledger::balances(&db, SYMBOL).reserved.abs() < 1e-9);
+    }
+
+    #[test]
+    fn imported_exchange_order_is_an_orphan_until_acknowledged() {
+        let db = test_db();
+        insert_buy(&db, 1, "bot");
+        insert_missing_order(&db, &serde_json::json!({
+            "orderId": 2, "symbol": SYMBOL, "price": "90.0", "origQty": "1.0",
+            "type": "LIMIT", "side": "BUY", "status": "NEW", "time": 1_700_000_000_000u64
+        }));
+        let local = local_orders(&db);
+        assert!(!is_orphan(&local, 1));
+        assert!(is_orphan(&local, 2));
+        assert!(is_orphan(&local, 3));
+
+        db.execute("UPDATE orders SET acknowledged = 1 WHERE order_id = 2", []).unwrap();
+        assert!(!is_orphan(&local_orders(&db), 2));
+    }
+
+    #[test]
+    fn unknown_status_leaves_the_order_open() {
+        let db = test_db();
+        insert_buy(&db, 1, "bot");
+        close_stale_order(&db, 1, SYMBOL, "UNKNOWN");
+        assert_eq!(status(&db, 1), "NEW");
+        assert!((ledger::balances(&db, SYMBOL).reserved - 100.0).abs() < 1e-9);
+    }
+}
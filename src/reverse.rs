@@ -0,0 +1,127 @@
+use crate::strategy::{FillEvent, OrderIntent, OrderSpec, Strategy, StrategyContext};
+
+/// Reverse grid: startuje z posiadanego base – sprzedaje go powyżej ceny i odkupuje niżej
+/// za cały przychód, więc zysk zostaje w base (księgowany w `capital.base_profit`)
+pub struct ReverseGrid;
+
+impl Strategy for ReverseGrid {
+    /// `levels_above` sprzedaży po `order_size` przydziału base powyżej ceny
+    fn on_start(&mut self, ctx: &StrategyContext, price: f64) -> Vec<OrderIntent> {
+        let slice = ctx.base_amount * ctx.pair.order_size;
+        if slice <= 0.0 {
+            return Vec::new();
+        }
+        (1..=ctx.pair.levels_above as i32)
+            .map(|level| OrderIntent::Place(OrderSpec::new("SELL", ctx.pair.level_price(price, level), slice)))
+            .collect()
+    }
+
+    /// Sprzedaż → odkup poziom niżej za cały przychód; odkup → ponowna sprzedaż sprzedanej ilości poziom wyżej
+    fn on_fill(&mut self, ctx: &StrategyContext, fill: &FillEvent) -> Vec<OrderIntent> {
+        if fill.side == "SELL" {
+            let buy_price = ctx.pair.level_price(fill.price, -1);
+            let mut buy = OrderSpec::new("BUY", buy_price, fill.net_quote / buy_price);
+            buy.covers_qty = Some(fill.quantity);
+            return vec![OrderIntent::Place(buy)];
+        }
+        match fill.covers_qty {
+            Some(covers) => {
+                let sell_price = ctx.pair.level_price(fill.price, 1);
+                vec![OrderIntent::Place(OrderSpec::new("SELL", sell_price, covers))]
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{PairConfig, SpacingType};
+
+    fn pair() -> PairConfig {
+        PairConfig {
+            spacing: SpacingType::Arithmetic,
+            step: 0.1,
+            levels_above: 2,
+            order_size: 0.25,
+            ..PairConfig::default_for("BTCUSDT")
+        }
+    }
+
+    fn context(pair: &PairConfig, base_amount: f64) -> StrategyContext<'_> {
+        StrategyContext {
+            pair,
+            capital: 0.0,
+            base_amount,
+            min_price: 0.0,
+            held: base_amount,
+            avg_entry: 0.0,
+            last_buy: None,
+            now: 0,
+            open_orders: Vec::new(),
+        }
+    }
+
+    fn fill(side: &str, price: f64, quantity: f64, net_quote: f64, covers_qty: Option<f64>) -> FillEvent {
+        FillEvent {
+            order_id: 1, side: side.to_string(), price, quantity,
+            net_base: 0.0, net_quote, level: None, covers_qty,
+        }
+    }
+
+    fn only_order(intents: Vec<OrderIntent>) -> OrderSpec {
+        assert_eq!(intents.len(), 1);
+        match intents.into_iter().next() {
+            Some(OrderIntent::Place(spec)) => spec,
+            other => panic!("expected a placement, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn start_sells_slices_of_the_base_allocation_above_price() {
+        let pair = pair();
+        let intents = ReverseGrid.on_start(&context(&pair, 2.0), 100.0);
+
+        let sells: Vec<(f64, f64)> = intents.iter().map(|intent| match intent {
+            OrderIntent::Place(spec) if spec.side == "SELL" => (spec.price, spec.quantity),
+            other => panic!("unexpected intent {:?}", other),
+        }).collect();
+        assert_eq!(sells.len(), 2);
+        assert!((sells[0].0 - 110.0).abs() < 1e-9 && (sells[0].1 - 0.5).abs() < 1e-12);
+        assert!((sells[1].0 - 120.0).abs() < 1e-9 && (sells[1].1 - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn start_without_base_places_nothing() {
+        let pair = pair();
+        assert!(ReverseGrid.on_start(&context(&pair, 0.0), 100.0).is_empty());
+    }
+
+    #[test]
+    fn sell_fill_buys_back_with_all_proceeds_one_level_lower() {
+        let pair = pair();
+        let buy = only_order(ReverseGrid.on_fill(&context(&pair, 2.0), &fill("SELL", 110.0, 0.5, 54.945, None)));
+
+        assert_eq!(buy.side, "BUY");
+        assert!((buy.price - 99.0).abs() < 1e-9);
+        assert!((buy.quantity - 54.945 / 99.0).abs() < 1e-12);
+        assert_eq!(buy.covers_qty, Some(0.5));
+    }
+
+    #[test]
+    fn buy_back_fill_resells_only_the_covered_quantity() {
+        let pair = pair();
+        let sell = only_order(ReverseGrid.on_fill(&context(&pair, 2.0), &fill("BUY", 99.0, 0.555, 0.0, Some(0.5))));
+
+        assert_eq!(sell.side, "SELL");
+        assert!((sell.price - 108.9).abs() < 1e-9);
+        assert_eq!(sell.quantity, 0.5);
+    }
+
+    #[test]
+    fn buy_without_covered_quantity_is_ignored() {
+        let pair = pair();
+        assert!(ReverseGrid.on_fill(&context(&pair, 2.0), &fill("BUY", 99.0, 0.5, 0.0, None)).is_empty());
+    }
+}
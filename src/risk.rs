@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use reqwest::Client;
+use rusqlite::Connection;
+
+use crate::{breaker, config, credentials, ledger, portfolio, send_signed_request, volatility};
+
+/// Zlecenie zgłoszone do sprawdzenia przed wysłaniem na giełdę
+#[derive(Debug, Clone)]
+pub struct OrderRequest<'a> {
+    pub symbol: &'a str,
+    pub side: &'a str,
+    pub price: f64,
+    pub quantity: f64,
+    /// Aktualna cena rynkowa pary
+    pub market_price: f64,
+    /// Poziom składany po cenie rynkowej (np. startowe kupno) – bez wymogu `min_price_distance`
+    pub at_market: bool,
+    /// Otwarte zlecenia na giełdzie: (para, łącznie)
+    pub open_orders: (usize, usize),
+}
+
+impl OrderRequest<'_> {
+    fn notional(&self) -> f64 {
+        self.price * self.quantity
+    }
+
+    /// Kupno po cenie >= rynkowej lub sprzedaż <= rynkowej wykona się od razu (nie czeka w księdze)
+    fn is_marketable(&self) -> bool {
+        if self.side == "BUY" {
+            self.price >= self.market_price
+        } else {
+            self.price <= self.market_price
+        }
+    }
+}
+
+/// Otwarte zlecenia wg bazy (gdy giełda nie odpowiada)
+fn local_open_orders(db: &Connection) -> HashMap<String, usize> {
+    let mut stmt = db.prepare(
+        "SELECT symbol, COUNT(*) FROM orders WHERE status IN ('NEW', 'PARTIALLY_FILLED') GROUP BY symbol"
+    ).expect("Failed to prepare statement");
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as usize)))
+        .expect("Failed to query orders")
+        .filter_map(Result::ok)
+        .collect()
+}
+
+/// Jak długo liczniki z giełdy obsługują kolejne zlecenia serii (np. wszystkie poziomy startu gridu)
+const OPEN_ORDERS_TTL: Duration = Duration::from_secs(30);
+
+/// Liczniki otwartych zleceń pobrane z giełdy raz na serię; zlecenia złożone i anulowane
+/// w jej trakcie są doliczane lokalnie (`track_open_order`), bez kolejnych zapytań o wadze 40
+static OPEN_ORDERS: Mutex<Option<(Instant, HashMap<String, usize>)>> = Mutex::new(None);
+
+fn cached_open_orders() -> Option<HashMap<String, usize>> {
+    match OPEN_ORDERS.lock().expect("Open orders cache poisoned").as_ref() {
+        Some((fetched_at, counts)) if fetched_at.elapsed() < OPEN_ORDERS_TTL => Some(counts.clone()),
+        _ => None,
+    }
+}
+
+/// Uwzględnia w bieżącej serii zlecenie złożone (`delta` = 1) lub anulowane (`delta` = -1) przez bota
+pub fn track_open_order(symbol: &str, delta: isize) {
+    if let Some((_, counts)) = OPEN_ORDERS.lock().expect("Open orders cache poisoned").as_mut() {
+        let count = counts.entry(symbol.to_string()).or_insert(0);
+        *count = count.saturating_add_signed(delta);
+    }
+}
+
+/// Otwarte zlecenia per para wg `/api/v3/openOrders`; lokalne statusy mogą być nieaktualne
+/// (zlecenia wykonane lub anulowane poza botem), więc limity liczone są z giełdy – raz na serię zleceń
+pub async fn open_orders(db: &mut Connection, client: &Client) -> HashMap<String, usize> {
+    if let Some(counts) = cached_open_orders() {
+        return counts;
+    }
+    let creds = credentials::credentials();
+    match send_signed_request(client, reqwest::Method::GET, "/api/v3/openOrders", "", &creds.api_key, &creds.secret_key).await {
+        Ok(orders) => {
+            let mut counts = HashMap::new();
+            for order in orders.as_array().into_iter().flatten() {
+                *counts.entry(order["symbol"].as_str().unwrap_or("").to_string()).or_insert(0) += 1;
+            }
+            *OPEN_ORDERS.lock().expect("Open orders cache poisoned") = Some((Instant::now(), counts.clone()));
+            counts
+        }
+        Err(e) => {
+            println!("⚠️ Cannot fetch open orders from Binance ({}), using local order statuses", e);
+            local_open_orders(db)
+        }
+    }
+}
+
+/// Liczniki do `OrderRequest::open_orders`: (para, łącznie)
+pub fn open_order_counts(counts: &HashMap<String, usize>, symbol: &str) -> (usize, usize) {
+    (counts.get(symbol).copied().unwrap_or(0), counts.values().sum())
+}
+
+fn pair_symbols(db: &Connection) -> Vec<String> {
+    let mut stmt = db.prepare("SELECT DISTINCT symbol FROM capital ORDER BY symbol ASC")
+        .expect("Failed to prepare statement");
+    stmt.query_map([], |row| row.get(0))
+        .expect("Failed to query capital pairs")
+        .filter_map(Result::ok)
+        .collect()
+}
+
+/// Zapas pary po cenie rynkowej plus kapitał zarezerwowany pod otwarte kupna
+fn pair_exposure(db: &Connection, symbol: &str, market_price: f64) -> f64 {
+    portfolio::load_position(db, symbol).held_qty * market_price + ledger::balance(db, symbol, ledger::RESERVED)
+}
+
+/// Ekspozycja pozostałych par po koszcie z księgi (bez dodatkowych zapytań o ceny)
+fn other_pairs_exposure(db: &Connection, symbol: &str) -> f64 {
+    pair_symbols(db).iter()
+        .filter(|s| s.as_str() != symbol)
+        .map(|s| {
+            let balances = ledger::balances(db, s);
+            balances.inventory + balances.reserved
+        })
+        .sum()
+}
+
+/// Sprawdza limity ryzyka; `Err` zawiera powód odrzucenia zlecenia
+pub fn check_order(db: &Connection, order: &OrderRequest) -> Result<(), String> {
+    if let Some(reason) = breaker::tripped(db) {
+        return Err(format!("circuit breaker tripped ({}); run `rearm` to resume", reason));
+    }
+
+    let risk = &config::config().risk;
+    let notional = order.notional();
+
+    if notional > risk.max_order_notional {
+        return Err(format!(
+            "order value {:.2} exceeds max_order_notional {:.2}",
+            notional, risk.max_order_notional
+        ));
+    }
+
+    let (pair_open, total_open) = order.open_orders;
+    if pair_open >= risk.max_open_orders_per_pair {
+        return Err(format!(
+            "{} open orders for {} (max_open_orders_per_pair = {})",
+            pair_open, order.symbol, risk.max_open_orders_per_pair
+        ));
+    }
+
+    if total_open >= risk.max_open_orders {
+        return Err(format!("{} open orders in total (max_open_orders = {})", total_open, risk.max_open_orders));
+    }
+
+    if !order.at_market && !order.is_marketable() && order.market_price > 0.0 {
+        let distance = (order.price - order.market_price).abs() / order.market_price;
+        if distance < risk.min_price_distance {
+            return Err(format!(
+                "price {:.8} is {:.3}% from market {:.8} (min_price_distance = {:.3}%)",
+                order.price, distance * 100.0, order.market_price, risk.min_price_distance * 100.0
+            ));
+        }
+    }
+
+    // Sprzedaż zmniejsza ekspozycję – limity zapasu dotyczą tylko kupna
+    if order.side != "BUY" {
+        return Ok(());
+    }
+
+    if let Some((until, reason)) = volatility::buy_pause(db, order.symbol) {
+        return Err(format!("new buys for {} paused until {} UTC ({})", order.symbol, until, reason));
+    }
+
+    let pair_after = pair_exposure(db, order.symbol, order.market_price) + notional;
+    if let Some(limit) = risk.max_inventory_value_per_pair {
+        if pair_after > limit {
+            return Err(format!(
+                "inventory of {} would reach {:.2} (max_inventory_value_per_pair = {:.2})",
+                order.symbol, pair_after, limit
+            ));
+        }
+    }
+
+    if let Some(limit) = risk.max_total_exposure {
+        let total_after = pair_after + other_pairs_exposure(db, order.symbol);
+        if total_after > limit {
+            return Err(format!("total exposure would reach {:.2} (max_total_exposure = {:.2})", total_after, limit));
+        }
+    }
+
+    Ok(())
+}
+
+fn show_limit(limit: Option<f64>) -> String {
+    limit.map(|value| format!("{:.2}", value)).unwrap_or_else(|| "none".to_string())
+}
+
+/// Wykorzystanie limitów ryzyka (liczniki zleceń i ekspozycja po koszcie z księgi)
+pub async fn show_status(db: &mut Connection, client: &Client) {
+    let risk = &config::config().risk;
+    let open = open_orders(db, client).await;
+
+    println!("\n🛡️ **Risk limits:**");
+    breaker::show_status(db);
+    println!(
+        "Open orders: {} / {} | max order value: {:.2} | min distance from price: {:.3}%",
+        open.values().sum::<usize>(), risk.max_open_orders, risk.max_order_notional, risk.min_price_distance * 100.0
+    );
+
+    let mut total = 0.0;
+    for symbol in pair_symbols(db) {
+        let balances = ledger::balances(db, &symbol);
+        let exposure = balances.inventory + balances.reserved;
+        total += exposure;
+        println!(
+            "Pair: {} | Open orders: {} / {} | Exposure (cost): {:.2} / {}",
+            symbol, open.get(&symbol).copied().unwrap_or(0), risk.max_open_orders_per_pair,
+            exposure, show_limit(risk.max_inventory_value_per_pair)
+        );
+        if let Some((until, reason)) = volatility::buy_pause(db, &symbol) {
+            println!("   🌪️ New buys paused until {} UTC: {}", until, reason);
+        }
+    }
+    println!("Total exposure (cost): {:.2} / {}", total, show_limit(risk.max_total_exposure));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Connection {
+        config::init_for_tests();
+        let db = Connection::open_in_memory().unwrap();
+        crate::create_schema(&db);
+        db
+    }
+
+    fn buy(price: f64, quantity: f64) -> OrderRequest<'static> {
+        OrderRequest {
+            symbol: "BTCUSDT",
+            side: "BUY",
+            price,
+            quantity,
+            market_price: 100.0,
+            at_market: false,
+            open_orders: (0, 0),
+        }
+    }
+
+    #[test]
+    fn placed_and_canceled_orders_update_the_batch_counts() {
+        track_open_order("BTCUSDT", 1);
+        assert!(cached_open_orders().is_none());
+
+        *OPEN_ORDERS.lock().unwrap() = Some((Instant::now(), HashMap::from([("ETHUSDT".to_string(), 2)])));
+        track_open_order("BTCUSDT", 1);
+        track_open_order("BTCUSDT", 1);
+        track_open_order("ETHUSDT", -1);
+        let counts = cached_open_orders().unwrap();
+        assert_eq!(open_order_counts(&counts, "BTCUSDT"), (2, 3));
+
+        // Po upływie serii liczniki są pobierane z giełdy od nowa
+        *OPEN_ORDERS.lock().unwrap() = Some((Instant::now() - OPEN_ORDERS_TTL, counts));
+        assert!(cached_open_orders().is_none());
+    }
+
+    #[test]
+    fn accepts_order_within_limits() {
+        let db = test_db();
+        assert!(check_order(&db, &buy(95.0, 1.0)).is_ok());
+    }
+
+    #[test]
+    fn rejects_order_above_max_notional() {
+        let db = test_db();
+        let reason = check_order(&db, &OrderRequest { side: "SELL", ..buy(105.0, 10.0) }).unwrap_err();
+        assert!(reason.contains("max_order_notional"), "{}", reason);
+    }
+
+    #[test]
+    fn counts_open_orders_per_pair_and_in_total() {
+        let db = test_db();
+        let pair_full = check_order(&db, &OrderRequest { open_orders: (3, 3), ..buy(95.0, 1.0) }).unwrap_err();
+        assert!(pair_full.contains("max_open_orders_per_pair"), "{}", pair_full);
+        let total_full = check_order(&db, &OrderRequest { open_orders: (1, 5), ..buy(95.0, 1.0) }).unwrap_err();
+        assert!(total_full.contains("max_open_orders ="), "{}", total_full);
+    }
+
+    #[test]
+    fn min_price_distance_skips_at_market_and_marketable_orders() {
+        let db = test_db();
+        let reason = check_order(&db, &buy(99.5, 1.0)).unwrap_err();
+        assert!(reason.contains("min_price_distance"), "{}", reason);
+        assert!(check_order(&db, &OrderRequest { at_market: true, ..buy(99.5, 1.0) }).is_ok());
+        assert!(check_order(&db, &buy(100.5, 1.0)).is_ok());
+        assert!(check_order(&db, &buy(98.0, 1.0)).is_ok());
+    }
+
+    #[test]
+    fn inventory_limit_applies_to_buys_only() {
+        let db = test_db();
+        let reason = check_order(&db, &buy(90.0, 6.0)).unwrap_err();
+        assert!(reason.contains("max_inventory_value_per_pair"), "{}", reason);
+        assert!(check_order(&db, &OrderRequest { side: "SELL", ..buy(110.0, 6.0) }).is_ok());
+    }
+
+    #[test]
+    fn tripped_breaker_rejects_everything() {
+        let db = test_db();
+        db.execute("UPDATE circuit_breaker SET tripped = 1, reason = 'test' WHERE id = 1", []).unwrap();
+        let reason = check_order(&db, &OrderRequest { side: "SELL", ..buy(110.0, 1.0) }).unwrap_err();
+        assert!(reason.contains("circuit breaker"), "{}", reason);
+    }
+}
@@ -0,0 +1,476 @@
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use reqwest::Client;
+
+use crate::config::{PairConfig, ProfitMode};
+use crate::strategy::{self, FillEvent, OpenOrder, OrderIntent, Strategy, StrategyContext};
+use crate::{get_price, split_symbol, Candle};
+
+/// Zlecenie oczekujące na symulowanej giełdzie (z metadanymi strategii)
+struct SimOrder {
+    order: OpenOrder,
+    level: Option<i32>,
+    covers_qty: Option<f64>,
+}
+
+/// Symulowana giełda dla backtestu i paper tradingu – salda, blokady środków, prowizje
+pub struct SimExchange {
+    pair: PairConfig,
+    base_amount: f64,
+    min_price: f64,
+    fee_rate: f64,
+    pub quote: f64,
+    pub base: f64,
+    /// Koszt posiadanego base w quote (do średniej ceny wejścia)
+    cost: f64,
+    /// Zysk odłożony w trybie sweep – strategia go nie wydaje
+    pub harvested: f64,
+    last_buy: Option<(i64, f64)>,
+    now: i64,
+    orders: Vec<SimOrder>,
+    next_id: u64,
+    pub trades: usize,
+    pub rejected: usize,
+    pub fees_paid: f64,
+}
+
+impl SimExchange {
+    /// Reverse grid startuje z base kupionego za cały kapitał, pozostałe strategie z quote
+    pub fn new(pair: PairConfig, capital: f64, min_price: f64, fee_rate: f64, first_price: f64, now: i64) -> SimExchange {
+        let (quote, base) = if pair.strategy == crate::config::StrategyKind::Reverse {
+            (0.0, capital / first_price)
+        } else {
+            (capital, 0.0)
+        };
+        SimExchange {
+            pair,
+            base_amount: base,
+            min_price,
+            fee_rate,
+            quote,
+            base,
+            cost: capital - quote,
+            harvested: 0.0,
+            last_buy: None,
+            now,
+            orders: Vec::new(),
+            next_id: 1,
+            trades: 0,
+            rejected: 0,
+            fees_paid: 0.0,
+        }
+    }
+
+    pub fn equity(&self, price: f64) -> f64 {
+        self.quote + self.base * price + self.harvested
+    }
+
+    fn context(&self) -> StrategyContext<'_> {
+        StrategyContext {
+            pair: &self.pair,
+            // Jak na żywo: kapitał roboczy po koszcie (przydział + zysk pozostawiony w parze)
+            capital: self.quote + self.cost,
+            base_amount: self.base_amount,
+            min_price: self.min_price,
+            held: self.base,
+            avg_entry: if self.base > 0.0 { self.cost / self.base } else { 0.0 },
+            last_buy: self.last_buy,
+            now: self.now,
+            open_orders: self.orders.iter().map(|o| o.order.clone()).collect(),
+        }
+    }
+
+    /// Środki wolne od blokad otwartych zleceń: (quote, base)
+    fn available(&self) -> (f64, f64) {
+        self.orders.iter().fold((self.quote, self.base), |(quote, base), o| {
+            if o.order.side == "BUY" {
+                (quote - o.order.price * o.order.quantity, base)
+            } else {
+                (quote, base - o.order.quantity)
+            }
+        })
+    }
+
+    /// Rozlicza wykonanie po cenie `price` (prowizja w otrzymanym aktywie)
+    fn fill(&mut self, order_id: u64, side: &str, price: f64, quantity: f64, level: Option<i32>, covers_qty: Option<f64>) -> FillEvent {
+        let value = price * quantity;
+        let fee = value * self.fee_rate;
+        let (net_base, net_quote) = if side == "BUY" {
+            self.cost += value;
+            self.last_buy = Some((self.now, price));
+            self.quote -= value;
+            self.base += quantity * (1.0 - self.fee_rate);
+            (quantity * (1.0 - self.fee_rate), value)
+        } else {
+            let sold_cost = if self.base > 0.0 { self.cost * (quantity / self.base).min(1.0) } else { 0.0 };
+            self.cost -= sold_cost;
+            self.base -= quantity;
+            self.quote += value - fee;
+            if self.pair.profit_mode == ProfitMode::Sweep {
+                self.sweep(value - fee - sold_cost);
+            }
+            (quantity, value - fee)
+        };
+        self.trades += 1;
+        self.fees_paid += fee;
+        FillEvent { order_id, side: side.to_string(), price, quantity, net_base, net_quote, level, covers_qty }
+    }
+
+    /// Tryb sweep: zysk do `harvested`, strata pokrywana z tej puli (jak `ledger::sweep_profit`)
+    fn sweep(&mut self, profit: f64) {
+        let moved = if profit > 0.0 { profit } else { -(-profit).min(self.harvested) };
+        self.quote -= moved;
+        self.harvested += moved;
+    }
+
+    /// Wykonuje polecenia strategii; zlecenia po cenie rynkowej wykonują się od razu
+    /// i ich `on_fill` jest przetwarzane w tej samej kolejce. Zwraca wykonania.
+    pub fn apply(&mut self, strategy: &mut dyn Strategy, intents: Vec<OrderIntent>, market_price: f64) -> Vec<FillEvent> {
+        let mut queue: VecDeque<(OrderIntent, f64)> = intents.into_iter().map(|intent| (intent, market_price)).collect();
+        let mut fills = Vec::new();
+
+        while let Some((intent, market)) = queue.pop_front() {
+            let spec = match intent {
+                OrderIntent::Cancel(order_id) => {
+                    self.orders.retain(|o| o.order.order_id != order_id);
+                    continue;
+                }
+                OrderIntent::Place(spec) => spec,
+            };
+
+            let (free_quote, free_base) = self.available();
+            let funded = if spec.side == "BUY" { spec.price * spec.quantity <= free_quote } else { spec.quantity <= free_base };
+            if spec.price <= 0.0 || spec.quantity <= 0.0 || !funded {
+                self.rejected += 1;
+                continue;
+            }
+
+            let order_id = self.next_id;
+            self.next_id += 1;
+            if !spec.is_marketable(market) {
+                self.orders.push(SimOrder {
+                    order: OpenOrder { order_id, side: spec.side.to_string(), price: spec.price, quantity: spec.quantity },
+                    level: spec.level,
+                    covers_qty: spec.covers_qty,
+                });
+                continue;
+            }
+
+            let fill = self.fill(order_id, spec.side, market, spec.quantity, spec.level, spec.covers_qty);
+            for next in strategy.on_fill(&self.context(), &fill) {
+                queue.push_back((next, fill.price));
+            }
+            fills.push(fill);
+        }
+        fills
+    }
+
+    /// Wykonuje oczekujące zlecenia dotknięte przez świecę. Na świecy wzrostowej cena najpierw
+    /// spadała (najpierw kupna), na spadkowej najpierw rosła (najpierw sprzedaże).
+    pub fn on_candle(&mut self, strategy: &mut dyn Strategy, candle: &Candle) -> Vec<FillEvent> {
+        self.now = candle.open_time / 1000;
+        let mut buys: Vec<(u64, f64)> = self.orders.iter()
+            .filter(|o| o.order.side == "BUY" && o.order.price >= candle.low)
+            .map(|o| (o.order.order_id, o.order.price))
+            .collect();
+        buys.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let mut sells: Vec<(u64, f64)> = self.orders.iter()
+            .filter(|o| o.order.side == "SELL" && o.order.price <= candle.high)
+            .map(|o| (o.order.order_id, o.order.price))
+            .collect();
+        sells.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+        let touched: Vec<(u64, f64)> = if candle.close >= candle.open {
+            buys.into_iter().chain(sells).collect()
+        } else {
+            sells.into_iter().chain(buys).collect()
+        };
+
+        let mut fills = Vec::new();
+        for (order_id, _) in touched {
+            // Zlecenie mogło zostać anulowane przez strategię po wcześniejszym wykonaniu
+            let Some(index) = self.orders.iter().position(|o| o.order.order_id == order_id) else {
+                continue;
+            };
+            let resting = self.orders.remove(index);
+            let fill = self.fill(
+                order_id, &resting.order.side, resting.order.price, resting.order.quantity, resting.level, resting.covers_qty
+            );
+            let intents = strategy.on_fill(&self.context(), &fill);
+            let price = fill.price;
+            fills.push(fill);
+            fills.extend(self.apply(strategy, intents, price));
+        }
+
+        let intents = strategy.on_price(&self.context(), candle.close);
+        fills.extend(self.apply(strategy, intents, candle.close));
+        fills
+    }
+}
+
+/// Wynik backtestu strategii na historycznych świecach
+#[derive(Debug, Clone)]
+pub struct BacktestResult {
+    pub trades: usize,
+    pub rejected: usize,
+    pub fees_paid: f64,
+    pub start_equity: f64,
+    pub final_equity: f64,
+    pub return_pct: f64,
+    pub max_drawdown_pct: f64,
+    pub final_quote: f64,
+    pub final_base: f64,
+    /// Zysk odłożony w trybie sweep (wliczony w `final_equity`)
+    pub harvested: f64,
+}
+
+/// Backtest strategii `pair.strategy` na świecach (od najstarszej); equity w quote
+pub fn run_backtest(pair: &PairConfig, capital: f64, min_price: f64, candles: &[Candle], fee_rate: f64) -> Result<BacktestResult, String> {
+    let first = candles.first().ok_or("No candles to backtest on")?;
+    if first.open <= 0.0 {
+        return Err("Invalid first candle price".to_string());
+    }
+
+    let mut exchange = SimExchange::new(pair.clone(), capital, min_price, fee_rate, first.open, first.open_time / 1000);
+    let mut strategy = strategy::build(pair.strategy);
+    let start_equity = exchange.equity(first.open);
+
+    let intents = strategy.on_start(&exchange.context(), first.open);
+    exchange.apply(strategy.as_mut(), intents, first.open);
+
+    let (mut peak, mut max_drawdown_pct) = (start_equity, 0.0_f64);
+    for candle in candles {
+        exchange.on_candle(strategy.as_mut(), candle);
+        let equity = exchange.equity(candle.close);
+        peak = peak.max(equity);
+        if peak > 0.0 {
+            max_drawdown_pct = max_drawdown_pct.max((peak - equity) / peak * 100.0);
+        }
+    }
+
+    let last_price = candles.last().map_or(first.open, |c| c.close);
+    let final_equity = exchange.equity(last_price);
+    Ok(BacktestResult {
+        trades: exchange.trades,
+        rejected: exchange.rejected,
+        fees_paid: exchange.fees_paid,
+        start_equity,
+        final_equity,
+        return_pct: if start_equity > 0.0 { (final_equity / start_equity - 1.0) * 100.0 } else { 0.0 },
+        max_drawdown_pct,
+        final_quote: exchange.quote,
+        final_base: exchange.base,
+        harvested: exchange.harvested,
+    })
+}
+
+pub fn print_backtest(symbol: &str, result: &BacktestResult) {
+    let (base_asset, quote_asset) = split_symbol(symbol);
+    println!("\n📊 **Backtest {}**", symbol);
+    println!("   Trades: {} | Rejected orders: {} | Fees: {:.4} {}", result.trades, result.rejected, result.fees_paid, quote_asset);
+    println!("   Equity: {:.2} -> {:.2} {} ({:+.2}%)", result.start_equity, result.final_equity, quote_asset, result.return_pct);
+    println!("   Max drawdown: {:.2}%", result.max_drawdown_pct);
+    println!("   Final balances: {:.4} {} | {:.8} {}", result.final_quote, quote_asset, result.final_base, base_asset);
+    if result.harvested > 0.0 {
+        println!("   Harvested profit (sweep): {:.4} {}", result.harvested, quote_asset);
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64)
+}
+
+/// Paper trading: strategia na żywych cenach, zlecenia tylko na symulowanej giełdzie
+pub async fn run_paper(pair: PairConfig, capital: f64, min_price: f64, fee_rate: f64, every_secs: u64) {
+    let client = Client::new();
+    let symbol = pair.symbol.clone();
+    let (base_asset, quote_asset) = split_symbol(&symbol);
+
+    let mut last_price = match get_price(&symbol, &client).await {
+        Ok(price) if price > 0.0 => price,
+        _ => {
+            eprintln!("❌ Failed to fetch price for {}", symbol);
+            return;
+        }
+    };
+
+    let mut exchange = SimExchange::new(pair.clone(), capital, min_price, fee_rate, last_price, unix_now());
+    let mut strategy = strategy::build(pair.strategy);
+    println!("📝 Paper trading {} with {} strategy from {:.8} (Ctrl+C to stop)", symbol, pair.strategy.as_str(), last_price);
+
+    let intents = strategy.on_start(&exchange.context(), last_price);
+    let mut fills = exchange.apply(strategy.as_mut(), intents, last_price);
+
+    loop {
+        for fill in &fills {
+            println!("📥 [PAPER] {} {} {:.8} {} at {:.8}", symbol, fill.side, fill.quantity, base_asset, fill.price);
+        }
+        println!(
+            "💼 [PAPER] {} price {:.8} | equity {:.2} {} | {:.4} {} + {:.8} {} | trades {}",
+            symbol, last_price, exchange.equity(last_price), quote_asset,
+            exchange.quote, quote_asset, exchange.base, base_asset, exchange.trades
+        );
+
+        tokio::time::sleep(Duration::from_secs(every_secs)).await;
+        let price = match get_price(&symbol, &client).await {
+            Ok(price) if price > 0.0 => price,
+            _ => {
+                fills = Vec::new();
+                continue;
+            }
+        };
+        // Między odczytami cena mogła przejść przez wszystkie poziomy pomiędzy
+        let tick = Candle { open_time: unix_now() * 1000, open: last_price, high: last_price.max(price), low: last_price.min(price), close: price };
+        fills = exchange.on_candle(strategy.as_mut(), &tick);
+        last_price = price;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::OrderSpec;
+
+    /// Strategia, która tylko zapisuje kolejność wykonań
+    #[derive(Default)]
+    struct Recorder {
+        fills: Vec<(String, f64)>,
+    }
+
+    impl Strategy for Recorder {
+        fn on_start(&mut self, _ctx: &StrategyContext, _price: f64) -> Vec<OrderIntent> {
+            Vec::new()
+        }
+
+        fn on_fill(&mut self, _ctx: &StrategyContext, fill: &FillEvent) -> Vec<OrderIntent> {
+            self.fills.push((fill.side.clone(), fill.price));
+            Vec::new()
+        }
+    }
+
+    fn exchange(capital: f64) -> SimExchange {
+        SimExchange::new(PairConfig::default_for("BTCUSDT"), capital, 0.0, 0.001, 100.0, 0)
+    }
+
+    fn place(side: &'static str, price: f64, quantity: f64) -> OrderIntent {
+        OrderIntent::Place(OrderSpec::new(side, price, quantity))
+    }
+
+    fn candle(open: f64, high: f64, low: f64, close: f64) -> Candle {
+        Candle { open_time: 60_000, open, high, low, close }
+    }
+
+    #[test]
+    fn marketable_order_fills_at_market_with_fee_in_received_asset() {
+        let mut sim = exchange(1000.0);
+        let mut recorder = Recorder::default();
+        let fills = sim.apply(&mut recorder, vec![place("BUY", 101.0, 2.0)], 100.0);
+
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].price, 100.0);
+        assert!((sim.quote - 800.0).abs() < 1e-9);
+        assert!((sim.base - 1.998).abs() < 1e-12);
+        assert!((sim.fees_paid - 0.2).abs() < 1e-12);
+    }
+
+    #[test]
+    fn unfunded_orders_are_rejected() {
+        let mut sim = exchange(100.0);
+        let mut recorder = Recorder::default();
+        sim.apply(&mut recorder, vec![place("BUY", 90.0, 1.0), place("BUY", 80.0, 1.0), place("SELL", 110.0, 1.0)], 100.0);
+
+        assert_eq!(sim.rejected, 2);
+        assert_eq!(sim.context().open_orders.len(), 1);
+    }
+
+    #[test]
+    fn green_candle_fills_buys_before_sells() {
+        let mut sim = exchange(1000.0);
+        sim.base = 1.0;
+        let mut recorder = Recorder::default();
+        sim.apply(&mut recorder, vec![place("SELL", 105.0, 1.0), place("BUY", 95.0, 1.0)], 100.0);
+
+        sim.on_candle(&mut recorder, &candle(100.0, 106.0, 94.0, 104.0));
+        assert_eq!(recorder.fills, vec![("BUY".to_string(), 95.0), ("SELL".to_string(), 105.0)]);
+    }
+
+    #[test]
+    fn red_candle_fills_sells_before_buys() {
+        let mut sim = exchange(1000.0);
+        sim.base = 1.0;
+        let mut recorder = Recorder::default();
+        sim.apply(&mut recorder, vec![place("BUY", 95.0, 1.0), place("SELL", 105.0, 1.0)], 100.0);
+
+        sim.on_candle(&mut recorder, &candle(100.0, 106.0, 94.0, 96.0));
+        assert_eq!(recorder.fills, vec![("SELL".to_string(), 105.0), ("BUY".to_string(), 95.0)]);
+    }
+
+    #[test]
+    fn untouched_orders_stay_open() {
+        let mut sim = exchange(1000.0);
+        let mut recorder = Recorder::default();
+        sim.apply(&mut recorder, vec![place("BUY", 90.0, 1.0)], 100.0);
+
+        assert!(sim.on_candle(&mut recorder, &candle(100.0, 101.0, 91.0, 99.0)).is_empty());
+        assert_eq!(sim.context().open_orders.len(), 1);
+    }
+
+    #[test]
+    fn sweep_mode_harvests_profit_and_covers_losses() {
+        let pair = PairConfig { profit_mode: ProfitMode::Sweep, ..PairConfig::default_for("BTCUSDT") };
+        let mut sim = SimExchange::new(pair, 1000.0, 0.0, 0.0, 100.0, 0);
+        let mut recorder = Recorder::default();
+
+        sim.apply(&mut recorder, vec![place("BUY", 100.0, 1.0), place("SELL", 110.0, 1.0)], 100.0);
+        sim.on_candle(&mut recorder, &candle(100.0, 111.0, 99.0, 110.0));
+        assert!((sim.harvested - 10.0).abs() < 1e-9);
+        assert!((sim.context().capital - 1000.0).abs() < 1e-9);
+
+        sim.apply(&mut recorder, vec![place("BUY", 110.0, 1.0)], 110.0);
+        sim.apply(&mut recorder, vec![place("SELL", 106.0, 1.0)], 106.0);
+        assert!((sim.harvested - 6.0).abs() < 1e-9);
+        assert!((sim.equity(106.0) - 1006.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn compound_mode_grows_the_working_capital() {
+        let mut sim = SimExchange::new(PairConfig::default_for("BTCUSDT"), 1000.0, 0.0, 0.0, 100.0, 0);
+        let mut recorder = Recorder::default();
+        sim.apply(&mut recorder, vec![place("BUY", 100.0, 1.0), place("SELL", 110.0, 1.0)], 100.0);
+        sim.on_candle(&mut recorder, &candle(100.0, 111.0, 99.0, 110.0));
+
+        assert_eq!(sim.harvested, 0.0);
+        assert!((sim.context().capital - 1010.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn backtest_needs_candles() {
+        assert!(run_backtest(&PairConfig::default_for("BTCUSDT"), 1000.0, 0.0, &[], 0.001).is_err());
+    }
+
+    #[test]
+    fn flat_market_loses_exactly_the_fees() {
+        let candles: Vec<Candle> = (0..50)
+            .map(|i| Candle { open_time: i * 60_000, open: 100.0, high: 100.0, low: 100.0, close: 100.0 })
+            .collect();
+        let result = run_backtest(&PairConfig::default_for("BTCUSDT"), 1000.0, 0.0, &candles, 0.001).unwrap();
+
+        assert!((result.start_equity - 1000.0).abs() < 1e-9);
+        assert!((result.start_equity - result.final_equity - result.fees_paid).abs() < 1e-9);
+        assert!(result.max_drawdown_pct >= 0.0);
+    }
+
+    #[test]
+    fn oscillating_market_trades_and_values_equity_at_last_close() {
+        let candles: Vec<Candle> = (0..200)
+            .map(|i| {
+                let price = 100.0 + (i as f64 * 0.25).sin() * 10.0;
+                Candle { open_time: i * 60_000, open: price, high: price + 1.0, low: price - 1.0, close: price }
+            })
+            .collect();
+        let result = run_backtest(&PairConfig::default_for("BTCUSDT"), 1000.0, 0.0, &candles, 0.001).unwrap();
+
+        assert!(result.trades > 0);
+        assert!((result.final_equity - (result.final_quote + result.final_base * candles[199].close)).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,181 @@
+use reqwest::Client;
+use rusqlite::{params, Connection};
+
+use crate::config::{PairConfig, SizingScheme};
+use crate::{adjust_quantity, get_lot_size, get_min_notional, get_price, get_user_input, ledger, split_symbol, strategy};
+
+/// Ceny kupna klasycznego gridu: `levels_above` po cenie bieżącej, potem `levels_below` poniżej
+pub fn grid_buy_prices(pair: &PairConfig, price: f64) -> Vec<f64> {
+    let above = (0..pair.levels_above).map(|_| price);
+    let below = (1..=pair.levels_below as i32).map(|level| pair.level_price(price, -level));
+    above.chain(below).collect()
+}
+
+/// Ilość base na każdą cenę kupna wg schematu `pair.sizing`
+pub fn level_quantities(pair: &PairConfig, capital: f64, prices: &[f64]) -> Vec<f64> {
+    let levels = prices.len() as f64;
+    if prices.is_empty() || capital <= 0.0 {
+        return vec![0.0; prices.len()];
+    }
+    // Poziomów może być więcej niż w walidowanej konfiguracji (np. przegląd w `optimize`) – nigdy ponad przydział
+    let budget = (capital * pair.order_size * levels).min(capital);
+
+    match pair.sizing {
+        SizingScheme::EqualQuote => prices.iter().map(|price| budget / levels / price).collect(),
+        SizingScheme::EqualBase => {
+            let quantity = budget / prices.iter().sum::<f64>();
+            vec![quantity; prices.len()]
+        }
+        SizingScheme::Pyramid => {
+            // Waga = 1 + liczba poziomów o wyższej cenie
+            let weights: Vec<f64> = prices.iter()
+                .map(|price| 1.0 + prices.iter().filter(|other| *other > price).count() as f64)
+                .collect();
+            let total: f64 = weights.iter().sum();
+            prices.iter().zip(&weights).map(|(price, weight)| budget * weight / total / price).collect()
+        }
+        SizingScheme::Full => prices.iter().map(|price| capital / levels / price).collect(),
+    }
+}
+
+/// Poziom drabiny po dopasowaniu do filtrów giełdy
+pub struct LadderLevel {
+    pub price: f64,
+    pub quantity: f64,
+    /// Powód pominięcia (LOT_SIZE / NOTIONAL) – takie zlecenie zostałoby odrzucone
+    pub skipped: Option<String>,
+}
+
+/// Zaokrągla ilości do `step_size` i oznacza poziomy poniżej LOT_SIZE / NOTIONAL
+pub fn fit_ladder(prices: &[f64], quantities: &[f64], min_qty: f64, step_size: f64, min_notional: f64) -> Vec<LadderLevel> {
+    prices.iter().zip(quantities).map(|(&price, &quantity)| {
+        let quantity = adjust_quantity(quantity, step_size);
+        let skipped = if quantity < min_qty {
+            Some(format!("below LOT_SIZE {:.8}", min_qty))
+        } else if price * quantity < min_notional {
+            Some(format!("below NOTIONAL {:.2}", min_notional))
+        } else {
+            None
+        };
+        LadderLevel { price, quantity, skipped }
+    }).collect()
+}
+
+/// Podgląd drabiny kupna gridu dla kapitału `capital` przy cenie `price`
+pub async fn print_preview(pair: &PairConfig, capital: f64, price: f64) {
+    let symbol = pair.symbol.as_str();
+    let (_, quote_asset) = split_symbol(symbol);
+    let (min_qty, step_size) = get_lot_size(symbol).await.unwrap_or((0.01, 0.01));
+    let min_notional = get_min_notional(symbol).await.unwrap_or(10.0);
+
+    let prices = grid_buy_prices(pair, price);
+    let ladder = fit_ladder(&prices, &level_quantities(pair, capital, &prices), min_qty, step_size, min_notional);
+
+    println!("\n🪜 **Order ladder for {}** (sizing: {}, capital: {:.2} {})", symbol, pair.sizing.as_str(), capital, quote_asset);
+    let mut used = 0.0;
+    for (index, level) in ladder.iter().enumerate() {
+        let value = level.price * level.quantity;
+        let note = match &level.skipped {
+            Some(reason) => format!("⚠️ skipped: {}", reason),
+            None => {
+                used += value;
+                String::new()
+            }
+        };
+        println!(
+            "   {:>2}. BUY | Price: {:>14.4} | Qty: {:>14.6} | Value: {:>12.2} {}",
+            index + 1, level.price, level.quantity, value, note
+        );
+    }
+    let unused = capital - used;
+    println!(
+        "   Used: {:.2} {} ({:.1}%) | Unused: {:.2} {}",
+        used, quote_asset, if capital > 0.0 { used / capital * 100.0 } else { 0.0 }, unused, quote_asset
+    );
+}
+
+/// Podgląd drabiny dla pary z tabeli `capital` (opcja menu)
+pub async fn show_preview(db: &Connection) {
+    let symbol = get_user_input("Enter trading pair symbol to preview (e.g., BTCUSDT):").to_uppercase();
+    let exists = db.query_row("SELECT 1 FROM capital WHERE symbol = ?1", params![symbol], |_| Ok(())).is_ok();
+    if !exists {
+        println!("❌ No capital allocated for {}. Please set capital for the pair first.", symbol);
+        return;
+    }
+    let capital = ledger::balances(db, &symbol).working_capital();
+    let price = match get_price(&symbol, &Client::new()).await {
+        Ok(price) if price > 0.0 => price,
+        _ => {
+            println!("❌ Failed to fetch price for {}", symbol);
+            return;
+        }
+    };
+    print_preview(&strategy::pair_for(db, &symbol), capital, price).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(sizing: SizingScheme, order_size: f64) -> PairConfig {
+        PairConfig { sizing, order_size, ..PairConfig::default_for("BTCUSDT") }
+    }
+
+    fn spent(prices: &[f64], quantities: &[f64]) -> f64 {
+        prices.iter().zip(quantities).map(|(price, quantity)| price * quantity).sum()
+    }
+
+    const PRICES: [f64; 4] = [100.0, 95.0, 90.0, 85.0];
+
+    #[test]
+    fn equal_quote_spends_order_size_per_level() {
+        let quantities = level_quantities(&pair(SizingScheme::EqualQuote, 0.1), 1000.0, &PRICES);
+        for (price, quantity) in PRICES.iter().zip(&quantities) {
+            assert!((price * quantity - 100.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn equal_base_buys_the_same_quantity() {
+        let quantities = level_quantities(&pair(SizingScheme::EqualBase, 0.1), 1000.0, &PRICES);
+        assert!(quantities.windows(2).all(|w| (w[0] - w[1]).abs() < 1e-12));
+        assert!((spent(&PRICES, &quantities) - 400.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn pyramid_weights_lower_levels_more() {
+        let quantities = level_quantities(&pair(SizingScheme::Pyramid, 0.1), 1000.0, &PRICES);
+        let values: Vec<f64> = PRICES.iter().zip(&quantities).map(|(price, quantity)| price * quantity).collect();
+        assert!(values.windows(2).all(|w| w[0] < w[1]));
+        assert!((spent(&PRICES, &quantities) - 400.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn full_spends_the_whole_allocation() {
+        let quantities = level_quantities(&pair(SizingScheme::Full, 0.1), 1000.0, &PRICES);
+        assert!((spent(&PRICES, &quantities) - 1000.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn budget_never_exceeds_capital() {
+        for sizing in SizingScheme::ALL {
+            let quantities = level_quantities(&pair(sizing, 0.5), 1000.0, &PRICES);
+            assert!(spent(&PRICES, &quantities) <= 1000.0 + 1e-9, "{:?}", sizing);
+        }
+    }
+
+    #[test]
+    fn no_capital_means_no_orders() {
+        assert_eq!(level_quantities(&pair(SizingScheme::EqualQuote, 0.1), 0.0, &PRICES), vec![0.0; 4]);
+        assert!(level_quantities(&pair(SizingScheme::EqualQuote, 0.1), 1000.0, &[]).is_empty());
+    }
+
+    #[test]
+    fn fit_ladder_rounds_down_and_flags_exchange_filters() {
+        let levels = fit_ladder(&[100.0, 100.0, 100.0], &[0.1239, 0.0004, 0.05], 0.001, 0.001, 10.0);
+        assert!((levels[0].quantity - 0.123).abs() < 1e-12);
+        assert!(levels[0].skipped.is_none());
+        assert!(levels[1].skipped.as_deref().unwrap().contains("LOT_SIZE"));
+        assert!(levels[2].skipped.as_deref().unwrap().contains("NOTIONAL"));
+    }
+}
@@ -0,0 +1,154 @@
+use reqwest::Client;
+use rusqlite::{params, Connection};
+
+use crate::config::PairConfig;
+use crate::{breaker, config, dry_run, events, get_price, ledger, live, place_market_sell, portfolio};
+
+/// Sprawdza warunki wyjścia z gridu; zwraca (zdarzenie, opis) dla pierwszego spełnionego
+fn evaluate(pair: &PairConfig, price: f64, pnl: f64, allocation: f64) -> Option<(&'static str, String)> {
+    if let Some(stop) = pair.stop_loss_price {
+        if price <= stop {
+            return Some(("STOP_LOSS", format!("price {:.8} <= stop_loss_price {:.8}", price, stop)));
+        }
+    }
+    if let Some(pct) = pair.stop_loss_pct {
+        if allocation > 0.0 && pnl <= -pct * allocation {
+            return Some(("STOP_LOSS", format!("PnL {:.2} <= -{:.1}% of allocation {:.2}", pnl, pct * 100.0, allocation)));
+        }
+    }
+    if let Some(take) = pair.take_profit_price {
+        if price >= take {
+            return Some(("TAKE_PROFIT", format!("price {:.8} >= take_profit_price {:.8}", price, take)));
+        }
+    }
+    if let Some(pct) = pair.take_profit_pct {
+        if allocation > 0.0 && pnl >= pct * allocation {
+            return Some(("TAKE_PROFIT", format!("PnL {:.2} >= {:.1}% of allocation {:.2}", pnl, pct * 100.0, allocation)));
+        }
+    }
+    None
+}
+
+/// Zamyka grid: zatrzymuje strategię pary, opcjonalnie sprzedaje zapas, wyłącza parę i zapisuje zdarzenie
+async fn exit_grid(db: &mut Connection, client: &Client, pair: &PairConfig, event: &str, reason: &str) {
+    let symbol = pair.symbol.as_str();
+    println!("🚨 {} triggered for {}: {}", event, symbol, reason);
+
+    if dry_run() {
+        println!("🧪 [DRY-RUN] Grid for {} would be closed (nothing was sent)", symbol);
+        return;
+    }
+
+    let mut details = reason.to_string();
+    // Zatrzymanie przez strategię (`on_stop`) – anuluje zlecenia bota dla pary
+    let count = live::stop(db, client, symbol).await;
+    details.push_str(&format!("; canceled {} orders", count));
+
+    if pair.liquidate_on_exit {
+        let held = portfolio::load_position(db, symbol).held_qty;
+        if held > 0.0 {
+            // Druga próba po ponownym odczycie salda (np. zwolnionego po anulowaniu zleceń)
+            let mut result = place_market_sell(db, client, symbol, held).await;
+            if let Err(e) = &result {
+                println!("⚠️ Liquidation of {} failed ({}), retrying", symbol, e);
+                result = place_market_sell(db, client, symbol, held).await;
+            }
+            match result {
+                Ok(order) => details.push_str(&format!("; liquidated {:.8} (order {})", order.quantity, order.order_id)),
+                Err(e) => {
+                    println!("❌ Failed to liquidate inventory of {}: {}", symbol, e);
+                    details.push_str("; liquidation failed");
+                    // Pozycja została bez ochrony – wstrzymujemy wszystkie gridy do ręcznej decyzji
+                    breaker::trip(db, client, &format!("liquidation of {} after {} failed: {}", symbol, event, e)).await;
+                }
+            }
+        }
+    }
+
+    db.execute("UPDATE capital SET is_active = 0 WHERE symbol = ?1", params![symbol])
+        .expect("Failed to update trading bot status");
+    events::record(db, Some(symbol), event, &details);
+    println!("🛑 Grid for {} stopped: {}", symbol, details);
+}
+
+/// Sprawdza stop-loss / take-profit wszystkich aktywnych gridów (wywoływane z pętli monitora)
+pub async fn check_active_grids(db: &mut Connection, client: &Client) {
+    let symbols: Vec<String> = {
+        let mut stmt = db.prepare("SELECT DISTINCT symbol FROM capital WHERE is_active = 1 ORDER BY symbol ASC")
+            .expect("Failed to prepare statement");
+        stmt.query_map([], |row| row.get(0))
+            .expect("Failed to query capital pairs")
+            .filter_map(Result::ok)
+            .collect()
+    };
+
+    for symbol in symbols {
+        let pair = config::config().pair(&symbol);
+        if pair.stop_loss_price.is_none() && pair.stop_loss_pct.is_none()
+            && pair.take_profit_price.is_none() && pair.take_profit_pct.is_none() {
+            continue;
+        }
+
+        let price = match get_price(&symbol, client).await {
+            Ok(price) if price > 0.0 => price,
+            _ => {
+                println!("⚠️ Cannot check stop-loss / take-profit for {}: price unavailable", symbol);
+                continue;
+            }
+        };
+        let position = portfolio::load_position(db, &symbol);
+        let pnl = position.realized_pnl + position.unrealized_pnl(price);
+        let allocation = ledger::balances(db, &symbol).allocation;
+
+        if let Some((event, reason)) = evaluate(&pair, price, pnl, allocation) {
+            exit_grid(db, client, &pair, event, &reason).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair() -> PairConfig {
+        PairConfig::default_for("BTCUSDT")
+    }
+
+    #[test]
+    fn no_exit_without_thresholds() {
+        assert!(evaluate(&pair(), 50.0, -500.0, 1000.0).is_none());
+    }
+
+    #[test]
+    fn stop_loss_price_and_percentage() {
+        let by_price = PairConfig { stop_loss_price: Some(90.0), ..pair() };
+        assert_eq!(evaluate(&by_price, 90.0, 0.0, 1000.0).map(|(event, _)| event), Some("STOP_LOSS"));
+        assert!(evaluate(&by_price, 90.5, 0.0, 1000.0).is_none());
+
+        let by_pct = PairConfig { stop_loss_pct: Some(0.1), ..pair() };
+        assert_eq!(evaluate(&by_pct, 100.0, -100.0, 1000.0).map(|(event, _)| event), Some("STOP_LOSS"));
+        assert!(evaluate(&by_pct, 100.0, -99.0, 1000.0).is_none());
+    }
+
+    #[test]
+    fn take_profit_price_and_percentage() {
+        let by_price = PairConfig { take_profit_price: Some(120.0), ..pair() };
+        assert_eq!(evaluate(&by_price, 121.0, 0.0, 1000.0).map(|(event, _)| event), Some("TAKE_PROFIT"));
+
+        let by_pct = PairConfig { take_profit_pct: Some(0.05), ..pair() };
+        assert_eq!(evaluate(&by_pct, 100.0, 50.0, 1000.0).map(|(event, _)| event), Some("TAKE_PROFIT"));
+        assert!(evaluate(&by_pct, 100.0, 49.0, 1000.0).is_none());
+    }
+
+    #[test]
+    fn percentage_limits_need_an_allocation() {
+        let both = PairConfig { stop_loss_pct: Some(0.1), take_profit_pct: Some(0.1), ..pair() };
+        assert!(evaluate(&both, 100.0, -500.0, 0.0).is_none());
+    }
+
+    #[test]
+    fn stop_loss_wins_over_take_profit() {
+        let both = PairConfig { stop_loss_price: Some(110.0), take_profit_price: Some(100.0), ..pair() };
+        assert_eq!(evaluate(&both, 105.0, 0.0, 1000.0).map(|(event, _)| event), Some("STOP_LOSS"));
+    }
+}
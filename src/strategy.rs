@@ -0,0 +1,192 @@
+use rusqlite::{params, Connection};
+
+use crate::config::{self, PairConfig, StrategyKind};
+use crate::dca::DcaAccumulation;
+use crate::infinity::InfinityGrid;
+use crate::reverse::ReverseGrid;
+use crate::{ledger, sizing};
+
+/// Zlecenie LIMIT, które strategia chce złożyć
+#[derive(Debug, Clone)]
+pub struct OrderSpec {
+    pub side: &'static str,
+    pub price: f64,
+    pub quantity: f64,
+    /// Grid: o ile poziomów nad ceną wykonania kupna złożyć sprzedaż
+    pub level: Option<i32>,
+    /// Reverse grid: ilość base sprzedana wcześniej, którą to kupno odkupuje
+    pub covers_qty: Option<f64>,
+}
+
+impl OrderSpec {
+    pub fn new(side: &'static str, price: f64, quantity: f64) -> OrderSpec {
+        OrderSpec { side, price, quantity, level: None, covers_qty: None }
+    }
+
+    /// Kupno po cenie >= `price` lub sprzedaż <= `price` wykona się od razu
+    pub fn is_marketable(&self, price: f64) -> bool {
+        if self.side == "BUY" { self.price >= price } else { self.price <= price }
+    }
+}
+
+/// Polecenie strategii dla runnera (live, paper lub backtest)
+#[derive(Debug, Clone)]
+pub enum OrderIntent {
+    Place(OrderSpec),
+    Cancel(u64),
+}
+
+#[derive(Debug, Clone)]
+pub struct OpenOrder {
+    pub order_id: u64,
+    pub side: String,
+    pub price: f64,
+    pub quantity: f64,
+}
+
+/// W pełni wykonane zlecenie strategii
+#[derive(Debug, Clone)]
+pub struct FillEvent {
+    pub order_id: u64,
+    pub side: String,
+    pub price: f64,
+    pub quantity: f64,
+    /// Otrzymane base po prowizji (kupno)
+    pub net_base: f64,
+    /// Otrzymane quote po prowizji (sprzedaż)
+    pub net_quote: f64,
+    pub level: Option<i32>,
+    pub covers_qty: Option<f64>,
+}
+
+/// Stan pary widziany przez strategię
+#[derive(Debug, Clone)]
+pub struct StrategyContext<'a> {
+    pub pair: &'a PairConfig,
+    /// Przydział w quote
+    pub capital: f64,
+    /// Przydział w base (reverse grid)
+    pub base_amount: f64,
+    /// Dolna granica zakresu pary
+    pub min_price: f64,
+    /// Posiadane base wg wykonań
+    pub held: f64,
+    /// Średnia cena wejścia posiadanego base
+    pub avg_entry: f64,
+    /// Ostatnie kupno strategii: (czas unix w sekundach, cena)
+    pub last_buy: Option<(i64, f64)>,
+    /// Bieżący czas unix w sekundach (w backteście – czas świecy)
+    pub now: i64,
+    pub open_orders: Vec<OpenOrder>,
+}
+
+/// Strategia handlowa. Metody przyjmują `&mut self`, ale obecne implementacje nie trzymają stanu
+/// między wywołaniami – wszystko czytają z kontekstu i zdarzeń, więc runner może je odtworzyć
+/// po restarcie, a ten sam kod działa na żywo, na papierze i w backteście.
+pub trait Strategy {
+    fn on_start(&mut self, ctx: &StrategyContext, price: f64) -> Vec<OrderIntent>;
+
+    fn on_price(&mut self, _ctx: &StrategyContext, _price: f64) -> Vec<OrderIntent> {
+        Vec::new()
+    }
+
+    fn on_fill(&mut self, ctx: &StrategyContext, fill: &FillEvent) -> Vec<OrderIntent>;
+
+    fn on_cancel(&mut self, _ctx: &StrategyContext, _order: &OpenOrder) -> Vec<OrderIntent> {
+        Vec::new()
+    }
+
+    /// Domyślnie anuluje wszystkie otwarte zlecenia strategii
+    fn on_stop(&mut self, ctx: &StrategyContext) -> Vec<OrderIntent> {
+        ctx.open_orders.iter().map(|order| OrderIntent::Cancel(order.order_id)).collect()
+    }
+}
+
+/// Odkup po każdej wykonanej sprzedaży, `reinvest_offset` poniżej jej ceny
+pub struct ReinvestLadder;
+
+impl Strategy for ReinvestLadder {
+    fn on_start(&mut self, _ctx: &StrategyContext, _price: f64) -> Vec<OrderIntent> {
+        Vec::new()
+    }
+
+    fn on_fill(&mut self, ctx: &StrategyContext, fill: &FillEvent) -> Vec<OrderIntent> {
+        if fill.side != "SELL" {
+            return Vec::new();
+        }
+        let price = fill.price * (1.0 - ctx.pair.reinvest_offset);
+        let mut buy = OrderSpec::new("BUY", price, fill.quantity);
+        buy.level = Some(1);
+        vec![OrderIntent::Place(buy)]
+    }
+}
+
+/// Klasyczny grid: `levels_above` kupn po cenie rynkowej z poziomami sprzedaży nad ceną,
+/// `levels_below` kupn poniżej; każde wykonane kupno dostaje sprzedaż, każda sprzedaż – odkup
+pub struct ClassicGrid {
+    ladder: ReinvestLadder,
+}
+
+impl Strategy for ClassicGrid {
+    fn on_start(&mut self, ctx: &StrategyContext, price: f64) -> Vec<OrderIntent> {
+        let pair = ctx.pair;
+        let prices = sizing::grid_buy_prices(pair, price);
+        let quantities = sizing::level_quantities(pair, ctx.capital, &prices);
+
+        // Pierwsze `levels_above` kupn sprzedajemy na kolejnych poziomach nad ceną, pozostałe poziom wyżej
+        prices.into_iter().zip(quantities).enumerate().map(|(index, (buy_price, quantity))| {
+            let mut buy = OrderSpec::new("BUY", buy_price, quantity);
+            buy.level = Some(if index < pair.levels_above as usize { index as i32 + 1 } else { 1 });
+            OrderIntent::Place(buy)
+        }).collect()
+    }
+
+    fn on_fill(&mut self, ctx: &StrategyContext, fill: &FillEvent) -> Vec<OrderIntent> {
+        if fill.side == "SELL" {
+            return self.ladder.on_fill(ctx, fill);
+        }
+        // Sprzedajemy to, co faktycznie otrzymaliśmy (po prowizji)
+        if fill.net_base <= 0.0 {
+            return Vec::new();
+        }
+        let sell_price = ctx.pair.level_price(fill.price, fill.level.unwrap_or(1));
+        vec![OrderIntent::Place(OrderSpec::new("SELL", sell_price, fill.net_base))]
+    }
+}
+
+pub fn build(kind: StrategyKind) -> Box<dyn Strategy + Send> {
+    match kind {
+        StrategyKind::Grid => Box::new(ClassicGrid { ladder: ReinvestLadder }),
+        StrategyKind::Ladder => Box::new(ReinvestLadder),
+        StrategyKind::Infinity => Box::new(InfinityGrid),
+        StrategyKind::Reverse => Box::new(ReverseGrid),
+        StrategyKind::Dca => Box::new(DcaAccumulation),
+    }
+}
+
+/// Strategia pary zapisana w `capital.strategy`, a gdy jej brak – domyślna z konfiguracji
+pub fn kind_for(db: &Connection, symbol: &str) -> StrategyKind {
+    db.query_row(
+        "SELECT strategy FROM capital WHERE symbol = ?1",
+        params![symbol],
+        |row| row.get::<_, Option<String>>(0),
+    ).ok().flatten()
+        .and_then(|name| StrategyKind::parse(&name))
+        .unwrap_or_else(|| config::config().pair(symbol).strategy)
+}
+
+/// Konfiguracja pary z ustawieniami zapisanymi w `capital` (strategia, tryb zysku, `levels_below`)
+pub fn pair_for(db: &Connection, symbol: &str) -> PairConfig {
+    let mut pair = config::config().pair(symbol);
+    pair.strategy = kind_for(db, symbol);
+    pair.profit_mode = ledger::profit_mode(db, symbol);
+    let levels_below = db.query_row(
+        "SELECT levels_below FROM capital WHERE symbol = ?1",
+        params![symbol],
+        |row| row.get::<_, Option<u32>>(0),
+    ).ok().flatten();
+    if let Some(levels_below) = levels_below {
+        pair.levels_below = levels_below;
+    }
+    pair
+}
@@ -0,0 +1,186 @@
+use reqwest::Client;
+use rusqlite::{params, Connection};
+use serde_json::Value;
+
+use crate::{ledger, now_millis, record_fill, send_signed_request, Fill};
+
+/// Maksymalna liczba transakcji zwracana przez `myTrades` na jedną stronę
+const MY_TRADES_PAGE_LIMIT: usize = 1000;
+
+/// Ostatni zsynchronizowany `trade_id` dla pary (kursor do wznowienia synchronizacji)
+fn last_trade_id(db: &Connection, symbol: &str) -> Option<u64> {
+    db.query_row(
+        "SELECT MAX(trade_id) FROM trades WHERE symbol = ?1",
+        params![symbol],
+        |row| row.get::<_, Option<i64>>(0),
+    ).ok().flatten().map(|id| id as u64)
+}
+
+/// Zapisuje stronę `myTrades` do `trades`; zwraca `fromId` następnej strony
+fn record_page(db: &Connection, symbol: &str, trades: &[Value], from_id: u64) -> u64 {
+    let mut next_id = from_id;
+    for trade in trades {
+        let fill = Fill::from_json(trade, now_millis());
+        let order_id = trade["orderId"].as_u64().unwrap_or(0);
+        let side = if trade["isBuyer"].as_bool().unwrap_or(false) { "BUY" } else { "SELL" };
+        record_fill(db, symbol, side, order_id, &fill);
+        next_id = next_id.max(fill.trade_id + 1);
+    }
+    next_id
+}
+
+/// Pobiera stronami `myTrades` od `fromId` i zapisuje transakcje do `trades` (upsert po `symbol`, `trade_id`).
+/// Bez żadnego `trade_id` (baza sprzed tej kolumny) zaczyna od początku historii – `record_fill`
+/// dopasowuje wtedy stare wiersze zamiast je dublować. Zwraca liczbę przetworzonych transakcji.
+pub async fn sync_symbol_trades(
+    db: &mut Connection,
+    client: &Client,
+    api_key: &str,
+    secret_key: &str,
+    symbol: &str
+) -> Result<usize, String> {
+    let mut from_id = last_trade_id(db, symbol).map(|id| id + 1).unwrap_or(0);
+    let mut imported = 0;
+
+    loop {
+        let params = format!("symbol={}&fromId={}&limit={}", symbol, from_id, MY_TRADES_PAGE_LIMIT);
+        let page = send_signed_request(client, reqwest::Method::GET, "/api/v3/myTrades", &params, api_key, secret_key).await?;
+        let trades = page.as_array().cloned().unwrap_or_default();
+
+        let tx = db.transaction().map_err(|e| e.to_string())?;
+        from_id = record_page(&tx, symbol, &trades, from_id);
+        tx.commit().map_err(|e| e.to_string())?;
+
+        imported += trades.len();
+        if trades.len() < MY_TRADES_PAGE_LIMIT {
+            break;
+        }
+    }
+
+    Ok(imported)
+}
+
+/// Zamyka otwarte lokalnie zlecenia bota dla pary, które wg `trades` zostały w pełni wykonane.
+/// Zwraca je jako (order_id, side, price, quantity). Zleceń spoza bota strategia nie obsługuje.
+pub fn close_filled_orders(db: &Connection, symbol: &str) -> Vec<(u64, String, f64, f64)> {
+    let filled: Vec<(u64, String, f64, f64)> = {
+        let mut stmt = db.prepare(
+            "SELECT o.order_id, COALESCE(o.side, ''), o.price, o.quantity FROM orders o
+             WHERE o.symbol = ?1 AND o.source = 'bot' AND o.status IN ('NEW', 'PARTIALLY_FILLED')
+               AND (SELECT COALESCE(SUM(t.quantity), 0) FROM trades t
+                    WHERE t.symbol = o.symbol AND t.order_id = o.order_id) >= o.quantity * 0.999999
+             ORDER BY o.order_id ASC"
+        ).expect("Failed to prepare statement");
+        stmt.query_map(params![symbol], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+            .expect("Failed to query orders")
+            .filter_map(Result::ok)
+            .collect()
+    };
+
+    for (order_id, _, _, _) in &filled {
+        db.execute("UPDATE orders SET status = 'FILLED' WHERE order_id = ?1", params![order_id])
+            .expect("Failed to update order status");
+        ledger::release_order(db, symbol, *order_id);
+    }
+    filled
+}
+
+/// Synchronizuje historię transakcji dla wszystkich skonfigurowanych par
+pub async fn sync_all_trades(db: &mut Connection, client: &Client, api_key: &str, secret_key: &str) {
+    let symbols: Vec<String> = {
+        let mut stmt = db.prepare("SELECT DISTINCT symbol FROM capital ORDER BY symbol ASC")
+            .expect("Failed to prepare statement");
+        stmt.query_map([], |row| row.get(0))
+            .expect("Failed to query capital pairs")
+            .filter_map(Result::ok)
+            .collect()
+    };
+
+    for symbol in symbols {
+        match sync_symbol_trades(db, client, api_key, secret_key, &symbol).await {
+            Ok(0) => {}
+            Ok(count) => println!("🔄 Synced {} new trades for {}", count, symbol),
+            Err(e) => println!("❌ Failed to sync trades for {}: {}", symbol, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SYMBOL: &str = "BTCUSDT";
+
+    fn test_db() -> Connection {
+        let db = Connection::open_in_memory().unwrap();
+        crate::create_schema(&db);
+        db
+    }
+
+    fn trade(id: u64, order_id: u64, is_buyer: bool, price: &str, qty: &str) -> Value {
+        json!({
+            "id": id, "orderId": order_id, "isBuyer": is_buyer, "price": price, "qty": qty,
+            "commission": "0.0", "commissionAsset": "USDT", "time": 1_700_000_000_000u64 + id * 1000
+        })
+    }
+
+    fn insert_order(db: &Connection, order_id: u64, symbol: &str, side: &str, quantity: f64, source: &str) {
+        db.execute(
+            "INSERT INTO orders (order_id, symbol, price, stop_price, quantity, type, status, timestamp, side, source)
+             VALUES (?1, ?2, 100.0, 0.0, ?3, 'LIMIT', 'NEW', datetime('now'), ?4, ?5)",
+            params![order_id, symbol, quantity, side, source],
+        ).unwrap();
+    }
+
+    fn count(db: &Connection, sql: &str) -> i64 {
+        db.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn page_is_recorded_once_and_advances_the_cursor() {
+        let db = test_db();
+        let page = vec![trade(5, 1, true, "100.0", "1.0"), trade(6, 2, false, "110.0", "1.0")];
+
+        assert_eq!(record_page(&db, SYMBOL, &page, 0), 7);
+        assert_eq!(record_page(&db, SYMBOL, &page, 0), 7);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM trades"), 2);
+        assert_eq!(last_trade_id(&db, SYMBOL), Some(6));
+        let profit: f64 = db.query_row("SELECT profit FROM trades WHERE trade_id = 6", [], |row| row.get(0)).unwrap();
+        assert!((profit - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn legacy_rows_are_claimed_by_a_full_resync() {
+        let db = test_db();
+        db.execute(
+            "INSERT INTO trades (symbol, price, quantity, timestamp, type, order_id)
+             VALUES (?1, 100.0, 1.0, '2023-11-14 22:13:25', 'Buy', 1)",
+            params![SYMBOL],
+        ).unwrap();
+        assert_eq!(last_trade_id(&db, SYMBOL), None);
+
+        record_page(&db, SYMBOL, &[trade(5, 1, true, "100.0", "1.0")], 0);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM trades"), 1);
+        assert_eq!(last_trade_id(&db, SYMBOL), Some(5));
+    }
+
+    #[test]
+    fn only_fully_filled_bot_orders_are_closed() {
+        let db = test_db();
+        insert_order(&db, 1, SYMBOL, "BUY", 1.0, "bot");
+        insert_order(&db, 2, SYMBOL, "BUY", 1.0, "exchange");
+        insert_order(&db, 3, SYMBOL, "BUY", 2.0, "bot");
+        record_page(&db, SYMBOL, &[
+            trade(5, 1, true, "100.0", "1.0"),
+            trade(6, 2, true, "100.0", "1.0"),
+            trade(7, 3, true, "100.0", "1.0"),
+        ], 0);
+        // Ten sam `orderId` w innej parze nie wykonuje zlecenia
+        record_page(&db, "ETHUSDT", &[trade(8, 3, true, "100.0", "1.0")], 0);
+
+        let closed = close_filled_orders(&db, SYMBOL);
+        assert_eq!(closed.iter().map(|(order_id, ..)| *order_id).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM orders WHERE status = 'FILLED'"), 1);
+    }
+}
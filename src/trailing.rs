@@ -0,0 +1,236 @@
+use reqwest::Client;
+use rusqlite::{params, Connection};
+
+use crate::config::{PairConfig, StrategyKind};
+use crate::{
+    cancel_order, dry_run, events, get_price, place_binance_order, portfolio, record_placed_order, strategy, PlacedOrder,
+};
+
+/// Najdalszy od ceny otwarty poziom bota po danej stronie
+struct Level {
+    order_id: u64,
+    price: f64,
+    quantity: f64,
+    level: Option<i32>,
+    covers_qty: Option<f64>,
+}
+
+fn farthest_order(db: &Connection, symbol: &str, side: &str) -> Option<Level> {
+    let order = if side == "BUY" { "ASC" } else { "DESC" };
+    db.query_row(
+        &format!(
+            "SELECT order_id, price, quantity, level, covers_qty FROM orders
+             WHERE symbol = ?1 AND side = ?2 AND source = 'bot' AND status IN ('NEW', 'PARTIALLY_FILLED')
+             ORDER BY price {} LIMIT 1",
+            order
+        ),
+        params![symbol, side],
+        |row| Ok(Level { order_id: row.get(0)?, price: row.get(1)?, quantity: row.get(2)?, level: row.get(3)?, covers_qty: row.get(4)? }),
+    ).ok()
+}
+
+/// Przesunięcie zakresu o jeden poziom: nowy poziom po drugiej stronie ceny i nowy zakres
+#[derive(Debug, PartialEq)]
+struct Shift {
+    side: &'static str,
+    price: f64,
+    quantity: f64,
+    range: (f64, f64),
+}
+
+/// Strona przenoszonego poziomu: w górę najniższe kupno, w dół najwyższa sprzedaż
+fn moved_side(up: bool) -> &'static str {
+    if up { "BUY" } else { "SELL" }
+}
+
+/// Wylicza przesunięcie przeniesionego poziomu (`level`: cena, ilość); `Err` – powód pominięcia.
+/// Kupno zachowuje wartość w quote, sprzedaż – ilość; sprzedaż poniżej średniej ceny zakupu
+/// realizowałaby stratę, więc w dół przesuwamy tylko do kosztu.
+fn plan_shift(pair: &PairConfig, up: bool, price: f64, range: (f64, f64), level: (f64, f64), avg_entry: f64) -> Result<Shift, String> {
+    let k = if up { 1 } else { -1 };
+    let (new_min, new_max) = (pair.level_price(range.0, k), pair.level_price(range.1, k));
+
+    if let Some(ceiling) = pair.trail_ceiling.filter(|ceiling| up && new_max > *ceiling) {
+        return Err(format!("above range, but trail_ceiling {:.8} reached", ceiling));
+    }
+    if let Some(floor) = pair.trail_floor.filter(|floor| !up && new_min < *floor) {
+        return Err(format!("below range, but trail_floor {:.8} reached", floor));
+    }
+
+    let (old_price, old_quantity) = level;
+    let new_price = pair.level_price(price, -k);
+    if !up && avg_entry > 0.0 && new_price < avg_entry {
+        return Err(format!("below range, but new SELL {:.8} would be under average entry {:.8}", new_price, avg_entry));
+    }
+    let quantity = if up { old_price * old_quantity / new_price } else { old_quantity };
+    Ok(Shift { side: moved_side(up), price: new_price, quantity, range: (new_min, new_max) })
+}
+
+/// Zapisuje złożony poziom razem z metadanymi strategii przeniesionego zlecenia
+fn record_level(db: &Connection, order: &PlacedOrder, from: &Level) {
+    record_placed_order(db, order);
+    db.execute(
+        "UPDATE orders SET level = ?2, covers_qty = ?3 WHERE order_id = ?1",
+        params![order.order_id, from.level, from.covers_qty],
+    ).expect("Failed to store order metadata");
+}
+
+/// Przesuwa zakres gridu o jeden poziom: anuluje najdalszy poziom i dodaje nowy po drugiej stronie
+async fn shift(db: &mut Connection, client: &Client, pair: &PairConfig, up: bool, price: f64, range: (f64, f64)) {
+    let symbol = pair.symbol.as_str();
+    let side = moved_side(up);
+    let Some(old) = farthest_order(db, symbol, side) else {
+        println!("ℹ️ {} out of range, but no open {} level to move", symbol, side);
+        return;
+    };
+    let avg_entry = portfolio::running_position(db, symbol).avg_entry_price();
+    let plan = match plan_shift(pair, up, price, range, (old.price, old.quantity), avg_entry) {
+        Ok(plan) => plan,
+        Err(reason) => {
+            println!("⛔ {} {}", symbol, reason);
+            return;
+        }
+    };
+    let (new_min, new_max) = plan.range;
+
+    println!(
+        "🧭 Trailing {} {}: {} {:.8} -> {:.8}, range {:.8} - {:.8} -> {:.8} - {:.8}",
+        symbol, if up { "up" } else { "down" }, side, old.price, plan.price, range.0, range.1, new_min, new_max
+    );
+    if dry_run() {
+        println!("🧪 [DRY-RUN] Grid for {} would be shifted (nothing was sent)", symbol);
+        return;
+    }
+
+    if let Err(e) = cancel_order(db, client, symbol, old.order_id).await {
+        println!("❌ Trailing {} skipped: cannot cancel order {}: {}", symbol, old.order_id, e);
+        return;
+    }
+    let order = match place_binance_order(db, client, symbol, side, plan.price, plan.quantity, false).await {
+        Ok(order) => order,
+        Err(e) => {
+            // Zakres zostaje bez zmian – przywracamy anulowany poziom
+            let details = match place_binance_order(db, client, symbol, side, old.price, old.quantity, false).await {
+                Ok(restored) => {
+                    record_level(db, &restored, &old);
+                    format!("{} at {:.8} rejected ({}); level {:.8} restored (order {})", side, plan.price, e, old.price, restored.order_id)
+                }
+                Err(restore_error) => format!(
+                    "{} at {:.8} rejected ({}); canceled level {:.8} could not be restored: {}",
+                    side, plan.price, e, old.price, restore_error
+                ),
+            };
+            println!("❌ Trailing {} failed: {}", symbol, details);
+            events::record(db, Some(symbol), "TRAIL_FAILED", &details);
+            return;
+        }
+    };
+    record_level(db, &order, &old);
+    let details = format!(
+        "{} moved {:.8} -> {:.8} (order {}), range {:.8} - {:.8}",
+        side, old.price, plan.price, order.order_id, new_min, new_max
+    );
+
+    db.execute(
+        "UPDATE capital SET min_price = ?2, max_price = ?3, trail_shifts = trail_shifts + 1, last_trail_at = datetime('now')
+         WHERE symbol = ?1",
+        params![symbol, new_min, new_max],
+    ).expect("Failed to update grid range");
+    events::record(db, Some(symbol), if up { "TRAIL_UP" } else { "TRAIL_DOWN" }, &details);
+}
+
+/// Przesuwa zakresy aktywnych gridów z włączonym trailingiem (wywoływane z pętli monitora)
+pub async fn check_active_grids(db: &mut Connection, client: &Client) {
+    let grids: Vec<(String, f64, f64)> = {
+        let mut stmt = db.prepare("SELECT symbol, min_price, max_price FROM capital WHERE is_active = 1 ORDER BY symbol ASC")
+            .expect("Failed to prepare statement");
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .expect("Failed to query capital pairs")
+            .filter_map(Result::ok)
+            .collect()
+    };
+
+    for (symbol, min_price, max_price) in grids {
+        // Trailing przesuwa poziomy klasycznego gridu – pozostałe strategie zarządzają zleceniami same
+        let pair = strategy::pair_for(db, &symbol);
+        if pair.strategy != StrategyKind::Grid || !(pair.trail_up || pair.trail_down) || min_price <= 0.0 || max_price <= min_price {
+            continue;
+        }
+
+        let price = match get_price(&symbol, client).await {
+            Ok(price) if price > 0.0 => price,
+            _ => continue,
+        };
+        if pair.trail_up && price > max_price {
+            shift(db, client, &pair, true, price, (min_price, max_price)).await;
+        } else if pair.trail_down && price < min_price {
+            shift(db, client, &pair, false, price, (min_price, max_price)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SpacingType;
+
+    fn pair() -> PairConfig {
+        let mut pair = PairConfig::default_for("BTCUSDT");
+        pair.spacing = SpacingType::Arithmetic;
+        pair.step = 0.01;
+        pair
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn trailing_up_moves_the_lowest_buy_keeping_its_quote_value() {
+        let shift = plan_shift(&pair(), true, 111.0, (100.0, 110.0), (100.0, 1.0), 0.0).unwrap();
+        assert_eq!(shift.side, "BUY");
+        assert_close(shift.price, 111.0 * 0.99);
+        assert_close(shift.price * shift.quantity, 100.0);
+        assert_close(shift.range.0, 101.0);
+        assert_close(shift.range.1, 111.1);
+    }
+
+    #[test]
+    fn trailing_down_moves_the_highest_sell_keeping_its_quantity() {
+        let shift = plan_shift(&pair(), false, 99.0, (100.0, 110.0), (110.0, 0.5), 95.0).unwrap();
+        assert_eq!(shift.side, "SELL");
+        assert_close(shift.price, 99.99);
+        assert_close(shift.quantity, 0.5);
+        assert_close(shift.range.0, 99.0);
+    }
+
+    #[test]
+    fn trailing_stops_at_ceiling_and_floor() {
+        let mut bounded = pair();
+        bounded.trail_ceiling = Some(111.0);
+        bounded.trail_floor = Some(99.5);
+        assert!(plan_shift(&bounded, true, 111.0, (100.0, 110.0), (100.0, 1.0), 0.0).unwrap_err().contains("trail_ceiling"));
+        assert!(plan_shift(&bounded, false, 99.0, (100.0, 110.0), (110.0, 0.5), 0.0).unwrap_err().contains("trail_floor"));
+    }
+
+    #[test]
+    fn trailing_down_never_sells_below_average_entry() {
+        assert!(plan_shift(&pair(), false, 99.0, (100.0, 110.0), (110.0, 0.5), 105.0).unwrap_err().contains("average entry"));
+    }
+
+    #[test]
+    fn only_bot_levels_are_moved() {
+        let db = Connection::open_in_memory().unwrap();
+        crate::create_schema(&db);
+        for (order_id, price, source) in [(1, 90.0, "exchange"), (2, 95.0, "bot"), (3, 99.0, "bot")] {
+            db.execute(
+                "INSERT INTO orders (order_id, symbol, price, stop_price, quantity, type, status, timestamp, side, source, level)
+                 VALUES (?1, 'BTCUSDT', ?2, 0.0, 1.0, 'LIMIT', 'NEW', datetime('now'), 'BUY', ?3, 2)",
+                params![order_id, price, source],
+            ).unwrap();
+        }
+        let level = farthest_order(&db, "BTCUSDT", "BUY").unwrap();
+        assert_eq!((level.order_id, level.level), (2, Some(2)));
+        assert!(farthest_order(&db, "BTCUSDT", "SELL").is_none());
+    }
+}
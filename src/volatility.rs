@@ -0,0 +1,178 @@
+use reqwest::Client;
+use rusqlite::{params, Connection};
+
+use crate::config::VolatilityConfig;
+use crate::{config, events, get_klines, Candle};
+
+/// Pary z wstrzymanymi kupnami po gwałtownym ruchu ceny
+pub fn setup(conn: &Connection) {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS buy_pauses (
+            symbol TEXT PRIMARY KEY,
+            until TEXT NOT NULL,
+            reason TEXT NOT NULL
+        )",
+        [],
+    ).expect("Failed to create buy_pauses table");
+}
+
+/// (do kiedy, powód), jeśli nowe kupna dla pary są wstrzymane
+pub fn buy_pause(db: &Connection, symbol: &str) -> Option<(String, String)> {
+    db.query_row(
+        "SELECT until, reason FROM buy_pauses WHERE symbol = ?1 AND until > datetime('now')",
+        params![symbol],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).ok()
+}
+
+pub fn true_range(candle: &Candle, prev_close: f64) -> f64 {
+    (candle.high - candle.low)
+        .max((candle.high - prev_close).abs())
+        .max((candle.low - prev_close).abs())
+}
+
+/// Wykrywa spadek o `max_drop_pct` w oknie lub skok zakresu ostatniej świecy ponad ATR * mnożnik
+fn detect(candles: &[Candle], settings: &VolatilityConfig) -> Option<String> {
+    let last = candles.last()?;
+
+    if let Some(max_drop) = settings.max_drop_pct {
+        let window = &candles[candles.len().saturating_sub(settings.window_minutes)..];
+        let peak = window.iter().map(|c| c.high).fold(0.0, f64::max);
+        if peak > 0.0 {
+            let drop = (peak - last.close) / peak;
+            if drop >= max_drop {
+                return Some(format!(
+                    "price dropped {:.2}% from {:.8} within {} min (max_drop_pct = {:.2}%)",
+                    drop * 100.0, peak, settings.window_minutes, max_drop * 100.0
+                ));
+            }
+        }
+    }
+
+    if let Some(multiplier) = settings.atr_spike_multiplier {
+        if candles.len() >= settings.atr_period + 2 {
+            let ranges: Vec<f64> = candles.windows(2).map(|pair| true_range(&pair[1], pair[0].close)).collect();
+            let (current, history) = ranges.split_last()?;
+            let history = &history[history.len() - settings.atr_period..];
+            let atr = history.iter().sum::<f64>() / history.len() as f64;
+            if atr > 0.0 && *current > atr * multiplier {
+                return Some(format!(
+                    "1m range {:.8} is {:.1}x ATR({}) {:.8} (atr_spike_multiplier = {:.1})",
+                    current, current / atr, settings.atr_period, atr, multiplier
+                ));
+            }
+        }
+    }
+
+    None
+}
+
+/// Sprawdza zmienność aktywnych gridów i wstrzymuje kupna na `cooldown_minutes` (wywoływane z pętli monitora)
+pub async fn check_active_grids(db: &mut Connection, client: &Client) {
+    let settings = &config::config().volatility;
+    if settings.max_drop_pct.is_none() && settings.atr_spike_multiplier.is_none() {
+        return;
+    }
+
+    let symbols: Vec<String> = {
+        let mut stmt = db.prepare("SELECT DISTINCT symbol FROM capital WHERE is_active = 1 ORDER BY symbol ASC")
+            .expect("Failed to prepare statement");
+        stmt.query_map([], |row| row.get(0))
+            .expect("Failed to query capital pairs")
+            .filter_map(Result::ok)
+            .collect()
+    };
+
+    let limit = settings.window_minutes.max(settings.atr_period + 2);
+    for symbol in symbols {
+        let candles = match get_klines(client, &symbol, "1m", limit).await {
+            Ok(candles) => candles,
+            Err(e) => {
+                println!("⚠️ Volatility check skipped for {}: {}", symbol, e);
+                continue;
+            }
+        };
+        let Some(reason) = detect(&candles, settings) else {
+            continue;
+        };
+
+        let already_paused = buy_pause(db, &symbol).is_some();
+        db.execute(
+            "INSERT INTO buy_pauses (symbol, until, reason) VALUES (?1, datetime('now', ?2), ?3)
+             ON CONFLICT(symbol) DO UPDATE SET until = excluded.until, reason = excluded.reason",
+            params![symbol, format!("+{} minutes", settings.cooldown_minutes), reason],
+        ).expect("Failed to record buy pause");
+
+        if !already_paused {
+            println!("🌪️ New buys for {} paused for {} min: {}", symbol, settings.cooldown_minutes, reason);
+            events::record(db, Some(&symbol), "VOLATILITY_PAUSE", &reason);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candle(high: f64, low: f64, close: f64) -> Candle {
+        Candle { open_time: 0, open: close, high, low, close }
+    }
+
+    /// Spokojny rynek: świece o zakresie 1.0 wokół 100
+    fn calm(count: usize) -> Vec<Candle> {
+        (0..count).map(|_| candle(100.5, 99.5, 100.0)).collect()
+    }
+
+    #[test]
+    fn nothing_detected_when_checks_are_disabled() {
+        let mut candles = calm(20);
+        candles.push(candle(100.0, 50.0, 50.0));
+        assert!(detect(&candles, &VolatilityConfig::default()).is_none());
+    }
+
+    #[test]
+    fn detects_drop_within_the_window() {
+        let settings = VolatilityConfig { max_drop_pct: Some(0.05), window_minutes: 5, ..VolatilityConfig::default() };
+        let mut candles = calm(10);
+        candles.push(candle(100.0, 94.0, 94.0));
+        assert!(detect(&candles, &settings).unwrap().contains("dropped"));
+
+        let mut shallow = calm(10);
+        shallow.push(candle(100.0, 96.0, 96.0));
+        assert!(detect(&shallow, &settings).is_none());
+    }
+
+    #[test]
+    fn drop_outside_the_window_is_ignored() {
+        let settings = VolatilityConfig { max_drop_pct: Some(0.05), window_minutes: 3, ..VolatilityConfig::default() };
+        let mut candles = vec![candle(110.0, 109.0, 110.0)];
+        candles.extend(calm(5));
+        assert!(detect(&candles, &settings).is_none());
+    }
+
+    #[test]
+    fn detects_range_spike_above_atr() {
+        let settings = VolatilityConfig { atr_spike_multiplier: Some(3.0), atr_period: 5, ..VolatilityConfig::default() };
+        let mut candles = calm(8);
+        candles.push(candle(104.0, 99.0, 103.0));
+        assert!(detect(&candles, &settings).unwrap().contains("ATR(5)"));
+
+        let mut moderate = calm(8);
+        moderate.push(candle(102.0, 99.5, 101.0));
+        assert!(detect(&moderate, &settings).is_none());
+    }
+
+    #[test]
+    fn atr_needs_enough_history() {
+        let settings = VolatilityConfig { atr_spike_multiplier: Some(3.0), atr_period: 14, ..VolatilityConfig::default() };
+        let mut candles = calm(5);
+        candles.push(candle(120.0, 99.0, 119.0));
+        assert!(detect(&candles, &settings).is_none());
+    }
+
+    #[test]
+    fn true_range_includes_gap_from_previous_close() {
+        assert_eq!(true_range(&candle(105.0, 103.0, 104.0), 100.0), 5.0);
+        assert_eq!(true_range(&candle(101.0, 99.0, 100.0), 100.0), 2.0);
+    }
+}